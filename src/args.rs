@@ -1,9 +1,44 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 /// Qtile alttab window
 #[derive(Parser, Debug, Clone, Default)]
 #[command(version, about, long_about = None)]
-pub struct Args {}
+pub struct Args {
+    /// Take over from the overlay already running on this display
+    #[arg(long)]
+    pub replace: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Drive the running overlay over its IPC socket
+    Ctl {
+        #[command(subcommand)]
+        action: CtlAction,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtlAction {
+    /// Select the next window, opening the switcher if it is hidden
+    Next,
+    /// Select the previous window, opening the switcher if it is hidden
+    Prev,
+    /// Show the switcher
+    Show,
+    /// Hide the switcher without focusing anything
+    Hide,
+    /// Select the window at INDEX
+    Select { index: usize },
+    /// Print the current overlay state as JSON
+    State,
+    /// Print the effective configuration as JSON
+    Config,
+    /// Print the version of the running instance
+    Version,
+}
 
 #[cfg(test)]
 mod tests {
@@ -12,7 +47,53 @@ mod tests {
 
     #[test]
     fn parses_with_no_args() {
-        assert!(Args::try_parse_from(["qalttab"]).is_ok());
+        let args = Args::try_parse_from(["qalttab"]).unwrap();
+        assert_eq!(args.command, None);
+        assert!(!args.replace);
+    }
+
+    #[test]
+    fn parses_replace_flag() {
+        let args = Args::try_parse_from(["qalttab", "--replace"]).unwrap();
+        assert!(args.replace);
+        assert_eq!(args.command, None);
+    }
+
+    #[test]
+    fn parses_ctl_actions() {
+        for (arg, action) in [
+            ("next", CtlAction::Next),
+            ("prev", CtlAction::Prev),
+            ("show", CtlAction::Show),
+            ("hide", CtlAction::Hide),
+            ("state", CtlAction::State),
+            ("config", CtlAction::Config),
+            ("version", CtlAction::Version),
+        ] {
+            let args = Args::try_parse_from(["qalttab", "ctl", arg]).unwrap();
+            assert_eq!(args.command, Some(Command::Ctl { action }));
+        }
+    }
+
+    #[test]
+    fn parses_ctl_select_index() {
+        let args = Args::try_parse_from(["qalttab", "ctl", "select", "3"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Ctl {
+                action: CtlAction::Select { index: 3 }
+            })
+        );
+    }
+
+    #[test]
+    fn ctl_select_rejects_negative_index() {
+        assert!(Args::try_parse_from(["qalttab", "ctl", "select", "-1"]).is_err());
+    }
+
+    #[test]
+    fn ctl_without_action_is_an_error() {
+        assert!(Args::try_parse_from(["qalttab", "ctl"]).is_err());
     }
 
     #[test]
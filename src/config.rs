@@ -1,4 +1,12 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, bail};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_path_to_error::Segment;
+use tokio::signal::unix::{SignalKind, signal};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Font {
@@ -22,18 +30,21 @@ pub struct FontFamily {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Fonts {
     pub text_font: FontFamily,
     pub icon_font: FontFamily,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
 pub struct WindowSize {
     pub width: f32,
     pub height: f32,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
 pub struct Colors {
     pub bg_color: String,
     pub text_color: String,
@@ -42,6 +53,7 @@ pub struct Colors {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct IconConfig {
     pub themes: Vec<String>,
     pub lookup_icon_size: f32,
@@ -50,6 +62,7 @@ pub struct IconConfig {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Sizes {
     pub group_spacing: f32,
     pub group_rect_stroke_width: f32,
@@ -63,9 +76,37 @@ pub enum Orientation {
 }
 
 #[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
 pub struct UiConfig {
     pub items: Vec<UiItem>,
     pub orientation: Orientation,
+    #[serde(default)]
+    pub layout: Layout,
+    /// Order of the sections in the `grouped` layout.
+    #[serde(default)]
+    pub group_order: GroupOrder,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// One card per window, most recently used first.
+    #[default]
+    #[serde(rename = "list")]
+    List,
+    /// Cards under a header per group (workspace).
+    #[serde(rename = "grouped")]
+    Grouped,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupOrder {
+    /// The group of the most recently used window first.
+    #[default]
+    #[serde(rename = "mru")]
+    Mru,
+    /// By group name, with numbers in numeric order.
+    #[serde(rename = "group")]
+    Group,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -78,81 +119,546 @@ pub enum UiItem {
     GroupName,
     #[serde(rename = "group_label")]
     GroupLabel,
+    /// A preview of the window, see [`ThumbnailConfig`].
+    #[serde(rename = "thumbnail")]
+    Thumbnail,
+}
+
+/// Window previews shown by the `thumbnail` card item.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThumbnailConfig {
+    /// Command printing an image of window `{id}` (or `{hex_id}`) to stdout,
+    /// e.g. a `grim` call. Without one, no previews are captured.
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Largest size a preview is drawn at, in points.
+    #[serde(default = "default_thumbnail_width")]
+    pub width: f32,
+    #[serde(default = "default_thumbnail_height")]
+    pub height: f32,
+    /// Previews older than this are captured again while the overlay is open.
+    #[serde(default = "default_thumbnail_refresh_ms")]
+    pub refresh_ms: u64,
+    /// A capture taking longer than this is abandoned.
+    #[serde(default = "default_thumbnail_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_thumbnail_width() -> f32 {
+    240.0
+}
+
+fn default_thumbnail_height() -> f32 {
+    135.0
+}
+
+fn default_thumbnail_refresh_ms() -> u64 {
+    2000
+}
+
+fn default_thumbnail_timeout_ms() -> u64 {
+    1000
+}
+
+impl Default for ThumbnailConfig {
+    fn default() -> Self {
+        Self {
+            command: Vec::new(),
+            width: default_thumbnail_width(),
+            height: default_thumbnail_height(),
+            refresh_ms: default_thumbnail_refresh_ms(),
+            timeout_ms: default_thumbnail_timeout_ms(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct IpcConfig {
+    /// Largest accepted IPC frame, in bytes. Bigger frames are rejected with an error reply.
+    pub max_frame_size: usize,
+}
+
+impl Default for IpcConfig {
+    fn default() -> Self {
+        Self {
+            max_frame_size: crate::ipc::DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputBackend {
+    /// Read `/dev/input` directly, falling back to libinput if no keyboard is readable.
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "evdev")]
+    Evdev,
+    #[serde(rename = "libinput")]
+    Libinput,
+}
+
+/// A key given either by keycode (`56`) or by name (`KEY_LEFTALT`, `alt`, `super`, ...).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ModifierKey {
+    Code(u16),
+    Name(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputConfig {
+    #[serde(default)]
+    pub backend: InputBackend,
+    /// Releasing any of these keys commits the selection.
+    #[serde(default = "default_release_keys")]
+    pub release_keys: Vec<ModifierKey>,
+}
+
+fn default_release_keys() -> Vec<ModifierKey> {
+    vec![
+        ModifierKey::Name("KEY_LEFTALT".into()),
+        ModifierKey::Name("KEY_RIGHTALT".into()),
+    ]
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            backend: InputBackend::default(),
+            release_keys: default_release_keys(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WmBackend {
+    /// Hyprland or sway when their environment variables are set, Qtile otherwise.
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "qtile")]
+    Qtile,
+    #[serde(rename = "sway")]
+    Sway,
+    #[serde(rename = "hyprland")]
+    Hyprland,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WmConfig {
+    #[serde(default)]
+    pub backend: WmBackend,
+    #[serde(default)]
+    pub overlay: OverlayMode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlayMode {
+    /// A normal window, shown, hidden and placed through the window manager.
+    #[default]
+    #[serde(rename = "window")]
+    Window,
+    /// A wlr-layer-shell surface that qalttab shows and places itself.
+    /// Needs the `layer-shell` build feature.
+    #[serde(rename = "layer_shell")]
+    LayerShell,
+}
+
+/// Where the overlay is shown, see [`crate::placement`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlacementConfig {
+    #[serde(default)]
+    pub screen: ScreenPolicy,
+    /// Screen used by `screen = "index"`, counting from 0 in the window
+    /// manager's order.
+    #[serde(default)]
+    pub screen_index: usize,
+    #[serde(default)]
+    pub anchor: Anchor,
+    /// Moves the overlay right from its anchored position, in pixels.
+    #[serde(default)]
+    pub offset_x: i32,
+    /// Moves the overlay down from its anchored position, in pixels.
+    #[serde(default)]
+    pub offset_y: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScreenPolicy {
+    /// The screen of the focused window.
+    #[default]
+    #[serde(rename = "focused")]
+    Focused,
+    /// The screen under the pointer, where the window manager reports it.
+    #[serde(rename = "pointer")]
+    Pointer,
+    /// The screen of `screen_index`.
+    #[serde(rename = "index")]
+    Index,
+    /// The screen of the selected window, following the selection.
+    #[serde(rename = "selection")]
+    Selection,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchor {
+    #[default]
+    #[serde(rename = "center")]
+    Center,
+    /// Horizontally centred against the top edge.
+    #[serde(rename = "top")]
+    Top,
+    /// Horizontally centred against the bottom edge.
+    #[serde(rename = "bottom")]
+    Bottom,
 }
 
+/// Every section, and every field in it, falls back to its default when it
+/// is missing. [`parse`] goes further and merges the file into the defaults
+/// key by key, so a partial font family works too.
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Config {
     pub fonts: Fonts,
     pub colors: Colors,
     pub icons: IconConfig,
     pub sizes: Sizes,
     pub ui: UiConfig,
+    pub ipc: IpcConfig,
+    pub input: InputConfig,
+    pub wm: WmConfig,
+    pub thumbnails: ThumbnailConfig,
+    pub placement: PlacementConfig,
+}
+
+/// Load `config.yaml`, or fall back to the defaults if it cannot be read.
+/// Writes the defaults out when there is no file yet.
+pub fn load() -> Config {
+    read().unwrap_or_else(|e| {
+        log::warn!("Failed to load config, using the defaults: {e:#}");
+        Config::default()
+    })
+}
+
+/// Where confy keeps `config.yaml`.
+pub fn path() -> anyhow::Result<PathBuf> {
+    Ok(confy::get_configuration_file_path(
+        "qalttab",
+        Some("config"),
+    )?)
+}
+
+/// Load `config.yaml` again, failing instead of falling back to the defaults.
+pub fn reload() -> anyhow::Result<Config> {
+    read()
+}
+
+fn read() -> anyhow::Result<Config> {
+    let path = path()?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let config = Config::default();
+            if let Err(e) = confy::store_path(&path, &config) {
+                log::warn!("Failed to write the default config to {path:?}: {e}");
+            }
+            return Ok(config);
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {path:?}")),
+    };
+    let (config, warnings) = parse(&text).with_context(|| format!("Failed to parse {path:?}"))?;
+    for warning in warnings {
+        log::warn!("{path:?}: {warning}");
+    }
+    log::debug!("Loaded config: {config:#?}");
+    Ok(config)
+}
+
+/// Parse the text of a config file.
+///
+/// Missing keys take their default. So does a key whose value does not fit,
+/// and a colour that is not a hex colour; each gets a warning naming its key
+/// path, as do unknown keys, which are ignored. Only fails if `text` is not
+/// a YAML mapping.
+pub fn parse(text: &str) -> anyhow::Result<(Config, Vec<String>)> {
+    let file: Value = serde_yaml::from_str(text).context("Not valid YAML")?;
+    let defaults = serde_json::to_value(Config::default())?;
+    let mut value = defaults.clone();
+    let mut warnings = Vec::new();
+    match file {
+        Value::Null => {}
+        Value::Object(file) => merge(&mut value, file, &mut Vec::new(), &mut warnings),
+        _ => bail!("Expected a mapping of config sections"),
+    }
+    let mut config = loop {
+        let error = match serde_path_to_error::deserialize::<_, Config>(value.clone()) {
+            Ok(config) => break config,
+            Err(error) => error,
+        };
+        // The keys leading to the bad value; list items fall back as a whole.
+        let keys: Vec<&str> = error
+            .path()
+            .iter()
+            .map_while(|segment| match segment {
+                Segment::Map { key } => Some(key.as_str()),
+                _ => None,
+            })
+            .collect();
+        let default = keys.iter().try_fold(&defaults, |value, key| value.get(key));
+        let current = keys
+            .iter()
+            .try_fold(&mut value, |value, key| value.get_mut(key));
+        match (default, current) {
+            (Some(default), Some(current)) if !keys.is_empty() && *current != *default => {
+                *current = default.clone();
+            }
+            _ => bail!("{}: {}", error.path(), error.inner()),
+        }
+        warnings.push(format!(
+            "{}: {}, using the default for {}",
+            error.path(),
+            error.inner(),
+            keys.join(".")
+        ));
+    };
+    check_colors(&mut config.colors, &mut warnings);
+    Ok((config, warnings))
+}
+
+/// Overwrite `defaults` with `file`, key by key. Keys the defaults do not
+/// have are dropped with a warning.
+fn merge(
+    defaults: &mut Value,
+    file: serde_json::Map<String, Value>,
+    path: &mut Vec<String>,
+    warnings: &mut Vec<String>,
+) {
+    for (key, value) in file {
+        path.push(key);
+        let key = path.last().unwrap();
+        match (defaults.get_mut(key), value) {
+            (None, _) => warnings.push(format!("{}: unknown key, ignored", path.join("."))),
+            (Some(default @ Value::Object(_)), Value::Object(value)) => {
+                merge(default, value, path, warnings)
+            }
+            (Some(default), value) => *default = value,
+        }
+        path.pop();
+    }
+}
+
+/// Replace colours egui cannot parse with their defaults.
+fn check_colors(colors: &mut Colors, warnings: &mut Vec<String>) {
+    let defaults = Colors::default();
+    for (key, color, default) in [
+        ("bg_color", &mut colors.bg_color, defaults.bg_color),
+        ("text_color", &mut colors.text_color, defaults.text_color),
+        (
+            "normal_group_color",
+            &mut colors.normal_group_color,
+            defaults.normal_group_color,
+        ),
+        (
+            "group_hover_color",
+            &mut colors.group_hover_color,
+            defaults.group_hover_color,
+        ),
+    ] {
+        if egui::Color32::from_hex(color.as_str()).is_err() {
+            warnings.push(format!(
+                "colors.{key}: {color:?} is not a hex colour, using {default}"
+            ));
+            *color = default;
+        }
+    }
+}
+
+/// The config in use, replaced as a whole when it is reloaded. Readers keep
+/// the snapshot they got for as long as they need a consistent view.
+#[derive(Clone, Debug, Default)]
+pub struct LiveConfig(Arc<Mutex<Arc<Config>>>);
+
+impl LiveConfig {
+    pub fn new(config: Config) -> Self {
+        Self(Arc::new(Mutex::new(Arc::new(config))))
+    }
+
+    pub fn get(&self) -> Arc<Config> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Switch to `loaded`, or keep the current config if it failed to load.
+    pub fn apply(&self, loaded: anyhow::Result<Config>) -> anyhow::Result<()> {
+        match loaded {
+            Ok(config) => {
+                log::info!("Reloaded config");
+                log::debug!("Reloaded config: {config:#?}");
+                *self.0.lock().unwrap() = Arc::new(config);
+                Ok(())
+            }
+            Err(e) => {
+                log::warn!("Keeping the previous config: {e:#}");
+                Err(e)
+            }
+        }
+    }
+
+    pub fn reload(&self) -> anyhow::Result<()> {
+        self.apply(reload())
+    }
+}
+
+/// How often the config file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+fn modified() -> Option<SystemTime> {
+    std::fs::metadata(path().ok()?).ok()?.modified().ok()
+}
+
+/// Reload `live` when the config file changes or on SIGHUP, calling
+/// `on_reload` after each successful reload. Runs forever.
+pub async fn watch(live: LiveConfig, on_reload: impl Fn() + Send + 'static) {
+    let mut hangup = signal(SignalKind::hangup())
+        .inspect_err(|e| log::warn!("Not reloading the config on SIGHUP: {e}"))
+        .ok();
+    let mut last_modified = modified();
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        let reason = tokio::select! {
+            Some(()) = async {
+                match &mut hangup {
+                    Some(hangup) => hangup.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => "SIGHUP",
+            _ = interval.tick() => {
+                let modified = modified();
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                "a change to the config file"
+            }
+        };
+        log::info!("Reloading the config on {reason}");
+        let live = live.clone();
+        let reloaded = tokio::task::spawn_blocking(move || live.reload()).await;
+        if matches!(reloaded, Ok(Ok(()))) {
+            on_reload();
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
-        let font_size = 20.0;
-        let lookup_icon_size = 48.0;
         Self {
-            fonts: Fonts {
-                text_font: FontFamily {
-                    family_name: "Caskaydia Cove".into(),
-                    fonts: vec![Font::new(
-                        "Caskaydia Cove Regular",
-                        "/usr/share/fonts/OTF/Caskaydia Cove Nerd Font Complete Regular.otf",
-                    )],
-                    size: font_size,
-                },
-                icon_font: FontFamily {
-                    family_name: "Font Awesome".into(),
-                    fonts: vec![
-                        Font::new("fa-brands", "/usr/share/fonts/TTF/fa-brands-400.ttf"),
-                        Font::new("fa-regular", "/usr/share/fonts/TTF/fa-regular-400.ttf"),
-                        Font::new(
-                            "Font Awesome Solid",
-                            "/usr/share/fonts/TTF/fa-solid-900.ttf",
-                        ),
-                    ],
-                    size: font_size,
-                },
-            },
-            sizes: Sizes {
-                group_spacing: 8.0,
-                group_rect_stroke_width: 3.0,
-                window_size: WindowSize {
-                    width: 400.0,
-                    height: 1000.0,
-                },
-            },
-            icons: IconConfig {
-                themes: vec![
-                    "Papirus".into(),
-                    "Papirus-Dark".into(),
-                    "Papirus-Light".into(),
-                ],
-                lookup_icon_size,
-                visible_icon_size: lookup_icon_size,
-                default_icon: "./assets/default.svg".into(),
-            },
-            colors: Colors {
-                bg_color: "#1E1E2E".into(),
-                group_hover_color: "#B4BEFE".into(),
-                normal_group_color: "#313244".into(),
-                text_color: "#6C7086".into(),
+            fonts: Fonts::default(),
+            colors: Colors::default(),
+            icons: IconConfig::default(),
+            sizes: Sizes::default(),
+            ui: UiConfig::default(),
+            ipc: IpcConfig::default(),
+            input: InputConfig::default(),
+            wm: WmConfig::default(),
+            thumbnails: ThumbnailConfig::default(),
+            placement: PlacementConfig::default(),
+        }
+    }
+}
+
+const DEFAULT_FONT_SIZE: f32 = 20.0;
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Self {
+            text_font: FontFamily {
+                family_name: "Caskaydia Cove".into(),
+                fonts: vec![Font::new(
+                    "Caskaydia Cove Regular",
+                    "/usr/share/fonts/OTF/Caskaydia Cove Nerd Font Complete Regular.otf",
+                )],
+                size: DEFAULT_FONT_SIZE,
             },
-            ui: UiConfig {
-                items: vec![
-                    UiItem::Icon,
-                    UiItem::Name,
-                    UiItem::GroupName,
-                    UiItem::GroupLabel,
+            icon_font: FontFamily {
+                family_name: "Font Awesome".into(),
+                fonts: vec![
+                    Font::new("fa-brands", "/usr/share/fonts/TTF/fa-brands-400.ttf"),
+                    Font::new("fa-regular", "/usr/share/fonts/TTF/fa-regular-400.ttf"),
+                    Font::new(
+                        "Font Awesome Solid",
+                        "/usr/share/fonts/TTF/fa-solid-900.ttf",
+                    ),
                 ],
-                orientation: Orientation::Vertical,
+                size: DEFAULT_FONT_SIZE,
             },
         }
     }
 }
 
+impl Default for Sizes {
+    fn default() -> Self {
+        Self {
+            group_spacing: 8.0,
+            group_rect_stroke_width: 3.0,
+            window_size: WindowSize::default(),
+        }
+    }
+}
+
+impl Default for WindowSize {
+    fn default() -> Self {
+        Self {
+            width: 400.0,
+            height: 1000.0,
+        }
+    }
+}
+
+impl Default for IconConfig {
+    fn default() -> Self {
+        let lookup_icon_size = 48.0;
+        Self {
+            themes: vec![
+                "Papirus".into(),
+                "Papirus-Dark".into(),
+                "Papirus-Light".into(),
+            ],
+            lookup_icon_size,
+            visible_icon_size: lookup_icon_size,
+            default_icon: "./assets/default.svg".into(),
+        }
+    }
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            bg_color: "#1E1E2E".into(),
+            group_hover_color: "#B4BEFE".into(),
+            normal_group_color: "#313244".into(),
+            text_color: "#6C7086".into(),
+        }
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            items: vec![
+                UiItem::Icon,
+                UiItem::Name,
+                UiItem::GroupName,
+                UiItem::GroupLabel,
+            ],
+            orientation: Orientation::Vertical,
+            layout: Layout::default(),
+            group_order: GroupOrder::default(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -296,10 +802,176 @@ mod tests {
     }
 
     #[test]
-    fn config_missing_required_field_fails() {
-        // Missing all but one field — must fail.
-        let json = r##"{"colors":{"bg_color":"#000","text_color":"#fff","normal_group_color":"#111","group_hover_color":"#222"}}"##;
-        assert!(serde_json::from_str::<Config>(json).is_err());
+    fn config_missing_fields_fall_back_to_defaults() {
+        let json = r##"{"colors":{"bg_color":"#000"},"sizes":{"window_size":{"width":500}}}"##;
+        let cfg: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(cfg.colors.bg_color, "#000");
+        assert_eq!(cfg.colors.text_color, Config::default().colors.text_color);
+        assert_eq!(cfg.sizes.window_size.width, 500.0);
+        assert_eq!(cfg.sizes.window_size.height, 1000.0);
+        assert_eq!(cfg.fonts.icon_font.family_name, "Font Awesome");
+    }
+
+    #[test]
+    fn parse_merges_a_partial_file_into_the_defaults() {
+        let (cfg, warnings) = parse("fonts:\n  icon_font:\n    size: 14\n").unwrap();
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(cfg.fonts.icon_font.size, 14.0);
+        assert_eq!(cfg.fonts.icon_font.family_name, "Font Awesome");
+        assert_eq!(cfg.fonts.icon_font.fonts.len(), 3);
+        assert_eq!(cfg.fonts.text_font.family_name, "Caskaydia Cove");
+    }
+
+    #[test]
+    fn parse_an_empty_file_gives_the_defaults() {
+        let (cfg, warnings) = parse("").unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(cfg.colors.bg_color, "#1E1E2E");
+    }
+
+    #[test]
+    fn parse_replaces_bad_values_and_names_their_keys() {
+        let text = "sizes:\n  group_spacing: wide\n  group_rect_stroke_width: 5\nui:\n  items: [icon, nmae]\n";
+        let (cfg, warnings) = parse(text).unwrap();
+        assert_eq!(cfg.sizes.group_spacing, 8.0);
+        assert_eq!(cfg.sizes.group_rect_stroke_width, 5.0);
+        assert_eq!(cfg.ui.items, Config::default().ui.items);
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(
+            warnings[0].starts_with("sizes.group_spacing: "),
+            "{warnings:?}"
+        );
+        assert!(warnings[1].starts_with("ui.items[1]: "), "{warnings:?}");
+    }
+
+    #[test]
+    fn parse_warns_about_unknown_keys() {
+        let (cfg, warnings) = parse("colors:\n  bg_colour: \"#000000\"\n").unwrap();
+        assert_eq!(cfg.colors.bg_color, "#1E1E2E");
+        assert_eq!(warnings, ["colors.bg_colour: unknown key, ignored"]);
+    }
+
+    #[test]
+    fn parse_replaces_invalid_hex_colours() {
+        let (cfg, warnings) =
+            parse("colors:\n  bg_color: \"#12345\"\n  text_color: \"#ffffff\"\n").unwrap();
+        assert_eq!(cfg.colors.bg_color, "#1E1E2E");
+        assert_eq!(cfg.colors.text_color, "#ffffff");
+        assert_eq!(
+            warnings,
+            [r##"colors.bg_color: "#12345" is not a hex colour, using #1E1E2E"##]
+        );
+    }
+
+    #[test]
+    fn parse_fails_on_text_that_is_not_a_mapping() {
+        assert!(parse("colors: [").is_err());
+        assert!(parse("- colors").is_err());
+    }
+
+    #[test]
+    fn config_without_ipc_section_uses_default_frame_size() {
+        let cfg = Config::default();
+        let mut value = serde_json::to_value(&cfg).unwrap();
+        value.as_object_mut().unwrap().remove("ipc");
+        let decoded: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            decoded.ipc.max_frame_size,
+            crate::ipc::DEFAULT_MAX_FRAME_SIZE
+        );
+    }
+
+    #[test]
+    fn config_without_input_section_uses_auto_backend() {
+        let cfg = Config::default();
+        let mut value = serde_json::to_value(&cfg).unwrap();
+        value.as_object_mut().unwrap().remove("input");
+        let decoded: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.input.backend, InputBackend::Auto);
+    }
+
+    #[test]
+    fn release_keys_accept_names_and_codes() {
+        let input: InputConfig =
+            serde_json::from_str(r#"{"release_keys": ["super", 29, "KEY_RIGHTCTRL"]}"#).unwrap();
+        assert_eq!(input.backend, InputBackend::Auto);
+        assert_eq!(
+            input.release_keys,
+            vec![
+                ModifierKey::Name("super".into()),
+                ModifierKey::Code(29),
+                ModifierKey::Name("KEY_RIGHTCTRL".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn release_keys_default_to_alt() {
+        let input: InputConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(input.release_keys, default_release_keys());
+    }
+
+    #[test]
+    fn input_backend_deserializes_from_snake_case_strings() {
+        assert_eq!(
+            serde_json::from_str::<InputBackend>(r#""evdev""#).unwrap(),
+            InputBackend::Evdev
+        );
+        assert_eq!(
+            serde_json::from_str::<InputBackend>(r#""libinput""#).unwrap(),
+            InputBackend::Libinput
+        );
+    }
+
+    #[test]
+    fn wm_backend_defaults_to_auto_and_accepts_sway() {
+        let cfg = Config::default();
+        let mut value = serde_json::to_value(&cfg).unwrap();
+        value.as_object_mut().unwrap().remove("wm");
+        let decoded: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.wm.backend, WmBackend::Auto);
+        let wm: WmConfig = serde_json::from_str(r#"{"backend": "sway"}"#).unwrap();
+        assert_eq!(wm.backend, WmBackend::Sway);
+        let wm: WmConfig = serde_json::from_str(r#"{"backend": "hyprland"}"#).unwrap();
+        assert_eq!(wm.backend, WmBackend::Hyprland);
+        assert_eq!(wm.overlay, OverlayMode::Window);
+        let wm: WmConfig = serde_json::from_str(r#"{"overlay": "layer_shell"}"#).unwrap();
+        assert_eq!(wm.overlay, OverlayMode::LayerShell);
+    }
+
+    #[test]
+    fn thumbnails_are_off_by_default_and_fill_in_missing_fields() {
+        let cfg = Config::default();
+        assert!(cfg.thumbnails.command.is_empty());
+        assert!(!cfg.ui.items.contains(&UiItem::Thumbnail));
+        let thumbnails: ThumbnailConfig =
+            serde_json::from_str(r#"{"command": ["grim", "-"], "width": 320}"#).unwrap();
+        assert_eq!(thumbnails.command, ["grim", "-"]);
+        assert_eq!(thumbnails.width, 320.0);
+        assert_eq!(thumbnails.refresh_ms, 2000);
+        assert_eq!(
+            serde_json::from_str::<UiItem>(r#""thumbnail""#).unwrap(),
+            UiItem::Thumbnail
+        );
+    }
+
+    #[test]
+    fn placement_defaults_to_the_centre_of_the_focused_screen() {
+        let cfg = Config::default();
+        let mut value = serde_json::to_value(&cfg).unwrap();
+        value.as_object_mut().unwrap().remove("placement");
+        let decoded: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.placement.screen, ScreenPolicy::Focused);
+        assert_eq!(decoded.placement.anchor, Anchor::Center);
+        let placement: PlacementConfig = serde_json::from_str(
+            r#"{"screen": "index", "screen_index": 2, "anchor": "top", "offset_y": 40}"#,
+        )
+        .unwrap();
+        assert_eq!(placement.screen, ScreenPolicy::Index);
+        assert_eq!(placement.screen_index, 2);
+        assert_eq!(placement.anchor, Anchor::Top);
+        assert_eq!((placement.offset_x, placement.offset_y), (0, 40));
+        assert!(serde_json::from_str::<ScreenPolicy>(r#""mouse""#).is_err());
     }
 
     #[test]
@@ -320,11 +992,23 @@ mod tests {
         let ui = UiConfig {
             items: vec![],
             orientation: Orientation::Horizontal,
+            layout: Layout::Grouped,
+            group_order: GroupOrder::Group,
         };
         let json = serde_json::to_string(&ui).unwrap();
         let decoded: UiConfig = serde_json::from_str(&json).unwrap();
         assert!(decoded.items.is_empty());
         assert_eq!(decoded.orientation, Orientation::Horizontal);
+        assert_eq!(decoded.layout, Layout::Grouped);
+        assert_eq!(decoded.group_order, GroupOrder::Group);
+    }
+
+    #[test]
+    fn ui_layout_defaults_to_an_mru_list() {
+        let ui: UiConfig =
+            serde_json::from_str(r#"{"items": [], "orientation": "Vertical"}"#).unwrap();
+        assert_eq!(ui.layout, Layout::List);
+        assert_eq!(ui.group_order, GroupOrder::Mru);
     }
 
     #[test]
@@ -352,4 +1036,21 @@ mod tests {
         let json = serde_json::to_string(&UiItem::Icon).unwrap();
         assert_eq!(json, "\"icon\"");
     }
+
+    #[test]
+    fn live_config_swaps_on_reload_and_keeps_the_old_one_on_error() {
+        let live = LiveConfig::default();
+        let before = live.get();
+
+        let mut changed = Config::default();
+        changed.colors.bg_color = "#000000".into();
+        live.apply(Ok(changed)).unwrap();
+        let after = live.get();
+        assert!(!Arc::ptr_eq(&before, &after));
+        assert_eq!(after.colors.bg_color, "#000000");
+        assert_eq!(before.colors.bg_color, Config::default().colors.bg_color);
+
+        assert!(live.apply(Err(anyhow::anyhow!("bad yaml"))).is_err());
+        assert!(Arc::ptr_eq(&live.get(), &after));
+    }
 }
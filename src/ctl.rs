@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use anyhow::{Context, bail};
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::UnixStream;
+
+use crate::args::CtlAction;
+use crate::ipc::{get_socket_path, write_frame};
+
+/// Build the IPC request sent for `action`.
+pub fn request_for(action: CtlAction) -> Value {
+    let message_type = match action {
+        CtlAction::Next => "next",
+        CtlAction::Prev => "prev",
+        CtlAction::Show => "show",
+        CtlAction::Hide => "hide",
+        CtlAction::Select { .. } => "select",
+        CtlAction::State => "get_state",
+        CtlAction::Config => "get_config",
+        CtlAction::Version => "get_version",
+    };
+    let mut request = json!({ "id": 1, "message_type": message_type });
+    if let CtlAction::Select { index } = action {
+        request["index"] = json!(index);
+    }
+    request
+}
+
+/// Send one request to the running instance and wait for its reply.
+///
+/// Returns the reply's `result`, if any. Fails if the reply status is not `ok`.
+pub async fn send_request(
+    request: &Value,
+    custom_socket_path: Option<&Path>,
+) -> anyhow::Result<Option<Value>> {
+    let socket_path = get_socket_path(custom_socket_path);
+    let mut stream = UnixStream::connect(&socket_path)
+        .await
+        .with_context(|| format!("Could not connect to {socket_path:?}, is qalttab running?"))?;
+    let (reader, mut writer) = stream.split();
+    write_frame(&mut writer, request).await?;
+
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+    let reply: Value = serde_json::from_str(&line)
+        .with_context(|| format!("Invalid reply from qalttab: {line:?}"))?;
+
+    match reply.get("status").and_then(Value::as_str) {
+        Some("ok") => Ok(reply.get("result").cloned()),
+        status => bail!(
+            "{}: {}",
+            status.unwrap_or("no status"),
+            reply
+                .get("error")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error")
+        ),
+    }
+}
+
+/// Run `qalttab ctl <action>`, printing the result of queries as JSON.
+pub async fn run(action: CtlAction, custom_socket_path: Option<&Path>) -> anyhow::Result<()> {
+    if let Some(result) = send_request(&request_for(action), custom_socket_path).await? {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_actions_map_to_message_types() {
+        for (action, message_type) in [
+            (CtlAction::Next, "next"),
+            (CtlAction::Prev, "prev"),
+            (CtlAction::Show, "show"),
+            (CtlAction::Hide, "hide"),
+            (CtlAction::State, "get_state"),
+            (CtlAction::Config, "get_config"),
+            (CtlAction::Version, "get_version"),
+        ] {
+            let request = request_for(action);
+            assert_eq!(request["message_type"], message_type);
+            assert!(request.get("index").is_none());
+        }
+    }
+
+    #[test]
+    fn select_request_carries_index() {
+        let request = request_for(CtlAction::Select { index: 4 });
+        assert_eq!(request["message_type"], "select");
+        assert_eq!(request["index"], 4);
+    }
+
+    #[test]
+    fn requests_carry_an_id() {
+        assert!(request_for(CtlAction::Next).get("id").is_some());
+    }
+}
@@ -0,0 +1,371 @@
+//! Minimal reader for Linux evdev keyboard devices (`/dev/input/event*`).
+//!
+//! Only what qalttab needs: find keyboards, decode `EV_KEY` events and follow
+//! devices as they are plugged in and removed. Devices are read without
+//! blocking, so dropping a reader never waits for the next key press.
+
+use std::{
+    collections::HashSet,
+    ffi::CString,
+    fs::{File, OpenOptions},
+    io::{self, Read},
+    os::fd::{FromRawFd, OwnedFd},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::bail;
+use tokio::io::Interest;
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinSet;
+
+pub const EV_KEY: u16 = 0x01;
+pub const KEY_A: u16 = 30;
+pub const KEY_SPACE: u16 = 57;
+pub const KEY_LEFTCTRL: u16 = 29;
+pub const KEY_LEFTSHIFT: u16 = 42;
+pub const KEY_RIGHTSHIFT: u16 = 54;
+pub const KEY_LEFTALT: u16 = 56;
+pub const KEY_RIGHTCTRL: u16 = 97;
+pub const KEY_RIGHTALT: u16 = 100;
+pub const KEY_LEFTMETA: u16 = 125;
+pub const KEY_RIGHTMETA: u16 = 126;
+
+/// Modifier keys by their `linux/input-event-codes.h` name, as printed by libinput.
+pub const MODIFIER_KEYS: &[(&str, u16)] = &[
+    ("KEY_LEFTCTRL", KEY_LEFTCTRL),
+    ("KEY_LEFTSHIFT", KEY_LEFTSHIFT),
+    ("KEY_RIGHTSHIFT", KEY_RIGHTSHIFT),
+    ("KEY_LEFTALT", KEY_LEFTALT),
+    ("KEY_RIGHTCTRL", KEY_RIGHTCTRL),
+    ("KEY_RIGHTALT", KEY_RIGHTALT),
+    ("KEY_LEFTMETA", KEY_LEFTMETA),
+    ("KEY_RIGHTMETA", KEY_RIGHTMETA),
+];
+
+/// Size of the kernel's `struct input_event`: a `struct timeval` followed by
+/// `__u16 type`, `__u16 code` and `__s32 value`.
+pub const INPUT_EVENT_SIZE: usize = 2 * size_of::<usize>() + 8;
+
+const INPUT_DIR: &str = "/dev/input";
+const SYSFS_INPUT_DIR: &str = "/sys/class/input";
+/// How often `/dev/input` is rescanned when inotify is not available.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Released,
+    Pressed,
+    Repeated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: u16,
+    pub state: KeyState,
+}
+
+/// Decode one raw `struct input_event`. Returns `None` for anything but key events.
+pub fn decode_event(raw: &[u8]) -> Option<KeyEvent> {
+    if raw.len() != INPUT_EVENT_SIZE {
+        return None;
+    }
+    let offset = INPUT_EVENT_SIZE - 8;
+    let kind = u16::from_ne_bytes([raw[offset], raw[offset + 1]]);
+    let code = u16::from_ne_bytes([raw[offset + 2], raw[offset + 3]]);
+    let value = i32::from_ne_bytes([
+        raw[offset + 4],
+        raw[offset + 5],
+        raw[offset + 6],
+        raw[offset + 7],
+    ]);
+    if kind != EV_KEY {
+        return None;
+    }
+    let state = match value {
+        0 => KeyState::Released,
+        1 => KeyState::Pressed,
+        2 => KeyState::Repeated,
+        _ => return None,
+    };
+    Some(KeyEvent { code, state })
+}
+
+/// Check a bit in a sysfs capability bitmask such as
+/// `/sys/class/input/event3/device/capabilities/key`.
+///
+/// The mask is a list of hex words, most significant first, each as wide as
+/// the kernel's `long`.
+pub fn bitmask_has(mask: &str, bit: u16) -> bool {
+    let word_bits = usize::BITS as usize;
+    let words: Vec<&str> = mask.split_whitespace().rev().collect();
+    let index = bit as usize / word_bits;
+    words
+        .get(index)
+        .and_then(|word| u64::from_str_radix(word, 16).ok())
+        .is_some_and(|word| word & (1 << (bit as usize % word_bits)) != 0)
+}
+
+/// Whether a device's key capability mask looks like a keyboard rather than
+/// a mouse, a power button or a lid switch.
+pub fn is_keyboard(key_capabilities: &str) -> bool {
+    bitmask_has(key_capabilities, KEY_A) && bitmask_has(key_capabilities, KEY_SPACE)
+}
+
+/// List the keyboard event devices currently present.
+pub fn keyboard_devices() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(INPUT_DIR) else {
+        return Vec::new();
+    };
+    let mut devices: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("event"))
+        })
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default();
+            let caps = Path::new(SYSFS_INPUT_DIR)
+                .join(name)
+                .join("device/capabilities/key");
+            std::fs::read_to_string(caps).is_ok_and(|mask| is_keyboard(&mask))
+        })
+        .collect();
+    devices.sort();
+    devices
+}
+
+/// Keyboards we are allowed to open. Empty if the user is not in the `input` group.
+pub fn readable_keyboards() -> Vec<PathBuf> {
+    keyboard_devices()
+        .into_iter()
+        .filter(|path| std::fs::File::open(path).is_ok())
+        .collect()
+}
+
+/// Forward key events from every keyboard to `tx`, picking up keyboards that
+/// are plugged in later and dropping the ones that disappear.
+///
+/// The readers are owned by this future: dropping or aborting it stops them.
+/// Fails if no keyboard can be opened at startup.
+pub async fn watch_keyboards(tx: UnboundedSender<KeyEvent>) -> anyhow::Result<()> {
+    let hotplug = Hotplug::watch(INPUT_DIR)
+        .inspect_err(|e| {
+            log::warn!("Cannot watch {INPUT_DIR}, polling it for new keyboards: {e}");
+        })
+        .ok();
+    let mut readers = JoinSet::new();
+    let mut active = HashSet::new();
+    let mut first_scan = true;
+    let mut rescan = true;
+    loop {
+        if rescan {
+            for path in keyboard_devices() {
+                if active.contains(&path) {
+                    continue;
+                }
+                let device = match open_device(&path) {
+                    Ok(device) => device,
+                    Err(e) => {
+                        log::debug!("Cannot open keyboard {path:?}: {e}");
+                        continue;
+                    }
+                };
+                log::info!("Listening for keys on {path:?}");
+                active.insert(path.clone());
+                let tx = tx.clone();
+                readers.spawn(async move {
+                    if let Err(e) = read_device(device, &tx).await {
+                        log::info!("Stopped reading {path:?}: {e}");
+                    }
+                    path
+                });
+            }
+            if first_scan && active.is_empty() {
+                bail!("no readable keyboard found in {INPUT_DIR}");
+            }
+            first_scan = false;
+        }
+        rescan = tokio::select! {
+            _ = tx.closed() => return Ok(()),
+            Some(stopped) = readers.join_next() => {
+                if let Ok(path) = stopped {
+                    active.remove(&path);
+                }
+                false
+            }
+            changed = async {
+                match &hotplug {
+                    Some(hotplug) => hotplug.changed().await,
+                    None => {
+                        tokio::time::sleep(RESCAN_INTERVAL).await;
+                        Ok(())
+                    }
+                }
+            } => {
+                changed?;
+                true
+            }
+        };
+    }
+}
+
+/// Open an event device for reading without blocking.
+fn open_device(path: &Path) -> io::Result<AsyncFd<File>> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+        .open(path)?;
+    AsyncFd::with_interest(file, Interest::READABLE)
+}
+
+async fn read_device(device: AsyncFd<File>, tx: &UnboundedSender<KeyEvent>) -> anyhow::Result<()> {
+    let mut buf = vec![0; INPUT_EVENT_SIZE * 64];
+    loop {
+        let mut guard = device.readable().await?;
+        let bytes_read = match guard.try_io(|file| file.get_ref().read(&mut buf)) {
+            Ok(result) => result?,
+            Err(_would_block) => continue,
+        };
+        if bytes_read == 0 {
+            bail!("device closed");
+        }
+        for raw in buf[..bytes_read].chunks_exact(INPUT_EVENT_SIZE) {
+            if let Some(event) = decode_event(raw) {
+                tx.send(event)?;
+            }
+        }
+    }
+}
+
+/// An inotify watch on a directory, for devices that appear in it or whose
+/// permissions change, as udev does right after creating a node.
+struct Hotplug {
+    inotify: AsyncFd<File>,
+}
+
+impl Hotplug {
+    fn watch(dir: &str) -> io::Result<Self> {
+        // SAFETY: inotify_init1 takes no pointers.
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just returned by inotify_init1 and is owned by nobody else.
+        let inotify = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        let dir = CString::new(dir)?;
+        let mask = libc::IN_CREATE | libc::IN_ATTRIB;
+        // SAFETY: `dir` is a valid C string and `fd` is still open.
+        if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), mask) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            inotify: AsyncFd::with_interest(inotify, Interest::READABLE)?,
+        })
+    }
+
+    /// Wait for the next batch of changes. What changed does not matter: the
+    /// caller rescans the directory.
+    async fn changed(&self) -> io::Result<()> {
+        let mut buf = [0; 4096];
+        loop {
+            let mut guard = self.inotify.readable().await?;
+            match guard.try_io(|inotify| inotify.get_ref().read(&mut buf)) {
+                Ok(result) => return result.map(|_| ()),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_event(kind: u16, code: u16, value: i32) -> Vec<u8> {
+        let mut raw = vec![0; INPUT_EVENT_SIZE - 8];
+        raw.extend_from_slice(&kind.to_ne_bytes());
+        raw.extend_from_slice(&code.to_ne_bytes());
+        raw.extend_from_slice(&value.to_ne_bytes());
+        raw
+    }
+
+    #[test]
+    fn decodes_key_release() {
+        assert_eq!(
+            decode_event(&raw_event(EV_KEY, KEY_LEFTALT, 0)),
+            Some(KeyEvent {
+                code: KEY_LEFTALT,
+                state: KeyState::Released
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_key_press_and_repeat() {
+        assert_eq!(
+            decode_event(&raw_event(EV_KEY, KEY_RIGHTALT, 1)).map(|e| e.state),
+            Some(KeyState::Pressed)
+        );
+        assert_eq!(
+            decode_event(&raw_event(EV_KEY, KEY_RIGHTALT, 2)).map(|e| e.state),
+            Some(KeyState::Repeated)
+        );
+    }
+
+    #[test]
+    fn ignores_non_key_events() {
+        // EV_SYN and EV_MSC
+        assert_eq!(decode_event(&raw_event(0x00, 0, 0)), None);
+        assert_eq!(decode_event(&raw_event(0x04, 4, 56)), None);
+    }
+
+    #[test]
+    fn ignores_truncated_events() {
+        let raw = raw_event(EV_KEY, KEY_LEFTALT, 0);
+        assert_eq!(decode_event(&raw[..raw.len() - 1]), None);
+    }
+
+    #[test]
+    fn bitmask_checks_bits_across_words() {
+        let mask = if usize::BITS == 64 {
+            "1 0 0000000000000002"
+        } else {
+            "1 0 0 0 00000002"
+        };
+        assert!(bitmask_has(mask, 1));
+        assert!(!bitmask_has(mask, 0));
+        assert!(bitmask_has(
+            mask,
+            (usize::BITS * if usize::BITS == 64 { 2 } else { 4 }) as u16
+        ));
+        assert!(!bitmask_has(mask, 1000));
+    }
+
+    #[test]
+    fn full_keyboard_is_detected() {
+        if usize::BITS != 64 {
+            return;
+        }
+        let mask = "1000000000007 ff9f207ac14057ff febeffdfffefffff fffffffffffffffe";
+        assert!(is_keyboard(mask));
+    }
+
+    #[test]
+    fn power_button_is_not_a_keyboard() {
+        if usize::BITS != 64 {
+            return;
+        }
+        assert!(!is_keyboard("10000000000000 0"));
+    }
+
+    #[test]
+    fn empty_mask_is_not_a_keyboard() {
+        assert!(!is_keyboard("0"));
+        assert!(!is_keyboard(""));
+    }
+}
@@ -0,0 +1,495 @@
+//! Hyprland backend, using the compositor's two local sockets.
+//!
+//! Requests such as `j/clients` or `dispatch focuswindow address:0x...` go to
+//! `.socket.sock`, one per connection; the reply is everything read until
+//! Hyprland closes it. `.socket2.sock` streams events as `name>>data` lines.
+//! Our own window is hidden on a special workspace.
+
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::{PlacementConfig, ScreenPolicy};
+use crate::placement::{ScreenGeometry, pick_screen};
+use crate::ui::{AppEvent, WindowInfo};
+use crate::wm::{FocusHistory, FocusReporter, OVERLAY_NAME, WindowManager, WmFuture, WmOp};
+
+/// Special workspace our window waits on while hidden.
+const HIDDEN_WORKSPACE: &str = "special:qalttab";
+
+/// The parts of a `j/clients` entry we use.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Client {
+    pub address: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub class: String,
+    #[serde(default)]
+    pub workspace: Workspace,
+    /// 0 for the focused window, counting up from there.
+    #[serde(default, rename = "focusHistoryID")]
+    pub focus_history_id: i64,
+    #[serde(default)]
+    pub at: [i32; 2],
+    #[serde(default)]
+    pub size: [i32; 2],
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Workspace {
+    #[serde(default)]
+    pub id: i64,
+    #[serde(default)]
+    pub name: String,
+}
+
+impl Client {
+    fn geometry(&self) -> ScreenGeometry {
+        let ([x, y], [width, height]) = (self.at, self.size);
+        ScreenGeometry {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        self.title == OVERLAY_NAME || self.class == OVERLAY_NAME
+    }
+
+    fn info(&self) -> Option<WindowInfo> {
+        Some(WindowInfo {
+            id: parse_address(&self.address)?,
+            name: self.title.clone(),
+            class: self.class.clone(),
+            group_name: self.workspace.name.clone(),
+            ..WindowInfo::default()
+        })
+    }
+}
+
+/// The parts of a `j/monitors` entry we use.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Monitor {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub focused: bool,
+    #[serde(rename = "activeWorkspace")]
+    pub active_workspace: Workspace,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl Monitor {
+    /// Position and size in layout coordinates, which divide out the scale.
+    pub fn geometry(&self) -> ScreenGeometry {
+        let scaled = |pixels: i32| (f64::from(pixels) / self.scale).round() as i32;
+        ScreenGeometry {
+            x: self.x,
+            y: self.y,
+            width: scaled(self.width),
+            height: scaled(self.height),
+        }
+    }
+}
+
+/// Window addresses are hex, with or without the `0x` Hyprland puts on them in JSON.
+pub fn parse_address(address: &str) -> Option<u64> {
+    let hex = address.strip_prefix("0x").unwrap_or(address);
+    u64::from_str_radix(hex, 16).ok()
+}
+
+fn address(wid: u64) -> String {
+    format!("address:0x{wid:x}")
+}
+
+/// Our window id as stored in the shared state, which keeps it in decimal.
+fn overlay_address(wid: impl Display) -> anyhow::Result<String> {
+    let wid = wid.to_string();
+    Ok(address(
+        wid.parse()
+            .with_context(|| format!("invalid window id {wid:?}"))?,
+    ))
+}
+
+/// `j/cursorpos`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct CursorPosition {
+    x: i32,
+    y: i32,
+}
+
+/// The dispatchers that carry out `op`. Showing and placing our window need
+/// the `monitor` picked by `placement`.
+pub fn dispatches_for(
+    op: &WmOp,
+    monitor: Option<&Monitor>,
+    placement: &PlacementConfig,
+) -> anyhow::Result<Vec<String>> {
+    let place = |wid: &str, width: i32, height: i32| -> anyhow::Result<Vec<String>> {
+        let monitor = monitor.context("no focused monitor")?;
+        let (x, y) = monitor.geometry().position(width, height, placement);
+        let window = overlay_address(wid)?;
+        Ok(vec![
+            format!("setfloating {window}"),
+            format!("resizewindowpixel exact {width} {height},{window}"),
+            format!("movewindowpixel exact {x} {y},{window}"),
+        ])
+    };
+    Ok(match op {
+        WmOp::Show {
+            wid, width, height, ..
+        }
+        | WmOp::Follow {
+            wid, width, height, ..
+        } => {
+            let workspace = monitor.context("no focused monitor")?.active_workspace.id;
+            let window = overlay_address(wid)?;
+            let mut dispatches = vec![format!("movetoworkspacesilent {workspace},{window}")];
+            dispatches.extend(place(wid, *width, *height)?);
+            dispatches.push(format!("focuswindow {window}"));
+            dispatches
+        }
+        WmOp::Hide { wid } => vec![format!(
+            "movetoworkspacesilent {HIDDEN_WORKSPACE},{}",
+            overlay_address(wid)?
+        )],
+        WmOp::Place {
+            wid, width, height, ..
+        } => place(wid, *width, *height)?,
+        WmOp::Focus { wid } | WmOp::Commit { wid: Some(wid) } => {
+            vec![format!("focuswindow {}", address(*wid))]
+        }
+        WmOp::Kill { wid } => vec![format!("closewindow {}", address(*wid))],
+        WmOp::Commit { wid: None } => vec![],
+    })
+}
+
+/// One line from `.socket2.sock`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HyprEvent {
+    Focused(u64),
+    Opened,
+    Closed(u64),
+    Other,
+}
+
+impl HyprEvent {
+    pub fn parse(line: &str) -> Self {
+        let Some((name, data)) = line.split_once(">>") else {
+            return Self::Other;
+        };
+        let first = data.split(',').next().unwrap_or_default();
+        match name {
+            // Empty when no window has focus.
+            "activewindowv2" => parse_address(first).map_or(Self::Other, Self::Focused),
+            "openwindow" => Self::Opened,
+            "closewindow" => parse_address(first).map_or(Self::Other, Self::Closed),
+            _ => Self::Other,
+        }
+    }
+}
+
+pub struct HyprlandBackend {
+    dir: PathBuf,
+    placement: PlacementConfig,
+}
+
+impl HyprlandBackend {
+    /// `dir` holds `.socket.sock` and `.socket2.sock`.
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            placement: PlacementConfig::default(),
+        }
+    }
+
+    pub fn with_placement(mut self, placement: PlacementConfig) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Find the sockets of the instance named by `$HYPRLAND_INSTANCE_SIGNATURE`,
+    /// under `$XDG_RUNTIME_DIR/hypr` or, for older releases, `/tmp/hypr`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")
+            .context("HYPRLAND_INSTANCE_SIGNATURE is not set")?;
+        let runtime = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("hypr").join(&signature));
+        let dir = runtime
+            .filter(|dir| dir.join(".socket.sock").exists())
+            .unwrap_or_else(|| PathBuf::from("/tmp/hypr").join(&signature));
+        Ok(Self::new(dir))
+    }
+
+    pub async fn request(&self, request: &str) -> anyhow::Result<String> {
+        let path = self.dir.join(".socket.sock");
+        let mut stream = UnixStream::connect(&path)
+            .await
+            .with_context(|| format!("Failed to connect to Hyprland at {path:?}"))?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).await?;
+        Ok(reply)
+    }
+
+    async fn query<T: DeserializeOwned>(&self, what: &str) -> anyhow::Result<T> {
+        let reply = self.request(&format!("j/{what}")).await?;
+        serde_json::from_str(&reply).with_context(|| format!("Unexpected {what} reply"))
+    }
+
+    async fn clients(&self) -> anyhow::Result<Vec<Client>> {
+        self.query("clients").await
+    }
+
+    /// Run `dispatches` as one batch, so nothing runs in between.
+    async fn dispatch(&self, dispatches: &[String]) -> anyhow::Result<()> {
+        let batch = dispatches
+            .iter()
+            .map(|d| format!("dispatch {d}"))
+            .collect::<Vec<_>>()
+            .join(";");
+        log::debug!("Hyprland batch: {batch}");
+        let reply = self.request(&format!("[[BATCH]]{batch}")).await?;
+        // One `ok` per dispatcher, or the error in its place.
+        if reply.split_whitespace().any(|r| r != "ok") {
+            bail!("Hyprland rejected {batch:?}: {}", reply.trim());
+        }
+        Ok(())
+    }
+
+    /// The monitor `placement` picks, falling back to the focused one.
+    async fn target_monitor(&self, selected: Option<u64>) -> anyhow::Result<Option<Monitor>> {
+        let mut monitors: Vec<Monitor> = self.query("monitors").await?;
+        let point = match (self.placement.screen, selected) {
+            (ScreenPolicy::Pointer, _) => {
+                let cursor: CursorPosition = self.query("cursorpos").await?;
+                Some((cursor.x, cursor.y))
+            }
+            (ScreenPolicy::Selection, Some(wid)) => self
+                .clients()
+                .await?
+                .iter()
+                .find(|c| parse_address(&c.address) == Some(wid))
+                .map(|c| c.geometry().center()),
+            _ => None,
+        };
+        let geometries: Vec<ScreenGeometry> = monitors.iter().map(Monitor::geometry).collect();
+        let index = pick_screen(&self.placement, &geometries, point)
+            .or_else(|| monitors.iter().position(|m| m.focused));
+        Ok(index.map(|index| monitors.swap_remove(index)))
+    }
+
+    async fn run_op(&self, op: WmOp) -> anyhow::Result<()> {
+        let monitor = match op {
+            WmOp::Show { selected, .. } | WmOp::Place { selected, .. } => {
+                self.target_monitor(selected).await?
+            }
+            WmOp::Follow { selected, .. } if self.placement.screen == ScreenPolicy::Selection => {
+                self.target_monitor(Some(selected)).await?
+            }
+            WmOp::Follow { .. } => return Ok(()),
+            _ => None,
+        };
+        let dispatches = dispatches_for(&op, monitor.as_ref(), &self.placement)?;
+        if dispatches.is_empty() {
+            return Ok(());
+        }
+        self.dispatch(&dispatches).await
+    }
+
+    async fn find(&self, name: &str) -> anyhow::Result<Option<u64>> {
+        Ok(self
+            .clients()
+            .await?
+            .iter()
+            .find(|c| c.title == name)
+            .and_then(|c| parse_address(&c.address)))
+    }
+
+    /// Every window except the overlay, most recently focused first.
+    async fn windows(&self) -> anyhow::Result<Vec<WindowInfo>> {
+        let mut clients = self.clients().await?;
+        clients.sort_by_key(|c| c.focus_history_id);
+        Ok(clients
+            .iter()
+            .filter(|c| !c.is_overlay())
+            .filter_map(Client::info)
+            .collect())
+    }
+
+    async fn watch(&self, tx: UnboundedSender<AppEvent>) -> anyhow::Result<()> {
+        let path = self.dir.join(".socket2.sock");
+        let events = UnixStream::connect(&path)
+            .await
+            .with_context(|| format!("Failed to connect to Hyprland events at {path:?}"))?;
+        let mut lines = BufReader::new(events).lines();
+
+        // Seed from Hyprland's own order, then keep our own from here on.
+        let mut history = FocusHistory::default();
+        history.sync(self.windows().await?);
+        let mut reporter = FocusReporter::new(tx);
+        if !reporter.report(&history) {
+            return Ok(());
+        }
+
+        while let Some(line) = lines.next_line().await? {
+            match HyprEvent::parse(&line) {
+                HyprEvent::Focused(id) => {
+                    let windows = self.windows().await?;
+                    let focused = windows.iter().find(|w| w.id == id).cloned();
+                    history.sync(windows);
+                    if let Some(window) = focused {
+                        history.focus(window);
+                    }
+                }
+                HyprEvent::Opened => history.sync(self.windows().await?),
+                HyprEvent::Closed(id) => {
+                    history.remove(id);
+                }
+                HyprEvent::Other => continue,
+            }
+            if !reporter.report(&history) {
+                return Ok(());
+            }
+        }
+        bail!("Hyprland closed the event socket")
+    }
+}
+
+impl WindowManager for HyprlandBackend {
+    fn name(&self) -> &'static str {
+        "hyprland"
+    }
+
+    fn run(&self, op: WmOp) -> WmFuture<'_, ()> {
+        Box::pin(self.run_op(op))
+    }
+
+    fn find_window<'a>(&'a self, name: &'a str) -> WmFuture<'a, Option<u64>> {
+        Box::pin(self.find(name))
+    }
+
+    fn watch_focus(&self, tx: UnboundedSender<AppEvent>) -> WmFuture<'_, ()> {
+        Box::pin(self.watch(tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn monitor() -> Monitor {
+        serde_json::from_value(json!({
+            "id": 0, "name": "DP-1", "x": 2560, "y": 0,
+            "width": 3840, "height": 2160, "scale": 2.0, "focused": true,
+            "activeWorkspace": {"id": 3, "name": "3"},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn addresses_parse_with_and_without_prefix() {
+        assert_eq!(parse_address("0x55d0c3a1b2c0"), Some(0x55d0c3a1b2c0));
+        assert_eq!(parse_address("55d0c3a1b2c0"), Some(0x55d0c3a1b2c0));
+        assert_eq!(parse_address(""), None);
+        assert_eq!(address(0x55d0c3a1b2c0), "address:0x55d0c3a1b2c0");
+    }
+
+    #[test]
+    fn events_parse() {
+        assert_eq!(
+            HyprEvent::parse("activewindowv2>>55d0c3a1b2c0"),
+            HyprEvent::Focused(0x55d0c3a1b2c0)
+        );
+        assert_eq!(HyprEvent::parse("activewindowv2>>"), HyprEvent::Other);
+        assert_eq!(
+            HyprEvent::parse("closewindow>>55d0c3a1b2c0"),
+            HyprEvent::Closed(0x55d0c3a1b2c0)
+        );
+        assert_eq!(
+            HyprEvent::parse("openwindow>>abc,1,foot,vim"),
+            HyprEvent::Opened
+        );
+        assert_eq!(HyprEvent::parse("workspace>>2"), HyprEvent::Other);
+        assert_eq!(HyprEvent::parse("garbage"), HyprEvent::Other);
+    }
+
+    #[test]
+    fn monitor_geometry_divides_out_the_scale() {
+        let geometry = monitor().geometry();
+        assert_eq!((geometry.width, geometry.height), (1920, 1080));
+        assert_eq!(geometry.centered(300, 400), (3370, 340));
+    }
+
+    #[test]
+    fn show_moves_places_and_focuses_the_overlay() {
+        let op = WmOp::Show {
+            wid: "255".into(),
+            width: 300,
+            height: 400,
+            selected: None,
+        };
+        let placement = PlacementConfig::default();
+        assert_eq!(
+            dispatches_for(&op, Some(&monitor()), &placement).unwrap(),
+            vec![
+                "movetoworkspacesilent 3,address:0xff",
+                "setfloating address:0xff",
+                "resizewindowpixel exact 300 400,address:0xff",
+                "movewindowpixel exact 3370 340,address:0xff",
+                "focuswindow address:0xff",
+            ]
+        );
+        assert!(dispatches_for(&op, None, &placement).is_err());
+
+        let placement = PlacementConfig {
+            anchor: crate::config::Anchor::Bottom,
+            offset_y: -20,
+            ..PlacementConfig::default()
+        };
+        assert_eq!(
+            dispatches_for(&op, Some(&monitor()), &placement).unwrap()[3],
+            "movewindowpixel exact 3370 660,address:0xff"
+        );
+    }
+
+    #[test]
+    fn other_ops_map_to_single_dispatchers() {
+        let dispatches_for =
+            |op: &WmOp, monitor| super::dispatches_for(op, monitor, &PlacementConfig::default());
+        assert_eq!(
+            dispatches_for(&WmOp::Hide { wid: "255".into() }, None).unwrap(),
+            vec!["movetoworkspacesilent special:qalttab,address:0xff"]
+        );
+        assert_eq!(
+            dispatches_for(&WmOp::Kill { wid: 0xabc }, None).unwrap(),
+            vec!["closewindow address:0xabc"]
+        );
+        assert_eq!(
+            dispatches_for(&WmOp::Commit { wid: Some(0xabc) }, None).unwrap(),
+            vec!["focuswindow address:0xabc"]
+        );
+        assert!(
+            dispatches_for(&WmOp::Commit { wid: None }, None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! One running overlay per Wayland display.
+//!
+//! The instance holds an exclusive lock on a file next to its IPC socket for
+//! as long as it runs. The kernel drops the lock when the process dies, so a
+//! crash never leaves a stale lock behind. A socket file can outlive its
+//! instance, though, so whether one is still served is found out by
+//! connecting to it.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use serde_json::json;
+use tokio::net::UnixStream;
+
+use crate::ipc::get_socket_path;
+
+/// How long `--replace` waits for the running instance to exit.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(3);
+const REPLACE_POLL: Duration = Duration::from_millis(50);
+
+/// Held by the running instance. Dropping it releases the lock.
+#[derive(Debug)]
+pub struct InstanceLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl InstanceLock {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write_pid(&mut self) -> anyhow::Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        write!(self.file, "{}", std::process::id())?;
+        Ok(())
+    }
+}
+
+/// The lock file guarding the socket at `socket_path`.
+pub fn lock_path(socket_path: &Path) -> PathBuf {
+    let mut path = socket_path.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// Whether an instance answers on `socket_path`. A socket file nobody
+/// listens on is stale and gets replaced when the IPC server binds.
+pub async fn is_live(socket_path: &Path) -> bool {
+    UnixStream::connect(socket_path).await.is_ok()
+}
+
+/// Become the only instance for the socket at `custom_socket_path`, or the
+/// default socket of this Wayland display.
+///
+/// Fails if another instance is running, unless `replace` is set, in which
+/// case that instance is asked to quit and its lock taken over.
+pub async fn acquire(
+    custom_socket_path: Option<&Path>,
+    replace: bool,
+) -> anyhow::Result<InstanceLock> {
+    let socket_path = get_socket_path(custom_socket_path);
+    let path = lock_path(&socket_path);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Could not create {dir:?}"))?;
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("Could not open the lock file {path:?}"))?;
+    let mut lock = InstanceLock { file, path };
+
+    let mut locked = try_lock(&lock.file)?;
+    // An instance from before the lock file, or one whose lock file was
+    // removed, still answers on the socket.
+    let live = is_live(&socket_path).await;
+    if !locked || live {
+        let running = match read_pid(&mut lock.file) {
+            Some(pid) => format!("qalttab (pid {pid})"),
+            None => "qalttab".to_owned(),
+        };
+        if !replace {
+            if live {
+                bail!(
+                    "{running} is already running on {socket_path:?}, use --replace to take over"
+                );
+            }
+            bail!(
+                "{running} holds {:?} but does not answer on {socket_path:?}",
+                lock.path
+            );
+        }
+        if !live {
+            bail!("{running} does not answer on {socket_path:?}, so it cannot be replaced");
+        }
+        log::info!("Asking {running} to quit");
+        // It may exit before replying.
+        if let Err(e) =
+            crate::ctl::send_request(&json!({ "message_type": "quit" }), Some(&socket_path)).await
+        {
+            log::debug!("No reply to quit: {e:#}");
+        }
+        locked = wait_for_exit(&lock.file, &socket_path, locked).await?;
+        if !locked {
+            bail!("{running} did not quit within {REPLACE_TIMEOUT:?}");
+        }
+    }
+    lock.write_pid()?;
+    log::debug!("Holding the instance lock {:?}", lock.path);
+    Ok(lock)
+}
+
+fn try_lock(file: &File) -> anyhow::Result<bool> {
+    match file.try_lock() {
+        Ok(()) => Ok(true),
+        Err(TryLockError::WouldBlock) => Ok(false),
+        Err(TryLockError::Error(e)) => Err(e).context("Could not lock the lock file"),
+    }
+}
+
+/// Wait until the lock is ours and nothing answers on the socket.
+async fn wait_for_exit(file: &File, socket_path: &Path, locked: bool) -> anyhow::Result<bool> {
+    let mut locked = locked;
+    let deadline = tokio::time::Instant::now() + REPLACE_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        locked = locked || try_lock(file)?;
+        if locked && !is_live(socket_path).await {
+            return Ok(true);
+        }
+        tokio::time::sleep(REPLACE_POLL).await;
+    }
+    Ok(false)
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut pid = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut pid).ok()?;
+    pid.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_file_sits_next_to_the_socket() {
+        assert_eq!(
+            lock_path(Path::new("/run/qtile/qalttab.wayland-1")),
+            Path::new("/run/qtile/qalttab.wayland-1.lock")
+        );
+    }
+}
@@ -1,15 +1,23 @@
 use std::{
-    collections::HashMap,
     path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
 };
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::UnboundedSender;
 
 use anyhow::Context;
-use serde_json::{Value, json};
+use serde::Serialize;
+use serde_json::Value;
 
-use crate::ui::{AppEvent, MessageType, Response};
+use crate::config::LiveConfig;
+use crate::ui::{
+    AppEvent, ControlCommand, MessageType, OverlayEvent, Response, SharedState, WindowInfo,
+};
 
 /// Returns the socket path to use.
 /// If `custom` is `Some`, returns it unchanged (used in tests).
@@ -26,46 +34,353 @@ pub fn get_socket_path(custom: Option<&Path>) -> PathBuf {
         .join(format!("qalttab.{wayland_display}"))
 }
 
-type ParsedMessage = (MessageType, Vec<HashMap<String, String>>, Option<usize>);
+/// Default upper bound on the size of a single IPC frame, in bytes.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 1024 * 1024;
 
-/// Parse a raw IPC message into its typed components.
+/// Returned by [`FrameReader::next_frame`] when a frame exceeds the configured limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTooLarge {
+    pub max_frame_size: usize,
+}
+
+impl std::fmt::Display for FrameTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "frame exceeds maximum size of {} bytes",
+            self.max_frame_size
+        )
+    }
+}
+
+impl std::error::Error for FrameTooLarge {}
+
+/// Splits a byte stream into IPC frames.
 ///
-/// Returns `(message_type, windows, focus_index)`.
-pub fn parse_ipc_message(data: &[u8]) -> anyhow::Result<ParsedMessage> {
-    let response: Value = serde_json::from_slice(data).with_context(|| {
+/// A frame ends at a newline, which is not part of it (newline-delimited
+/// JSON). A frame also ends where a top-level JSON object or array closes,
+/// so existing hooks that write a bare JSON object and wait for the reply
+/// keep working. Frames may arrive split across any number of reads.
+pub struct FrameReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    max_frame_size: usize,
+    eof: bool,
+    /// How much of `buf` has been searched for the end of the current frame.
+    scanned: usize,
+    /// Nesting of objects and arrays at `scanned`.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    pub fn new(inner: R, max_frame_size: usize) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            max_frame_size,
+            eof: false,
+            scanned: 0,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+        }
+    }
+
+    /// Read the next complete frame.
+    ///
+    /// Returns `Ok(None)` once the peer has closed the stream and no data is left.
+    /// Fails with [`FrameTooLarge`] if a frame grows beyond the configured limit.
+    pub async fn next_frame(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut chunk = [0; 8192];
+        loop {
+            if let Some(frame) = self.take_frame() {
+                if frame.len() > self.max_frame_size {
+                    return Err(self.too_large());
+                }
+                return Ok(Some(frame));
+            }
+            if self.buf.len() > self.max_frame_size {
+                return Err(self.too_large());
+            }
+            if self.eof {
+                return Ok(None);
+            }
+            let bytes_read = self.inner.read(&mut chunk).await?;
+            log::debug!("Read {} bytes from IPC stream", bytes_read);
+            if bytes_read == 0 {
+                self.eof = true;
+            } else {
+                self.buf.extend_from_slice(&chunk[..bytes_read]);
+            }
+        }
+    }
+
+    fn too_large(&self) -> anyhow::Error {
+        FrameTooLarge {
+            max_frame_size: self.max_frame_size,
+        }
+        .into()
+    }
+
+    /// Remove and return the first complete frame in the buffer, if any.
+    /// At the end of the stream, whatever is left is the last frame.
+    fn take_frame(&mut self) -> Option<Vec<u8>> {
+        if self.scanned == 0 {
+            // Whitespace between frames, including a newline after a bare document.
+            let start = self
+                .buf
+                .iter()
+                .position(|b| !b.is_ascii_whitespace())
+                .unwrap_or(self.buf.len());
+            self.buf.drain(..start);
+        }
+        if self.buf.is_empty() {
+            return None;
+        }
+        let (end, consumed) = match self.scan() {
+            Some(end) => end,
+            None if self.eof => (self.buf.len(), self.buf.len()),
+            None => return None,
+        };
+        let mut frame: Vec<u8> = self.buf.drain(..consumed).collect();
+        frame.truncate(end);
+        self.scanned = 0;
+        self.depth = 0;
+        self.in_string = false;
+        self.escaped = false;
+        Some(frame)
+    }
+
+    /// Search the bytes not scanned yet for the end of the current frame.
+    /// Returns where the frame ends and where the next one may start.
+    fn scan(&mut self) -> Option<(usize, usize)> {
+        while self.scanned < self.buf.len() {
+            let pos = self.scanned;
+            let byte = self.buf[pos];
+            self.scanned += 1;
+            // JSON strings cannot hold a raw newline, so it always ends the frame.
+            if byte == b'\n' {
+                return Some((pos, pos + 1));
+            }
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' if self.depth > 0 => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        return Some((pos + 1, pos + 1));
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Returned by [`parse_ipc_message`] when `message_type` names no known message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownMessageType(pub String);
+
+impl std::fmt::Display for UnknownMessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MessageType {} not known", self.0)
+    }
+}
+
+impl std::error::Error for UnknownMessageType {}
+
+/// Outcome of a request, reported in the `status` field of every reply.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplyStatus {
+    Ok,
+    ParseError,
+    UnknownMessageType,
+    InvalidArgument,
+    FrameTooLarge,
+    InternalError,
+    /// `reload_config` found a config that does not load.
+    ConfigError,
+}
+
+/// Reply sent for every request frame.
+///
+/// `id` echoes the request's `id` field verbatim so clients keeping one
+/// connection open can correlate replies with requests.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Reply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub status: ReplyStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Payload of query requests such as `get_state`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+}
+
+impl Reply {
+    pub fn ok(id: Option<Value>) -> Self {
+        Self {
+            id,
+            status: ReplyStatus::Ok,
+            error: None,
+            result: None,
+        }
+    }
+
+    pub fn with_result(id: Option<Value>, result: Value) -> Self {
+        Self {
+            result: Some(result),
+            ..Self::ok(id)
+        }
+    }
+
+    pub fn error(id: Option<Value>, status: ReplyStatus, error: impl ToString) -> Self {
+        Self {
+            id,
+            status,
+            error: Some(error.to_string()),
+            result: None,
+        }
+    }
+}
+
+/// Everything a connection handler needs to serve requests.
+#[derive(Clone)]
+pub struct IpcContext {
+    pub tx: UnboundedSender<AppEvent>,
+    pub egui_ctx: egui::Context,
+    pub shared: Arc<Mutex<SharedState>>,
+    /// The config in use, swapped by `reload_config`.
+    pub config: LiveConfig,
+    /// Overlay events forwarded to `subscribe` connections.
+    pub events: broadcast::Sender<OverlayEvent>,
+    /// Set by `quit`. The UI host checks it every frame and closes.
+    pub shutdown: Arc<AtomicBool>,
+}
+
+impl IpcContext {
+    pub fn new(
+        tx: UnboundedSender<AppEvent>,
+        egui_ctx: egui::Context,
+        shared: Arc<Mutex<SharedState>>,
+        config: LiveConfig,
+    ) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Self {
+            tx,
+            egui_ctx,
+            shared,
+            config,
+            events,
+            shutdown: Arc::default(),
+        }
+    }
+}
+
+/// Events buffered per subscriber before the slowest one starts missing some.
+const EVENT_BUFFER_SIZE: usize = 64;
+
+/// A decoded request frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Window list pushed by the Qtile hook (`client_focus`, `cycle_windows`).
+    Windows(Response),
+    /// Drive the overlay (`next` or `cycle`, `prev`, `show`, `hide`, `select`).
+    Control(ControlCommand),
+    /// Read-only query answered directly from the socket task.
+    Query(Query),
+    /// Stream overlay events on this connection (`subscribe`).
+    Subscribe,
+    /// Exit, used by `--replace` (`quit`).
+    Quit,
+    /// Load the config file again (`reload_config`).
+    ReloadConfig,
+}
+
+/// Read-only queries, answered with a JSON `result`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    /// Visibility, selection, our window ID and the last window list (`get_state`).
+    State,
+    /// The effective configuration (`get_config`).
+    Config,
+    /// The qalttab version (`get_version`).
+    Version,
+}
+
+/// Write one newline-terminated JSON frame.
+pub async fn write_frame<W: AsyncWrite + Unpin, T: Serialize>(
+    writer: &mut W,
+    reply: &T,
+) -> anyhow::Result<()> {
+    let mut bytes = serde_json::to_vec(reply)?;
+    bytes.push(b'\n');
+    writer.write_all(&bytes).await?;
+    Ok(())
+}
+
+type ParsedMessage = (MessageType, Vec<WindowInfo>, Option<usize>);
+
+fn parse_json(data: &[u8]) -> anyhow::Result<Value> {
+    serde_json::from_slice(data).with_context(|| {
         format!(
             "Failed to parse JSON ({} bytes): {}",
             data.len(),
             String::from_utf8_lossy(data)
         )
-    })?;
+    })
+}
 
-    let message_type_str = response
+fn message_type_of(message: &Value) -> anyhow::Result<&str> {
+    message
         .get("message_type")
         .and_then(|v| v.as_str())
-        .context("message_type missing or not a string")?;
+        .context("message_type missing or not a string")
+}
 
-    let message_type = match message_type_str {
+/// Parse a raw IPC message into its typed components.
+///
+/// Only accepts the window list messages sent by the Qtile hook.
+/// Returns `(message_type, windows, focus_index)`.
+pub fn parse_ipc_message(data: &[u8]) -> anyhow::Result<ParsedMessage> {
+    let message = parse_json(data)?;
+    let message_type = match message_type_of(&message)? {
         "client_focus" => MessageType::ClientFocus,
         "cycle_windows" => MessageType::CycleWindows,
-        s => anyhow::bail!("MessageType {} not known", s),
+        s => return Err(UnknownMessageType(s.to_owned()).into()),
     };
+    parse_window_list(message_type, &message)
+}
 
-    let windows_val = response.get("windows").context("windows field missing")?;
+fn parse_window_list(message_type: MessageType, message: &Value) -> anyhow::Result<ParsedMessage> {
+    let windows_val = message.get("windows").context("windows field missing")?;
     let windows_array = windows_val
         .as_array()
         .context("windows field is not an array")?;
 
     let mut windows = Vec::new();
     for item in windows_array {
-        if let Ok(map) = serde_json::from_value::<HashMap<String, String>>(item.clone()) {
-            windows.push(map);
-        } else {
-            log::warn!("Skipping malformed window entry: {item}");
+        match serde_json::from_value::<WindowInfo>(item.clone()) {
+            Ok(win) => windows.push(win),
+            Err(e) => log::warn!("Skipping malformed window entry {item}: {e}"),
         }
     }
 
-    let focus_index = response
+    let focus_index = message
         .get("focus_index")
         .and_then(|v| v.as_u64())
         .map(|v| v as usize);
@@ -73,10 +388,57 @@ pub fn parse_ipc_message(data: &[u8]) -> anyhow::Result<ParsedMessage> {
     Ok((message_type, windows, focus_index))
 }
 
+/// Parse any request frame.
+pub fn parse_request(data: &[u8]) -> anyhow::Result<Request> {
+    let message = parse_json(data)?;
+    let window_message = |message_type| {
+        let (message_type, windows, focus_index) = parse_window_list(message_type, &message)?;
+        anyhow::Ok(Request::Windows(Response {
+            message_type,
+            windows,
+            focus_index,
+        }))
+    };
+    let request = match message_type_of(&message)? {
+        "client_focus" => window_message(MessageType::ClientFocus)?,
+        "cycle_windows" => window_message(MessageType::CycleWindows)?,
+        // `cycle` needs no window list: it opens on qalttab's own focus history.
+        "next" | "cycle" => Request::Control(ControlCommand::Next),
+        "prev" => Request::Control(ControlCommand::Prev),
+        "show" => Request::Control(ControlCommand::Show),
+        "hide" => Request::Control(ControlCommand::Hide),
+        "select" => {
+            let index = message
+                .get("index")
+                .and_then(|v| v.as_u64())
+                .context("index missing or not a non-negative integer")?;
+            Request::Control(ControlCommand::Select(index as usize))
+        }
+        "get_state" => Request::Query(Query::State),
+        "get_config" => Request::Query(Query::Config),
+        "get_version" => Request::Query(Query::Version),
+        "subscribe" => Request::Subscribe,
+        "quit" => Request::Quit,
+        "reload_config" => Request::ReloadConfig,
+        s => return Err(UnknownMessageType(s.to_owned()).into()),
+    };
+    Ok(request)
+}
+
+/// Remove the socket on shutdown, so nothing mistakes it for a live instance.
+pub fn remove_socket(custom_socket_path: Option<&Path>) {
+    let socket_path = get_socket_path(custom_socket_path);
+    if let Err(e) = std::fs::remove_file(&socket_path)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        log::warn!("Could not remove {socket_path:?}: {e}");
+    }
+}
+
 pub async fn listen(
-    tx: UnboundedSender<AppEvent>,
-    ctx: egui::Context,
+    ipc: IpcContext,
     custom_socket_path: Option<&Path>,
+    max_frame_size: usize,
 ) -> anyhow::Result<()> {
     let socket_path = get_socket_path(custom_socket_path);
 
@@ -93,10 +455,9 @@ pub async fn listen(
         match listener.accept().await {
             Ok((stream, _)) => {
                 log::debug!("Accepted new IPC connection");
-                let tx_clone = tx.clone();
-                let ctx_clone = ctx.clone();
+                let ipc_clone = ipc.clone();
                 tokio::spawn(async move {
-                    if let Err(e) = handle_conn(stream, tx_clone, ctx_clone).await {
+                    if let Err(e) = handle_conn(stream, ipc_clone, max_frame_size).await {
                         log::error!("Error handling connection: {e}");
                     }
                 });
@@ -106,44 +467,171 @@ pub async fn listen(
     }
 }
 
+/// Serve one client connection.
+///
+/// The connection stays open until the client closes it, so a hook can send
+/// any number of requests over it. Every request frame gets exactly one reply,
+/// in order. Malformed requests are answered with an error status and do not
+/// close the connection; only an oversized frame does.
+///
+/// After a `subscribe` request, overlay events are pushed on the same
+/// connection as they happen. They carry an `event` field instead of `status`.
 pub async fn handle_conn(
     mut stream: UnixStream,
-    tx: UnboundedSender<AppEvent>,
-    ctx: egui::Context,
+    ipc: IpcContext,
+    max_frame_size: usize,
 ) -> anyhow::Result<()> {
-    let mut buffer = [0; 8192];
-    let bytes_read = stream.read(&mut buffer).await?;
-    log::debug!("Read {} bytes from IPC stream", bytes_read);
-    if bytes_read == 0 {
-        return Ok(());
+    let (reader, mut writer) = stream.split();
+    let mut frames = FrameReader::new(reader, max_frame_size);
+    let mut subscription = None;
+    loop {
+        let next_frame = tokio::select! {
+            next_frame = frames.next_frame() => next_frame,
+            event = next_event(&mut subscription) => {
+                match event {
+                    Ok(event) => write_frame(&mut writer, &event).await?,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("IPC subscriber lagging, dropped {skipped} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => subscription = None,
+                }
+                continue;
+            }
+        };
+        let frame = match next_frame {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                if e.is::<FrameTooLarge>() {
+                    let reply = Reply::error(None, ReplyStatus::FrameTooLarge, &e);
+                    write_frame(&mut writer, &reply).await?;
+                }
+                return Err(e);
+            }
+        };
+        log::debug!(
+            "Received IPC message ({} bytes): {}",
+            frame.len(),
+            String::from_utf8_lossy(&frame)
+        );
+
+        let reply = handle_frame(&frame, &ipc, &mut subscription).await?;
+        write_frame(&mut writer, &reply).await?;
     }
-    let slice = &buffer[..bytes_read];
-    log::debug!(
-        "Received IPC message ({} bytes): {}",
-        bytes_read,
-        String::from_utf8_lossy(slice)
-    );
+}
+
+/// Wait for the next event of an optional subscription; never resolves without one.
+async fn next_event(
+    subscription: &mut Option<broadcast::Receiver<OverlayEvent>>,
+) -> Result<OverlayEvent, broadcast::error::RecvError> {
+    match subscription {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Extract the optional `id` of a request, used to correlate its reply.
+fn request_id(frame: &[u8]) -> Option<Value> {
+    serde_json::from_slice::<Value>(frame)
+        .ok()
+        .and_then(|v| v.get("id").cloned())
+}
+
+fn answer_query(query: Query, ipc: &IpcContext) -> anyhow::Result<Value> {
+    Ok(match query {
+        Query::State => ipc.shared.lock().unwrap().to_json(),
+        Query::Config => serde_json::to_value(&*ipc.config.get())?,
+        Query::Version => serde_json::json!({
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        }),
+    })
+}
 
-    let (message_type, windows, focus_index) = parse_ipc_message(slice)?;
+/// Process a single request frame and build its reply.
+///
+/// A `subscribe` request stores a new event receiver in `subscription`.
+/// Only fails if the event loop is gone, in which case the connection is closed.
+pub async fn handle_frame(
+    frame: &[u8],
+    ipc: &IpcContext,
+    subscription: &mut Option<broadcast::Receiver<OverlayEvent>>,
+) -> anyhow::Result<Reply> {
+    let id = request_id(frame);
+    let request = match parse_request(frame) {
+        Ok(request) => request,
+        Err(e) => {
+            log::warn!("Rejecting IPC message: {e:#}");
+            let status = if e.is::<UnknownMessageType>() {
+                ReplyStatus::UnknownMessageType
+            } else {
+                ReplyStatus::ParseError
+            };
+            return Ok(Reply::error(id, status, format!("{e:#}")));
+        }
+    };
 
-    tx.send(AppEvent::UnixSocketMsg(Response {
-        message_type,
-        windows,
-        focus_index,
-    }))
-    .map_err(|e| anyhow::anyhow!("could not send message to GUI thread: {e}"))?;
+    let event = match request {
+        Request::Windows(response) => AppEvent::UnixSocketMsg(response),
+        Request::Control(ControlCommand::Select(index)) => {
+            let len = ipc.shared.lock().unwrap().last_windows.len();
+            if index >= len {
+                return Ok(Reply::error(
+                    id,
+                    ReplyStatus::InvalidArgument,
+                    format!("index {index} out of range for {len} windows"),
+                ));
+            }
+            AppEvent::Control(ControlCommand::Select(index))
+        }
+        Request::Control(command) => AppEvent::Control(command),
+        // Answered here rather than by the event loop, which only starts
+        // once our window has been found.
+        Request::Quit => {
+            log::info!("Quitting for a new instance");
+            ipc.shutdown.store(true, Ordering::Relaxed);
+            ipc.egui_ctx.request_repaint();
+            return Ok(Reply::ok(id));
+        }
+        Request::Subscribe => {
+            *subscription = Some(ipc.events.subscribe());
+            return Ok(Reply::ok(id));
+        }
+        Request::ReloadConfig => {
+            // Reading and parsing the file blocks.
+            let config = ipc.config.clone();
+            let reloaded = tokio::task::spawn_blocking(move || config.reload())
+                .await
+                .unwrap_or_else(|e| Err(e.into()));
+            return Ok(match reloaded {
+                Ok(()) => {
+                    ipc.egui_ctx.request_repaint();
+                    Reply::ok(id)
+                }
+                Err(e) => Reply::error(id, ReplyStatus::ConfigError, format!("{e:#}")),
+            });
+        }
+        Request::Query(query) => {
+            return Ok(match answer_query(query, ipc) {
+                Ok(result) => Reply::with_result(id, result),
+                Err(e) => Reply::error(id, ReplyStatus::InternalError, format!("{e:#}")),
+            });
+        }
+    };
 
-    ctx.request_repaint();
+    ipc.tx
+        .send(event)
+        .map_err(|e| anyhow::anyhow!("could not send message to GUI thread: {e}"))?;
 
-    let success = json!({"message": "success"});
-    stream.write_all(success.to_string().as_bytes()).await?;
+    ipc.egui_ctx.request_repaint();
 
-    Ok(())
+    Ok(Reply::ok(id))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
 
     fn msg(json: &str) -> ParsedMessage {
         parse_ipc_message(json.as_bytes()).expect("parse failed")
@@ -153,13 +641,41 @@ mod tests {
         parse_ipc_message(json.as_bytes()).unwrap_err().to_string()
     }
 
+    fn test_ipc() -> (IpcContext, tokio::sync::mpsc::UnboundedReceiver<AppEvent>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let ipc = IpcContext::new(
+            tx,
+            egui::Context::default(),
+            Arc::new(Mutex::new(SharedState::default())),
+            LiveConfig::default(),
+        );
+        (ipc, rx)
+    }
+
+    async fn reply_for(json: &str) -> Reply {
+        let (ipc, _rx) = test_ipc();
+        handle_frame(json.as_bytes(), &ipc, &mut None)
+            .await
+            .expect("handle_frame")
+    }
+
+    async fn frames(input: &[u8], max_frame_size: usize) -> Vec<Vec<u8>> {
+        let mut reader = FrameReader::new(input, max_frame_size);
+        let mut out = Vec::new();
+        while let Some(frame) = reader.next_frame().await.expect("frame") {
+            out.push(frame);
+        }
+        out
+    }
+
     #[test]
     fn parses_cycle_windows_message() {
         let json = r#"{"message_type":"cycle_windows","windows":[{"id":"1","name":"Firefox","class":"firefox","group_name":"www","group_label":""}]}"#;
         let (mt, wins, fi) = msg(json);
         assert_eq!(mt, MessageType::CycleWindows);
         assert_eq!(wins.len(), 1);
-        assert_eq!(wins[0]["name"], "Firefox");
+        assert_eq!(wins[0].name, "Firefox");
+        assert_eq!(wins[0].id, 1);
         assert_eq!(fi, None);
     }
 
@@ -198,12 +714,48 @@ mod tests {
 
     #[test]
     fn malformed_window_entries_are_skipped() {
-        // One valid entry, one where a value is not a string — the bad one is skipped.
+        // One valid entry, one whose id is not numeric — the bad one is skipped.
         let json =
-            r#"{"message_type":"cycle_windows","windows":[{"id":"1","name":"Term"},{"id":99}]}"#;
+            r#"{"message_type":"cycle_windows","windows":[{"id":"1","name":"Term"},{"id":"abc"}]}"#;
+        let (_, wins, _) = msg(json);
+        assert_eq!(wins.len(), 1);
+        assert_eq!(wins[0].id, 1);
+    }
+
+    #[test]
+    fn window_entry_without_id_is_skipped() {
+        let json = r#"{"message_type":"cycle_windows","windows":[{"name":"Term"},{"id":"2"}]}"#;
+        let (_, wins, _) = msg(json);
+        assert_eq!(wins.len(), 1);
+        assert_eq!(wins[0].id, 2);
+    }
+
+    #[test]
+    fn numeric_window_id_is_accepted() {
+        let json = r#"{"message_type":"cycle_windows","windows":[{"id":99}]}"#;
+        let (_, wins, _) = msg(json);
+        assert_eq!(wins.len(), 1);
+        assert_eq!(wins[0].id, 99);
+    }
+
+    #[test]
+    fn missing_window_fields_default_to_empty() {
+        let json = r#"{"message_type":"cycle_windows","windows":[{"id":"5"}]}"#;
+        let (_, wins, _) = msg(json);
+        let w = &wins[0];
+        assert_eq!(w.id, 5);
+        assert!(w.name.is_empty());
+        assert!(w.class.is_empty());
+        assert!(w.group_name.is_empty());
+        assert!(w.group_label.is_empty());
+    }
+
+    #[test]
+    fn null_window_name_becomes_empty() {
+        let json = r#"{"message_type":"cycle_windows","windows":[{"id":"5","name":null}]}"#;
         let (_, wins, _) = msg(json);
         assert_eq!(wins.len(), 1);
-        assert_eq!(wins[0]["id"], "1");
+        assert!(wins[0].name.is_empty());
     }
 
     #[test]
@@ -215,7 +767,7 @@ mod tests {
         ]}"#;
         let (_, wins, _) = msg(json);
         assert_eq!(wins.len(), 3);
-        assert_eq!(wins[1]["class"], "b");
+        assert_eq!(wins[1].class, "b");
     }
 
     #[test]
@@ -246,11 +798,12 @@ mod tests {
         ]}"#;
         let (_, wins, _) = msg(json);
         let w = &wins[0];
-        assert_eq!(w["id"], "42");
-        assert_eq!(w["name"], "Term");
-        assert_eq!(w["class"], "alacritty");
-        assert_eq!(w["group_name"], "coding");
-        assert_eq!(w["group_label"], "\u{e795}");
+        assert_eq!(w.id, 42);
+        assert_eq!(w.name, "Term");
+        assert_eq!(w.class, "alacritty");
+        assert_eq!(w.group_name, "coding");
+        assert_eq!(w.group_label, "\u{e795}");
+        assert!(w.extra.is_empty());
     }
 
     #[test]
@@ -284,7 +837,7 @@ mod tests {
             r#"{"message_type":"cycle_windows","windows":[{"id":"7","name":"x","extra":"y"}]}"#;
         let (_, wins, _) = msg(json);
         assert_eq!(wins.len(), 1);
-        assert_eq!(wins[0]["extra"], "y");
+        assert_eq!(wins[0].extra["extra"], "y");
     }
 
     #[test]
@@ -323,4 +876,305 @@ mod tests {
         let e = err(r#"[1,2,3]"#);
         assert!(e.contains("message_type"), "got: {e}");
     }
+
+    #[tokio::test]
+    async fn frame_reader_splits_newline_delimited_frames() {
+        let input = b"{\"a\":1}\n{\"b\":2}\n";
+        let out = frames(input, DEFAULT_MAX_FRAME_SIZE).await;
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0], b"{\"a\":1}");
+        assert_eq!(out[1], b"{\"b\":2}");
+    }
+
+    #[tokio::test]
+    async fn frame_reader_ignores_brackets_and_escaped_quotes_in_strings() {
+        let input = b"{\"a\":\"}\\\"]\"}\n{\"b\":[2]}";
+        let out = frames(input, DEFAULT_MAX_FRAME_SIZE).await;
+        assert_eq!(out, [&b"{\"a\":\"}\\\"]\"}"[..], b"{\"b\":[2]}"]);
+    }
+
+    #[tokio::test]
+    async fn frame_reader_accepts_unterminated_frame() {
+        let out = frames(br#"{"message_type":"client_focus","windows":[]}"#, 1024).await;
+        assert_eq!(out.len(), 1);
+        assert!(parse_ipc_message(&out[0]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn frame_reader_reassembles_frame_larger_than_one_read() {
+        let windows: Vec<_> = (0..200)
+            .map(|i| json!({"id": i.to_string(), "name": "x".repeat(100)}))
+            .collect();
+        let payload = json!({"message_type": "cycle_windows", "windows": windows});
+        let bytes = serde_json::to_vec(&payload).unwrap();
+        assert!(bytes.len() > 8192);
+
+        let out = frames(&bytes, DEFAULT_MAX_FRAME_SIZE).await;
+        assert_eq!(out.len(), 1);
+        let (_, wins, _) = parse_ipc_message(&out[0]).unwrap();
+        assert_eq!(wins.len(), 200);
+    }
+
+    #[tokio::test]
+    async fn frame_reader_rejects_oversized_frame() {
+        let payload = format!(r#"{{"pad":"{}"}}"#, "x".repeat(64));
+        let mut reader = FrameReader::new(payload.as_bytes(), 32);
+        let e = reader.next_frame().await.unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<FrameTooLarge>(),
+            Some(&FrameTooLarge { max_frame_size: 32 })
+        );
+    }
+
+    #[tokio::test]
+    async fn frame_reader_whitespace_only_stream_yields_nothing() {
+        assert!(frames(b" \n\t\n", 1024).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn frame_reader_resynchronises_after_invalid_line() {
+        let out = frames(b"not json\n{\"ok\":true}\n", 1024).await;
+        assert_eq!(out.len(), 2);
+        assert!(parse_ipc_message(&out[0]).is_err());
+        assert_eq!(out[1], b"{\"ok\":true}");
+    }
+
+    #[tokio::test]
+    async fn reply_echoes_request_id() {
+        let reply = reply_for(r#"{"id":7,"message_type":"client_focus","windows":[]}"#).await;
+        assert_eq!(reply, Reply::ok(Some(json!(7))));
+    }
+
+    #[tokio::test]
+    async fn reply_without_request_id_omits_id() {
+        let reply = reply_for(r#"{"message_type":"client_focus","windows":[]}"#).await;
+        let encoded = serde_json::to_value(&reply).unwrap();
+        assert_eq!(encoded, json!({"status": "ok"}));
+    }
+
+    #[tokio::test]
+    async fn reply_reports_unknown_message_type() {
+        let reply = reply_for(r#"{"id":"a","message_type":"bogus","windows":[]}"#).await;
+        assert_eq!(reply.id, Some(json!("a")));
+        assert_eq!(reply.status, ReplyStatus::UnknownMessageType);
+        assert!(reply.error.unwrap().contains("bogus"));
+    }
+
+    #[tokio::test]
+    async fn reply_reports_parse_error() {
+        let reply = reply_for(r#"{"id":1,"message_type":"cycle_windows"}"#).await;
+        assert_eq!(reply.id, Some(json!(1)));
+        assert_eq!(reply.status, ReplyStatus::ParseError);
+    }
+
+    #[test]
+    fn reply_status_serializes_as_snake_case() {
+        let reply = Reply::error(None, ReplyStatus::UnknownMessageType, "x");
+        assert_eq!(
+            serde_json::to_value(&reply).unwrap(),
+            json!({"status": "unknown_message_type", "error": "x"})
+        );
+    }
+
+    #[test]
+    fn parses_control_requests() {
+        for (message_type, command) in [
+            ("next", ControlCommand::Next),
+            ("cycle", ControlCommand::Next),
+            ("prev", ControlCommand::Prev),
+            ("show", ControlCommand::Show),
+            ("hide", ControlCommand::Hide),
+        ] {
+            let json = format!(r#"{{"message_type":"{message_type}"}}"#);
+            assert_eq!(
+                parse_request(json.as_bytes()).unwrap(),
+                Request::Control(command)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn quit_sets_the_shutdown_flag_without_the_event_loop() {
+        let (ipc, mut rx) = test_ipc();
+        let reply = handle_frame(br#"{"id":3,"message_type":"quit"}"#, &ipc, &mut None)
+            .await
+            .unwrap();
+        assert_eq!(reply, Reply::ok(Some(json!(3))));
+        assert!(ipc.shutdown.load(Ordering::Relaxed));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn parses_quit_and_reload_config_requests() {
+        assert_eq!(
+            parse_request(br#"{"message_type":"quit"}"#).unwrap(),
+            Request::Quit
+        );
+        assert_eq!(
+            parse_request(br#"{"message_type":"reload_config"}"#).unwrap(),
+            Request::ReloadConfig
+        );
+    }
+
+    #[test]
+    fn parses_select_request_with_index() {
+        let request = parse_request(br#"{"message_type":"select","index":3}"#).unwrap();
+        assert_eq!(request, Request::Control(ControlCommand::Select(3)));
+    }
+
+    #[test]
+    fn select_request_without_index_returns_error() {
+        let e = parse_request(br#"{"message_type":"select"}"#).unwrap_err();
+        assert!(e.to_string().contains("index"), "got: {e}");
+    }
+
+    #[test]
+    fn control_messages_are_not_window_messages() {
+        let e = err(r#"{"message_type":"next"}"#);
+        assert!(e.contains("not known"), "got: {e}");
+    }
+
+    #[tokio::test]
+    async fn control_request_is_forwarded_to_event_loop() {
+        let (ipc, mut rx) = test_ipc();
+        let reply = handle_frame(br#"{"id":1,"message_type":"next"}"#, &ipc, &mut None)
+            .await
+            .unwrap();
+        assert_eq!(reply, Reply::ok(Some(json!(1))));
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(AppEvent::Control(ControlCommand::Next))
+        ));
+    }
+
+    #[tokio::test]
+    async fn select_out_of_range_is_rejected() {
+        let (ipc, mut rx) = test_ipc();
+        ipc.shared.lock().unwrap().last_windows = vec![WindowInfo::default()];
+        let reply = handle_frame(br#"{"message_type":"select","index":1}"#, &ipc, &mut None)
+            .await
+            .unwrap();
+        assert_eq!(reply.status, ReplyStatus::InvalidArgument);
+        assert!(rx.try_recv().is_err());
+
+        let reply = handle_frame(br#"{"message_type":"select","index":0}"#, &ipc, &mut None)
+            .await
+            .unwrap();
+        assert_eq!(reply.status, ReplyStatus::Ok);
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(AppEvent::Control(ControlCommand::Select(0)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn get_state_returns_shared_state() {
+        let (ipc, mut rx) = test_ipc();
+        {
+            let mut state = ipc.shared.lock().unwrap();
+            state.is_visible = true;
+            state.focus_index = 1;
+            state.cached_wid = Some("12".into());
+            state.last_windows = vec![WindowInfo {
+                id: 3,
+                name: "Term".into(),
+                ..WindowInfo::default()
+            }];
+        }
+        let reply = handle_frame(br#"{"id":9,"message_type":"get_state"}"#, &ipc, &mut None)
+            .await
+            .unwrap();
+        assert_eq!(reply.id, Some(json!(9)));
+        let state = reply.result.expect("result");
+        assert_eq!(state["visible"], true);
+        assert_eq!(state["focus_index"], 1);
+        assert_eq!(state["cached_wid"], "12");
+        assert_eq!(state["windows"][0]["id"], 3);
+        assert_eq!(state["windows"][0]["name"], "Term");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn parses_query_requests() {
+        for (message_type, query) in [
+            ("get_state", Query::State),
+            ("get_config", Query::Config),
+            ("get_version", Query::Version),
+        ] {
+            let json = format!(r#"{{"message_type":"{message_type}"}}"#);
+            assert_eq!(
+                parse_request(json.as_bytes()).unwrap(),
+                Request::Query(query)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn get_config_returns_effective_config() {
+        let reply = reply_for(r#"{"id":2,"message_type":"get_config"}"#).await;
+        assert_eq!(reply.status, ReplyStatus::Ok);
+        let config = reply.result.expect("result");
+        assert_eq!(config["colors"]["bg_color"], "#1E1E2E");
+        assert_eq!(config["ui"]["orientation"], "Vertical");
+        assert_eq!(
+            config["ipc"]["max_frame_size"],
+            json!(DEFAULT_MAX_FRAME_SIZE)
+        );
+    }
+
+    #[tokio::test]
+    async fn get_config_follows_a_reloaded_config() {
+        let (ipc, _rx) = test_ipc();
+        let mut config = crate::config::Config::default();
+        config.colors.bg_color = "#000000".into();
+        ipc.config.apply(Ok(config)).unwrap();
+        let reply = handle_frame(br#"{"message_type":"get_config"}"#, &ipc, &mut None)
+            .await
+            .unwrap();
+        assert_eq!(
+            reply.result.expect("result")["colors"]["bg_color"],
+            "#000000"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_version_returns_crate_version() {
+        let reply = reply_for(r#"{"message_type":"get_version"}"#).await;
+        let version = reply.result.expect("result");
+        assert_eq!(version["name"], "qalttab");
+        assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[tokio::test]
+    async fn queries_do_not_reach_event_loop() {
+        let (ipc, mut rx) = test_ipc();
+        for message_type in ["get_state", "get_config", "get_version"] {
+            let json = format!(r#"{{"message_type":"{message_type}"}}"#);
+            handle_frame(json.as_bytes(), &ipc, &mut None)
+                .await
+                .unwrap();
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn subscribe_request_registers_receiver() {
+        let (ipc, mut rx) = test_ipc();
+        let mut subscription = None;
+        let reply = handle_frame(
+            br#"{"id":5,"message_type":"subscribe"}"#,
+            &ipc,
+            &mut subscription,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply, Reply::ok(Some(json!(5))));
+        assert!(rx.try_recv().is_err());
+
+        ipc.events.send(OverlayEvent::Hidden).unwrap();
+        assert_eq!(
+            subscription.expect("subscribed").try_recv().unwrap(),
+            OverlayEvent::Hidden
+        );
+    }
 }
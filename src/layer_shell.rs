@@ -0,0 +1,186 @@
+//! Overlay mode where qalttab is a wlr-layer-shell surface instead of a
+//! window the window manager hides and places.
+//!
+//! [`LayerShellBackend`] turns the operations on our own window into
+//! [`SurfaceRequest`]s for the host drawing the surface, and passes every
+//! operation on other windows to the window manager's backend. The host
+//! lives in `layer_surface` behind the `layer-shell` feature.
+
+use std::sync::Arc;
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::ui::AppEvent;
+use crate::wm::{WindowManager, WmFuture, WmOp};
+
+/// Stands in for our window id in the shared state. No window manager sees it.
+pub const SURFACE_ID: u64 = 0;
+
+/// A change to the overlay surface, carried out by its host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceRequest {
+    /// Map the surface with this size.
+    Show {
+        width: i32,
+        height: i32,
+    },
+    Hide,
+    /// Resize the surface, if it is mapped.
+    Resize {
+        width: i32,
+        height: i32,
+    },
+}
+
+/// Hands a request to the host, failing once the host is gone.
+pub type SurfaceSender = Box<dyn Fn(SurfaceRequest) -> anyhow::Result<()> + Send + Sync>;
+
+pub struct LayerShellBackend {
+    wm: Arc<dyn WindowManager>,
+    surface: SurfaceSender,
+}
+
+impl LayerShellBackend {
+    /// Show the overlay through `surface` and everything else through `wm`.
+    pub fn new(wm: Arc<dyn WindowManager>, surface: SurfaceSender) -> Self {
+        Self { wm, surface }
+    }
+}
+
+impl WindowManager for LayerShellBackend {
+    fn name(&self) -> &'static str {
+        self.wm.name()
+    }
+
+    fn run(&self, op: WmOp) -> WmFuture<'_, ()> {
+        let request = match op {
+            WmOp::Show { width, height, .. } => SurfaceRequest::Show { width, height },
+            WmOp::Hide { .. } => SurfaceRequest::Hide,
+            WmOp::Place { width, height, .. } => SurfaceRequest::Resize { width, height },
+            // The surface stays on the output it was mapped on. No backend
+            // says which output the selection is on.
+            WmOp::Follow { .. } => return Box::pin(async { Ok(()) }),
+            op => return self.wm.run(op),
+        };
+        let result = (self.surface)(request);
+        Box::pin(async move { result })
+    }
+
+    fn find_window<'a>(&'a self, name: &'a str) -> WmFuture<'a, Option<u64>> {
+        self.wm.find_window(name)
+    }
+
+    fn overlay_id(&self) -> Option<u64> {
+        Some(SURFACE_ID)
+    }
+
+    fn watch_focus(&self, tx: UnboundedSender<AppEvent>) -> WmFuture<'_, ()> {
+        self.wm.watch_focus(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records the operations that reach the window manager.
+    #[derive(Default)]
+    struct Recorder {
+        ops: Mutex<Vec<String>>,
+    }
+
+    impl WindowManager for Recorder {
+        fn name(&self) -> &'static str {
+            "recorder"
+        }
+
+        fn run(&self, op: WmOp) -> WmFuture<'_, ()> {
+            self.ops.lock().unwrap().push(op.to_string());
+            Box::pin(async { Ok(()) })
+        }
+
+        fn find_window<'a>(&'a self, _name: &'a str) -> WmFuture<'a, Option<u64>> {
+            Box::pin(async { Ok(None) })
+        }
+    }
+
+    #[tokio::test]
+    async fn our_window_goes_to_the_surface_and_the_rest_to_the_wm() {
+        let wm = Arc::new(Recorder::default());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let sink = requests.clone();
+        let backend = LayerShellBackend::new(
+            wm.clone(),
+            Box::new(move |request: SurfaceRequest| -> anyhow::Result<()> {
+                sink.lock().unwrap().push(request);
+                Ok(())
+            }),
+        );
+        assert_eq!(backend.overlay_id(), Some(SURFACE_ID));
+        assert_eq!(backend.name(), "recorder");
+
+        let wid = SURFACE_ID.to_string();
+        for op in [
+            WmOp::Show {
+                wid: wid.clone(),
+                width: 300,
+                height: 400,
+                selected: Some(7),
+            },
+            WmOp::Follow {
+                wid: wid.clone(),
+                width: 300,
+                height: 400,
+                selected: 8,
+            },
+            WmOp::Place {
+                wid: wid.clone(),
+                width: 300,
+                height: 500,
+                selected: Some(8),
+            },
+            WmOp::Focus { wid: 8 },
+            WmOp::Hide { wid },
+            WmOp::Commit { wid: Some(8) },
+        ] {
+            backend.run(op).await.unwrap();
+        }
+
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                SurfaceRequest::Show {
+                    width: 300,
+                    height: 400
+                },
+                SurfaceRequest::Resize {
+                    width: 300,
+                    height: 500
+                },
+                SurfaceRequest::Hide,
+            ]
+        );
+        assert_eq!(
+            *wm.ops.lock().unwrap(),
+            vec!["focus window 8", "commit selection of window 8"]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_vanished_host_fails_the_operation() {
+        let backend = LayerShellBackend::new(
+            Arc::new(Recorder::default()),
+            Box::new(|_: SurfaceRequest| -> anyhow::Result<()> {
+                anyhow::bail!("layer surface is gone")
+            }),
+        );
+        let error = backend
+            .run(WmOp::Hide {
+                wid: SURFACE_ID.to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "layer surface is gone");
+    }
+}
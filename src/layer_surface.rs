@@ -0,0 +1,851 @@
+//! Host for `wm.overlay: layer_shell`: draws the app on a wlr-layer-shell
+//! surface with `egui_glow`.
+//!
+//! The surface is created on [`SurfaceRequest::Show`] and destroyed on
+//! [`SurfaceRequest::Hide`], so a hidden overlay takes no space and no
+//! keyboard focus. Keys and pointer events come straight from the seat.
+//! Everything runs on one calloop event loop, woken by the Wayland socket,
+//! by requests from the window-manager queue and by egui repaint requests.
+
+use std::ffi::c_void;
+use std::num::NonZeroU32;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, anyhow};
+use egui_glow::glow;
+use glutin::config::{Config as EglConfig, ConfigTemplateBuilder};
+use glutin::context::{ContextAttributesBuilder, NotCurrentContext, PossiblyCurrentContext};
+use glutin::display::{Display, DisplayApiPreference};
+use glutin::prelude::*;
+use glutin::surface::{Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface};
+use raw_window_handle::{
+    RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
+};
+use smithay_client_toolkit::compositor::{CompositorHandler, CompositorState};
+use smithay_client_toolkit::output::{OutputHandler, OutputState};
+use smithay_client_toolkit::reexports::calloop::{self, EventLoop};
+use smithay_client_toolkit::reexports::calloop_wayland_source::WaylandSource;
+use smithay_client_toolkit::reexports::client::globals::registry_queue_init;
+use smithay_client_toolkit::reexports::client::protocol::{
+    wl_keyboard, wl_output, wl_pointer, wl_seat, wl_surface,
+};
+use smithay_client_toolkit::reexports::client::{Connection, Proxy, QueueHandle};
+use smithay_client_toolkit::registry::{ProvidesRegistryState, RegistryState};
+use smithay_client_toolkit::seat::keyboard::{
+    KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers,
+};
+use smithay_client_toolkit::seat::pointer::{PointerEvent, PointerEventKind, PointerHandler};
+use smithay_client_toolkit::seat::{Capability, SeatHandler, SeatState};
+use smithay_client_toolkit::shell::WaylandSurface;
+use smithay_client_toolkit::shell::wlr_layer::{
+    Anchor as LayerAnchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler,
+    LayerSurface, LayerSurfaceConfigure,
+};
+use smithay_client_toolkit::{
+    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
+    delegate_registry, delegate_seat, registry_handlers,
+};
+use wayland_egl::WlEglSurface;
+
+use crate::config::{Anchor, Config, LiveConfig, PlacementConfig, ScreenPolicy};
+use crate::layer_shell::{LayerShellBackend, SurfaceRequest};
+use crate::ui::AsyncApp;
+use crate::wm::WindowManager;
+
+/// Run the app on a layer surface until the connection to the compositor
+/// ends or the app is asked to quit.
+pub fn run(config: Config) -> anyhow::Result<()> {
+    let conn = Connection::connect_to_env().context("Failed to connect to the Wayland display")?;
+    let (globals, queue) = registry_queue_init::<Overlay>(&conn)?;
+    let qh = queue.handle();
+    let mut event_loop: EventLoop<Overlay> = EventLoop::try_new()?;
+    let handle = event_loop.handle();
+    WaylandSource::new(conn.clone(), queue)
+        .insert(handle.clone())
+        .map_err(|e| anyhow!("Failed to watch the Wayland socket: {}", e.error))?;
+
+    let compositor = CompositorState::bind(&globals, &qh)?;
+    let layer_shell =
+        LayerShell::bind(&globals, &qh).context("The compositor has no wlr-layer-shell")?;
+
+    let (requests, request_source) = calloop::channel::channel();
+    handle
+        .insert_source(request_source, |event, _, overlay: &mut Overlay| {
+            if let calloop::channel::Event::Msg(request) = event {
+                overlay.handle(request);
+            }
+        })
+        .map_err(|e| anyhow!("Failed to watch surface requests: {}", e.error))?;
+
+    // egui asks for repaints from any thread; the ping wakes the loop.
+    let (ping, ping_source) = calloop::ping::make_ping()?;
+    handle
+        .insert_source(ping_source, |_, _, _| {})
+        .map_err(|e| anyhow!("Failed to watch repaint requests: {}", e.error))?;
+    let repaint_at = Arc::new(Mutex::new(None));
+    let ctx = egui::Context::default();
+    let next_repaint = repaint_at.clone();
+    let ping = Mutex::new(ping);
+    ctx.set_request_repaint_callback(move |info| {
+        let at = Instant::now() + info.delay;
+        let mut next = next_repaint.lock().unwrap();
+        if next.is_none_or(|next| at < next) {
+            *next = Some(at);
+        }
+        ping.lock().unwrap().ping();
+    });
+
+    let requests = Mutex::new(requests);
+    let app = AsyncApp::with_config(&ctx, config, move |config| -> Arc<dyn WindowManager> {
+        let send = move |request| {
+            requests
+                .lock()
+                .unwrap()
+                .send(request)
+                .map_err(|_| anyhow!("The layer surface is gone"))
+        };
+        Arc::new(LayerShellBackend::new(
+            crate::wm::backend(config),
+            Box::new(send),
+        ))
+    });
+
+    let config = app.live_config();
+    let mut overlay = Overlay {
+        registry: RegistryState::new(&globals),
+        seat: SeatState::new(&globals, &qh),
+        output: OutputState::new(&globals, &qh),
+        compositor,
+        layer_shell,
+        qh,
+        config,
+        egl: Egl::new(&conn)?,
+        surface: None,
+        painter: None,
+        scale: 1,
+        keyboard: None,
+        pointer: None,
+        input: Input::default(),
+        ctx,
+        app,
+        repaint_at,
+    };
+
+    loop {
+        event_loop.dispatch(overlay.timeout(), &mut overlay)?;
+        if overlay.app.quit_requested() {
+            return Ok(());
+        }
+        if overlay.take_due_repaint()
+            && let Err(e) = overlay.draw()
+        {
+            log::warn!("Failed to draw the layer surface: {e:#}");
+        }
+    }
+}
+
+/// The layer-shell anchor and the margins, top, right, bottom and left, for
+/// a surface of `size` placed by `placement` on an output of logical size
+/// `output`.
+///
+/// The compositor centres the surface along an axis it is not anchored on.
+/// To offset it from there, it is anchored to the left or top edge with the
+/// margin that centres it, plus the offset. That takes the output size, so
+/// until it is known the surface stays centred.
+fn anchor_and_margins(
+    placement: &PlacementConfig,
+    size: (i32, i32),
+    output: Option<(i32, i32)>,
+) -> (LayerAnchor, [i32; 4]) {
+    let centred = |total: i32, length: i32, offset: i32| (total - length) / 2 + offset;
+    let mut anchor = LayerAnchor::empty();
+    let mut margins = [0; 4];
+    match placement.anchor {
+        Anchor::Top => {
+            anchor = LayerAnchor::TOP;
+            margins[0] = placement.offset_y;
+        }
+        Anchor::Bottom => {
+            anchor = LayerAnchor::BOTTOM;
+            margins[2] = -placement.offset_y;
+        }
+        Anchor::Center => {
+            if let Some((_, height)) = output.filter(|_| placement.offset_y != 0) {
+                anchor = LayerAnchor::TOP;
+                margins[0] = centred(height, size.1, placement.offset_y);
+            }
+        }
+    }
+    if let Some((width, _)) = output.filter(|_| placement.offset_x != 0) {
+        anchor |= LayerAnchor::LEFT;
+        margins[3] = centred(width, size.0, placement.offset_x);
+    }
+    (anchor, margins)
+}
+
+/// The egui key for the keys the overlay binds, see [`crate::ui::key_command`].
+fn egui_key(keysym: Keysym) -> Option<egui::Key> {
+    use egui::Key;
+    Some(match keysym {
+        // Shift+Tab arrives as ISO_Left_Tab.
+        Keysym::Tab | Keysym::ISO_Left_Tab => Key::Tab,
+        Keysym::Left => Key::ArrowLeft,
+        Keysym::Right => Key::ArrowRight,
+        Keysym::Up => Key::ArrowUp,
+        Keysym::Down => Key::ArrowDown,
+        Keysym::Return | Keysym::KP_Enter => Key::Enter,
+        Keysym::Escape => Key::Escape,
+        Keysym::Delete => Key::Delete,
+        Keysym::BackSpace => Key::Backspace,
+        _ => return None,
+    })
+}
+
+/// Linux input event codes of the mouse buttons.
+fn egui_button(button: u32) -> Option<egui::PointerButton> {
+    match button {
+        0x110 => Some(egui::PointerButton::Primary),
+        0x111 => Some(egui::PointerButton::Secondary),
+        0x112 => Some(egui::PointerButton::Middle),
+        _ => None,
+    }
+}
+
+fn non_zero(pixels: i32) -> NonZeroU32 {
+    NonZeroU32::new(pixels.max(1) as u32).unwrap()
+}
+
+/// The EGL display and the one GL context every surface draws with.
+struct Egl {
+    display: Display,
+    config: EglConfig,
+    /// Until it is first made current.
+    not_current: Option<NotCurrentContext>,
+    context: Option<PossiblyCurrentContext>,
+}
+
+impl Egl {
+    fn new(conn: &Connection) -> anyhow::Result<Self> {
+        let display = NonNull::new(conn.backend().display_ptr() as *mut c_void)
+            .context("No Wayland display to draw on")?;
+        let display = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(display));
+        let display = unsafe { Display::new(display, DisplayApiPreference::Egl) }
+            .context("Failed to set up EGL")?;
+        let template = ConfigTemplateBuilder::new().with_alpha_size(8).build();
+        let config = unsafe { display.find_configs(template) }?
+            .next()
+            .context("No EGL config with an alpha channel")?;
+        let attributes = ContextAttributesBuilder::new().build(None);
+        let context = unsafe { display.create_context(&config, &attributes) }?;
+        Ok(Self {
+            display,
+            config,
+            not_current: Some(context),
+            context: None,
+        })
+    }
+
+    fn window_surface(
+        &self,
+        window: &WlEglSurface,
+        width: i32,
+        height: i32,
+    ) -> anyhow::Result<Surface<WindowSurface>> {
+        let window = NonNull::new(window.ptr() as *mut c_void).context("No EGL window")?;
+        let window = RawWindowHandle::Wayland(WaylandWindowHandle::new(window));
+        let attributes = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+            window,
+            non_zero(width),
+            non_zero(height),
+        );
+        Ok(unsafe {
+            self.display
+                .create_window_surface(&self.config, &attributes)
+        }?)
+    }
+
+    fn make_current(
+        &mut self,
+        surface: &Surface<WindowSurface>,
+    ) -> anyhow::Result<&PossiblyCurrentContext> {
+        if let Some(context) = self.not_current.take() {
+            self.context = Some(context.make_current(surface)?);
+        } else if let Some(context) = &self.context {
+            context.make_current(surface)?;
+        }
+        self.context.as_ref().context("The GL context is gone")
+    }
+}
+
+/// The mapped overlay. Fields drop in order, so the EGL surfaces go before
+/// the Wayland surface under them.
+struct OverlaySurface {
+    gl: Option<Surface<WindowSurface>>,
+    window: Option<WlEglSurface>,
+    layer: LayerSurface,
+    /// The output asked for, or the one the surface entered.
+    output: Option<wl_output::WlOutput>,
+    /// Logical size, as last configured.
+    size: (i32, i32),
+    /// A frame callback is pending, so drawing now would only block.
+    waiting_frame: bool,
+}
+
+#[derive(Default)]
+struct Input {
+    events: Vec<egui::Event>,
+    modifiers: egui::Modifiers,
+}
+
+struct Overlay {
+    registry: RegistryState,
+    seat: SeatState,
+    output: OutputState,
+    compositor: CompositorState,
+    layer_shell: LayerShell,
+    qh: QueueHandle<Self>,
+    /// Read each time the surface is placed, so reloads apply to the next one.
+    config: LiveConfig,
+    egl: Egl,
+    surface: Option<OverlaySurface>,
+    painter: Option<egui_glow::Painter>,
+    /// Buffer scale of the output the surface is on.
+    scale: i32,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    pointer: Option<wl_pointer::WlPointer>,
+    input: Input,
+    ctx: egui::Context,
+    app: AsyncApp,
+    repaint_at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Overlay {
+    fn handle(&mut self, request: SurfaceRequest) {
+        log::debug!("Layer surface request: {request:?}");
+        match request {
+            SurfaceRequest::Show { width, height } => match &self.surface {
+                Some(surface) => {
+                    self.place(surface, width, height);
+                    surface.layer.commit();
+                }
+                None => self.surface = Some(self.create_surface(width, height)),
+            },
+            SurfaceRequest::Resize { width, height } => {
+                if let Some(surface) = &self.surface {
+                    self.place(surface, width, height);
+                    surface.layer.commit();
+                }
+            }
+            SurfaceRequest::Hide => self.surface = None,
+        }
+    }
+
+    fn create_surface(&self, width: i32, height: i32) -> OverlaySurface {
+        let surface = self.compositor.create_surface(&self.qh);
+        // Without an output, the compositor picks one, usually the focused one.
+        let placement = &self.config.get().placement;
+        let output = match placement.screen {
+            ScreenPolicy::Index => self.output.outputs().nth(placement.screen_index),
+            _ => None,
+        };
+        let layer = self.layer_shell.create_layer_surface(
+            &self.qh,
+            surface,
+            Layer::Overlay,
+            Some(crate::wm::OVERLAY_NAME),
+            output.as_ref(),
+        );
+        layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+        let surface = OverlaySurface {
+            gl: None,
+            window: None,
+            layer,
+            output,
+            size: (width, height),
+            waiting_frame: false,
+        };
+        self.place(&surface, width, height);
+        // Drawing waits for the first configure.
+        surface.layer.commit();
+        surface
+    }
+
+    /// Size `surface` and anchor it where the current placement asks.
+    /// The caller commits.
+    fn place(&self, surface: &OverlaySurface, width: i32, height: i32) {
+        let output = surface
+            .output
+            .as_ref()
+            .and_then(|output| self.output.info(output))
+            .and_then(|info| info.logical_size);
+        let (anchor, [top, right, bottom, left]) =
+            anchor_and_margins(&self.config.get().placement, (width, height), output);
+        surface.layer.set_anchor(anchor);
+        surface.layer.set_margin(top, right, bottom, left);
+        surface.layer.set_size(width as u32, height as u32);
+    }
+
+    /// Create or resize the EGL surfaces for the configured size and scale.
+    fn update_buffers(&mut self) -> anyhow::Result<()> {
+        let Some(surface) = &mut self.surface else {
+            return Ok(());
+        };
+        let (width, height) = (surface.size.0 * self.scale, surface.size.1 * self.scale);
+        if let (Some(window), Some(gl)) = (&surface.window, &surface.gl) {
+            window.resize(width, height, 0, 0);
+            let context = self.egl.make_current(gl)?;
+            gl.resize(context, non_zero(width), non_zero(height));
+            return Ok(());
+        }
+        let window = WlEglSurface::new(surface.layer.wl_surface().id(), width, height)?;
+        let gl = self.egl.window_surface(&window, width, height)?;
+        let context = self.egl.make_current(&gl)?;
+        // Frame callbacks pace drawing instead.
+        gl.set_swap_interval(context, SwapInterval::DontWait)?;
+        surface.window = Some(window);
+        surface.gl = Some(gl);
+        Ok(())
+    }
+
+    /// How long the loop may sleep before the next repaint is due.
+    fn timeout(&self) -> Option<Duration> {
+        if self
+            .surface
+            .as_ref()
+            .is_none_or(|s| s.gl.is_none() || s.waiting_frame)
+        {
+            return None;
+        }
+        let at = (*self.repaint_at.lock().unwrap())?;
+        Some(at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Whether to draw now. A repaint asked for while hidden is dropped.
+    fn take_due_repaint(&mut self) -> bool {
+        let mut repaint_at = self.repaint_at.lock().unwrap();
+        if repaint_at.is_none_or(|at| at > Instant::now()) {
+            return false;
+        }
+        match &self.surface {
+            None => {
+                *repaint_at = None;
+                false
+            }
+            Some(surface) if surface.gl.is_none() || surface.waiting_frame => false,
+            Some(_) => {
+                *repaint_at = None;
+                true
+            }
+        }
+    }
+
+    fn draw(&mut self) -> anyhow::Result<()> {
+        let Some(surface) = &mut self.surface else {
+            return Ok(());
+        };
+        let Some(gl) = &surface.gl else {
+            return Ok(());
+        };
+        let context = self.egl.make_current(gl)?;
+        let painter = match &mut self.painter {
+            Some(painter) => painter,
+            None => {
+                let display = &self.egl.display;
+                let gl = unsafe {
+                    glow::Context::from_loader_function_cstr(|name| display.get_proc_address(name))
+                };
+                let painter = egui_glow::Painter::new(Arc::new(gl), "", None, false)
+                    .map_err(|e| anyhow!("Failed to set up egui_glow: {e:?}"))?;
+                self.painter.insert(painter)
+            }
+        };
+
+        let (width, height) = surface.size;
+        let mut input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(width as f32, height as f32),
+            )),
+            events: std::mem::take(&mut self.input.events),
+            modifiers: self.input.modifiers,
+            focused: true,
+            ..egui::RawInput::default()
+        };
+        input
+            .viewports
+            .entry(egui::ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(self.scale as f32);
+        let app = &mut self.app;
+        let output = self.ctx.run_ui(input, |ui| app.draw(ui));
+
+        let size = [(width * self.scale) as u32, (height * self.scale) as u32];
+        let primitives = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        painter.clear(size, [0.0; 4]);
+        painter.paint_and_update_textures(
+            size,
+            output.pixels_per_point,
+            &primitives,
+            &output.textures_delta,
+        );
+        let wl_surface = surface.layer.wl_surface();
+        wl_surface.frame(&self.qh, wl_surface.clone());
+        gl.swap_buffers(context)?;
+        surface.waiting_frame = true;
+        Ok(())
+    }
+
+    fn key(&mut self, event: KeyEvent, pressed: bool) {
+        if let Some(key) = egui_key(event.keysym) {
+            self.input.events.push(egui::Event::Key {
+                key,
+                physical_key: None,
+                pressed,
+                repeat: false,
+                modifiers: self.input.modifiers,
+            });
+        } else if pressed
+            && let Some(text) = event.utf8.filter(|t| !t.chars().any(char::is_control))
+        {
+            self.input.events.push(egui::Event::Text(text));
+        }
+        self.ctx.request_repaint();
+    }
+}
+
+impl CompositorHandler for Overlay {
+    fn scale_factor_changed(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        self.scale = new_factor.max(1);
+        surface.set_buffer_scale(self.scale);
+        if let Err(e) = self.update_buffers() {
+            log::warn!("Failed to rescale the layer surface: {e:#}");
+        }
+        self.ctx.request_repaint();
+    }
+
+    fn transform_changed(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_surface::WlSurface,
+        _: wl_output::Transform,
+    ) {
+    }
+
+    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {
+        if let Some(surface) = &mut self.surface {
+            surface.waiting_frame = false;
+        }
+    }
+
+    fn surface_enter(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
+    ) {
+        // Offsets from the centre take the size of the output the
+        // compositor picked.
+        let Some(surface) = &mut self.surface else {
+            return;
+        };
+        if surface.output.as_ref() == Some(output) {
+            return;
+        }
+        surface.output = Some(output.clone());
+        if let Some(surface) = &self.surface {
+            self.place(surface, surface.size.0, surface.size.1);
+            surface.layer.commit();
+        }
+    }
+
+    fn surface_leave(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_surface::WlSurface,
+        _: &wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl OutputHandler for Overlay {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output
+    }
+
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {}
+}
+
+impl LayerShellHandler for Overlay {
+    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &LayerSurface) {
+        log::warn!("The compositor closed the layer surface");
+        self.surface = None;
+    }
+
+    fn configure(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &LayerSurface,
+        configure: LayerSurfaceConfigure,
+        _: u32,
+    ) {
+        let Some(surface) = &mut self.surface else {
+            return;
+        };
+        // Zero leaves the size to us.
+        let (width, height) = configure.new_size;
+        if width > 0 {
+            surface.size.0 = width as i32;
+        }
+        if height > 0 {
+            surface.size.1 = height as i32;
+        }
+        surface.waiting_frame = false;
+        if let Err(e) = self.update_buffers() {
+            log::warn!("Failed to set up the layer surface: {e:#}");
+        }
+        self.ctx.request_repaint();
+    }
+}
+
+impl SeatHandler for Overlay {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat
+    }
+
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+
+    fn new_capability(
+        &mut self,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        match capability {
+            Capability::Keyboard if self.keyboard.is_none() => {
+                match self.seat.get_keyboard(qh, &seat, None) {
+                    Ok(keyboard) => self.keyboard = Some(keyboard),
+                    Err(e) => log::warn!("Failed to get the keyboard: {e}"),
+                }
+            }
+            Capability::Pointer if self.pointer.is_none() => match self.seat.get_pointer(qh, &seat)
+            {
+                Ok(pointer) => self.pointer = Some(pointer),
+                Err(e) => log::warn!("Failed to get the pointer: {e}"),
+            },
+            _ => {}
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        match capability {
+            Capability::Keyboard => {
+                if let Some(keyboard) = self.keyboard.take() {
+                    keyboard.release();
+                }
+            }
+            Capability::Pointer => {
+                if let Some(pointer) = self.pointer.take() {
+                    pointer.release();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl KeyboardHandler for Overlay {
+    fn enter(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _: &wl_surface::WlSurface,
+        _: u32,
+        _: &[u32],
+        _: &[Keysym],
+    ) {
+    }
+
+    fn leave(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _: &wl_surface::WlSurface,
+        _: u32,
+    ) {
+    }
+
+    fn press_key(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _: u32,
+        event: KeyEvent,
+    ) {
+        self.key(event, true);
+    }
+
+    fn release_key(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _: u32,
+        event: KeyEvent,
+    ) {
+        self.key(event, false);
+    }
+
+    fn update_modifiers(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _: u32,
+        modifiers: Modifiers,
+        _: RawModifiers,
+        _: u32,
+    ) {
+        self.input.modifiers = egui::Modifiers {
+            alt: modifiers.alt,
+            ctrl: modifiers.ctrl,
+            shift: modifiers.shift,
+            mac_cmd: false,
+            command: modifiers.ctrl,
+        };
+    }
+}
+
+impl PointerHandler for Overlay {
+    fn pointer_frame(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_pointer::WlPointer,
+        events: &[PointerEvent],
+    ) {
+        for event in events {
+            let pos = egui::pos2(event.position.0 as f32, event.position.1 as f32);
+            let event = match &event.kind {
+                PointerEventKind::Enter { .. } | PointerEventKind::Motion { .. } => {
+                    egui::Event::PointerMoved(pos)
+                }
+                PointerEventKind::Leave { .. } => egui::Event::PointerGone,
+                PointerEventKind::Press { button, .. }
+                | PointerEventKind::Release { button, .. } => {
+                    let Some(button) = egui_button(*button) else {
+                        continue;
+                    };
+                    egui::Event::PointerButton {
+                        pos,
+                        button,
+                        pressed: matches!(event.kind, PointerEventKind::Press { .. }),
+                        modifiers: self.input.modifiers,
+                    }
+                }
+                _ => continue,
+            };
+            self.input.events.push(event);
+        }
+        self.ctx.request_repaint();
+    }
+}
+
+impl ProvidesRegistryState for Overlay {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry
+    }
+
+    registry_handlers![OutputState, SeatState];
+}
+
+delegate_compositor!(Overlay);
+delegate_output!(Overlay);
+delegate_seat!(Overlay);
+delegate_keyboard!(Overlay);
+delegate_pointer!(Overlay);
+delegate_layer!(Overlay);
+delegate_registry!(Overlay);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchors_become_edges_and_margins() {
+        let size = (400, 300);
+        let output = Some((1920, 1080));
+        let mut placement = PlacementConfig::default();
+        assert_eq!(
+            anchor_and_margins(&placement, size, output),
+            (LayerAnchor::empty(), [0; 4])
+        );
+        placement.anchor = Anchor::Top;
+        placement.offset_y = 40;
+        assert_eq!(
+            anchor_and_margins(&placement, size, None),
+            (LayerAnchor::TOP, [40, 0, 0, 0])
+        );
+        placement.anchor = Anchor::Bottom;
+        placement.offset_y = -40;
+        assert_eq!(
+            anchor_and_margins(&placement, size, None),
+            (LayerAnchor::BOTTOM, [0, 0, 40, 0])
+        );
+    }
+
+    #[test]
+    fn offsets_from_the_centre_need_the_output_size() {
+        let size = (400, 300);
+        let placement = PlacementConfig {
+            offset_x: 100,
+            offset_y: -30,
+            ..PlacementConfig::default()
+        };
+        assert_eq!(
+            anchor_and_margins(&placement, size, None),
+            (LayerAnchor::empty(), [0; 4])
+        );
+        assert_eq!(
+            anchor_and_margins(&placement, size, Some((1920, 1080))),
+            (LayerAnchor::TOP | LayerAnchor::LEFT, [360, 0, 0, 860])
+        );
+    }
+
+    #[test]
+    fn overlay_keys_map_to_egui() {
+        assert_eq!(egui_key(Keysym::ISO_Left_Tab), Some(egui::Key::Tab));
+        assert_eq!(egui_key(Keysym::KP_Enter), Some(egui::Key::Enter));
+        assert_eq!(egui_key(Keysym::BackSpace), Some(egui::Key::Backspace));
+        assert_eq!(egui_key(Keysym::a), None);
+        assert_eq!(egui_button(0x110), Some(egui::PointerButton::Primary));
+        assert_eq!(egui_button(0x113), None);
+    }
+}
@@ -1,5 +1,18 @@
 pub mod args;
 pub mod config;
+pub mod ctl;
+pub mod evdev;
+pub mod hyprland;
+pub mod instance;
 pub mod ipc;
+pub mod layer_shell;
+#[cfg(feature = "layer-shell")]
+pub mod layer_surface;
+pub mod placement;
 pub mod qaltd;
+pub mod qtile;
+pub mod search;
+pub mod sway;
+pub mod thumbnails;
 pub mod ui;
+pub mod wm;
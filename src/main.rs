@@ -1,5 +1,5 @@
 use clap::Parser;
-use qalttab::args::Args;
+use qalttab::args::{Args, Command};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -10,12 +10,17 @@ async fn main() -> anyhow::Result<()> {
         .with_colors(true)
         .env()
         .init()?;
-    let _args: Args = Args::parse();
+    let args: Args = Args::parse();
+
+    if let Some(Command::Ctl { action }) = args.command {
+        return qalttab::ctl::run(action, None).await;
+    }
 
     // Unset DISPLAY to prevent arboard (used by eframe) from hanging on Xwayland connections
     unsafe {
         std::env::remove_var("DISPLAY");
     }
 
+    let _instance = qalttab::instance::acquire(None, args.replace).await?;
     qalttab::ui::run_ui()
 }
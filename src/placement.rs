@@ -0,0 +1,152 @@
+//! Where the overlay goes on a multi-monitor setup.
+//!
+//! Backends gather what they know about the screens, the pointer and the
+//! selected window; [`pick_screen`] chooses a screen by the `placement`
+//! policy and [`ScreenGeometry::position`] anchors the overlay on it.
+
+use serde::Deserialize;
+
+use crate::config::{Anchor, PlacementConfig, ScreenPolicy};
+
+/// A screen, or any rectangle, in global layout coordinates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct ScreenGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl ScreenGeometry {
+    /// Top-left corner of a `width` x `height` window centred on this screen.
+    pub fn centered(&self, width: i32, height: i32) -> (i32, i32) {
+        (
+            self.x + (self.width - width) / 2,
+            self.y + (self.height - height) / 2,
+        )
+    }
+
+    /// Top-left corner of a `width` x `height` window at `placement`'s
+    /// anchor on this screen, moved by its offsets.
+    pub fn position(&self, width: i32, height: i32, placement: &PlacementConfig) -> (i32, i32) {
+        let (x, y) = self.centered(width, height);
+        let y = match placement.anchor {
+            Anchor::Center => y,
+            Anchor::Top => self.y,
+            Anchor::Bottom => self.y + self.height - height,
+        };
+        (x + placement.offset_x, y + placement.offset_y)
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+}
+
+/// Index of the screen `placement` asks for among `screens`, given the
+/// pointer position or the centre of the selected window as `point`.
+///
+/// `None` means the focused screen: for the `focused` policy, and whenever
+/// the point or the index does not name one of `screens`.
+pub fn pick_screen(
+    placement: &PlacementConfig,
+    screens: &[ScreenGeometry],
+    point: Option<(i32, i32)>,
+) -> Option<usize> {
+    match placement.screen {
+        ScreenPolicy::Focused => None,
+        ScreenPolicy::Index => {
+            (placement.screen_index < screens.len()).then_some(placement.screen_index)
+        }
+        ScreenPolicy::Pointer | ScreenPolicy::Selection => {
+            let point = point?;
+            screens.iter().position(|screen| screen.contains(point))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: ScreenGeometry = ScreenGeometry {
+        x: 0,
+        y: 0,
+        width: 1920,
+        height: 1080,
+    };
+    const RIGHT: ScreenGeometry = ScreenGeometry {
+        x: 1920,
+        y: 0,
+        width: 2560,
+        height: 1440,
+    };
+
+    fn placement(screen: ScreenPolicy) -> PlacementConfig {
+        PlacementConfig {
+            screen,
+            ..PlacementConfig::default()
+        }
+    }
+
+    #[test]
+    fn centered_accounts_for_screen_offset() {
+        let screen = ScreenGeometry {
+            x: 1920,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        };
+        assert_eq!(screen.centered(300, 400), (2730, 340));
+    }
+
+    #[test]
+    fn anchors_and_offsets_move_the_overlay() {
+        let mut placement = PlacementConfig::default();
+        assert_eq!(RIGHT.position(300, 400, &placement), (3050, 520));
+        placement.anchor = Anchor::Top;
+        placement.offset_y = 40;
+        assert_eq!(RIGHT.position(300, 400, &placement), (3050, 40));
+        placement.anchor = Anchor::Bottom;
+        placement.offset_x = -100;
+        placement.offset_y = -40;
+        assert_eq!(RIGHT.position(300, 400, &placement), (2950, 1000));
+    }
+
+    #[test]
+    fn picks_the_screen_for_each_policy() {
+        let screens = [LEFT, RIGHT];
+        let inside_right = Some((2000, 100));
+        assert_eq!(
+            pick_screen(&placement(ScreenPolicy::Focused), &screens, inside_right),
+            None
+        );
+        assert_eq!(
+            pick_screen(&placement(ScreenPolicy::Pointer), &screens, inside_right),
+            Some(1)
+        );
+        assert_eq!(
+            pick_screen(&placement(ScreenPolicy::Selection), &screens, Some((0, 0))),
+            Some(0)
+        );
+        // Off every screen, or nothing known: the focused screen.
+        assert_eq!(
+            pick_screen(&placement(ScreenPolicy::Pointer), &screens, Some((-5, 0))),
+            None
+        );
+        assert_eq!(
+            pick_screen(&placement(ScreenPolicy::Selection), &screens, None),
+            None
+        );
+
+        let mut index = placement(ScreenPolicy::Index);
+        index.screen_index = 1;
+        assert_eq!(pick_screen(&index, &screens, None), Some(1));
+        index.screen_index = 2;
+        assert_eq!(pick_screen(&index, &screens, None), None);
+    }
+}
@@ -1,35 +1,445 @@
-use crate::ui::AppEvent;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
-use tokio::sync::mpsc::UnboundedSender;
+use crate::config::{InputBackend, InputConfig, ModifierKey};
+use crate::evdev::{self, KeyEvent, KeyState, MODIFIER_KEYS};
+use crate::ui::{AppEvent, SharedState};
+use anyhow::{Context, bail};
+use serde::Serialize;
+use std::{
+    future::Future,
+    path::Path,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// The modifier keys whose release commits the selection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseKeys {
+    codes: Vec<u16>,
+}
+
+impl Default for ReleaseKeys {
+    fn default() -> Self {
+        Self::new(&InputConfig::default().release_keys)
+    }
+}
+
+impl ReleaseKeys {
+    /// Resolve configured keys to keycodes. Unknown names are skipped with a
+    /// warning; if nothing is left, both Alt keys are used.
+    pub fn new(keys: &[ModifierKey]) -> Self {
+        let mut codes = Vec::new();
+        for key in keys {
+            let resolved = resolve_key(key);
+            if resolved.is_empty() {
+                log::warn!("Unknown release key {key:?}, ignoring it");
+            }
+            for code in resolved {
+                if !codes.contains(&code) {
+                    codes.push(code);
+                }
+            }
+        }
+        if codes.is_empty() {
+            log::warn!("No usable release keys configured, using Alt");
+            codes = vec![evdev::KEY_LEFTALT, evdev::KEY_RIGHTALT];
+        }
+        Self { codes }
+    }
+
+    pub fn codes(&self) -> &[u16] {
+        &self.codes
+    }
+
+    /// Match a `libinput debug-events --show-keycodes` line such as
+    /// `event5  KEYBOARD_KEY  +0.001s  KEY_LEFTALT (56) released`.
+    pub fn matches_line(&self, line: &str) -> bool {
+        if !line.contains("released") {
+            return false;
+        }
+        self.codes.iter().any(|code| {
+            line.contains(&format!("({code})"))
+                || key_name(*code)
+                    .is_some_and(|name| line.split_whitespace().any(|token| token == name))
+        })
+    }
+
+    pub fn matches_event(&self, event: &KeyEvent) -> bool {
+        event.state == KeyState::Released && self.codes.contains(&event.code)
+    }
+}
+
+fn key_name(code: u16) -> Option<&'static str> {
+    MODIFIER_KEYS
+        .iter()
+        .find(|(_, c)| *c == code)
+        .map(|(name, _)| *name)
+}
+
+fn resolve_key(key: &ModifierKey) -> Vec<u16> {
+    let name = match key {
+        ModifierKey::Code(code) => return vec![*code],
+        ModifierKey::Name(name) => name.trim().to_ascii_uppercase(),
+    };
+    let sides = |suffix: &str| -> Vec<u16> {
+        MODIFIER_KEYS
+            .iter()
+            .filter(|(n, _)| n.ends_with(suffix))
+            .map(|(_, code)| *code)
+            .collect()
+    };
+    match name.as_str() {
+        "ALT" => sides("ALT"),
+        "SUPER" | "META" | "LOGO" => sides("META"),
+        "CTRL" | "CONTROL" => sides("CTRL"),
+        "SHIFT" => sides("SHIFT"),
+        _ => {
+            let full = if name.starts_with("KEY_") {
+                name
+            } else {
+                format!("KEY_{name}")
+            };
+            MODIFIER_KEYS
+                .iter()
+                .filter(|(n, _)| *n == full)
+                .map(|(_, code)| *code)
+                .collect()
+        }
+    }
+}
 
 pub fn is_alt_release_event(line: &str) -> bool {
-    (line.contains("KEY_LEFTALT") || line.contains("KEY_RIGHTALT")) && line.contains("released")
+    ReleaseKeys::default().matches_line(line)
+}
+
+pub type KeySourceFuture<'a> =
+    Pin<Box<dyn Future<Output = anyhow::Result<Option<AppEvent>>> + Send + 'a>>;
+
+/// Something that tells us when the release keys go up.
+pub trait KeySource: Send {
+    /// Short name used in logs.
+    fn name(&self) -> &'static str;
+
+    /// Wait for the next release and return the event to send to the app,
+    /// normally `AppEvent::AltReleased`. `Ok(None)` means the source is exhausted.
+    fn next_event(&mut self) -> KeySourceFuture<'_>;
+}
+
+/// Reads `libinput debug-events` output from a child process.
+pub struct LibinputKeySource {
+    keys: ReleaseKeys,
+    _child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+}
+
+impl LibinputKeySource {
+    pub fn spawn(keys: ReleaseKeys) -> anyhow::Result<Self> {
+        let mut child = Command::new("libinput")
+            .args(["debug-events", "--show-keycodes"])
+            .stdout(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to start libinput")?;
+        let stdout = child
+            .stdout
+            .take()
+            .context("Failed to capture libinput stdout")?;
+        Ok(Self {
+            keys,
+            _child: child,
+            lines: BufReader::new(stdout).lines(),
+        })
+    }
+}
+
+impl KeySource for LibinputKeySource {
+    fn name(&self) -> &'static str {
+        "libinput"
+    }
+
+    fn next_event(&mut self) -> KeySourceFuture<'_> {
+        Box::pin(async move {
+            while let Some(line) = self.lines.next_line().await? {
+                if self.keys.matches_line(&line) {
+                    return Ok(Some(AppEvent::AltReleased));
+                }
+            }
+            Ok(None)
+        })
+    }
+}
+
+/// Reads keyboards under `/dev/input` directly.
+pub struct EvdevKeySource {
+    keys: ReleaseKeys,
+    key_rx: UnboundedReceiver<KeyEvent>,
+    watcher: Option<JoinHandle<anyhow::Result<()>>>,
+}
+
+impl EvdevKeySource {
+    pub fn start(keys: ReleaseKeys) -> Self {
+        let (key_tx, key_rx) = mpsc::unbounded_channel();
+        Self {
+            keys,
+            key_rx,
+            watcher: Some(tokio::spawn(evdev::watch_keyboards(key_tx))),
+        }
+    }
+}
+
+impl KeySource for EvdevKeySource {
+    fn name(&self) -> &'static str {
+        "evdev"
+    }
+
+    fn next_event(&mut self) -> KeySourceFuture<'_> {
+        Box::pin(async move {
+            while let Some(event) = self.key_rx.recv().await {
+                if self.keys.matches_event(&event) {
+                    return Ok(Some(AppEvent::AltReleased));
+                }
+            }
+            match self.watcher.take() {
+                Some(watcher) => watcher.await?.map(|()| None),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+impl Drop for EvdevKeySource {
+    /// Aborting the watcher also drops the device readers it owns.
+    fn drop(&mut self) {
+        if let Some(watcher) = &self.watcher {
+            watcher.abort();
+        }
+    }
+}
+
+/// One step of a scripted key sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptStep {
+    /// A release key went up.
+    Release,
+    /// Pause before the next step.
+    Wait(Duration),
+}
+
+impl FromStr for ScriptStep {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> anyhow::Result<Self> {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("release"), None, None) => Ok(Self::Release),
+            (Some("wait"), Some(ms), None) => Ok(Self::Wait(Duration::from_millis(
+                ms.parse().with_context(|| format!("invalid wait {ms:?}"))?,
+            ))),
+            _ => bail!("unknown script step {line:?}"),
+        }
+    }
+}
+
+/// Replays releases from a list, a file or a channel. Used for testing
+/// without a keyboard.
+pub struct ScriptedKeySource {
+    steps: UnboundedReceiver<ScriptStep>,
+}
+
+impl ScriptedKeySource {
+    /// Steps are fed as they arrive; the source ends when every sender is dropped.
+    pub fn from_channel(steps: UnboundedReceiver<ScriptStep>) -> Self {
+        Self { steps }
+    }
+
+    pub fn from_steps(steps: impl IntoIterator<Item = ScriptStep>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for step in steps {
+            tx.send(step).ok();
+        }
+        Self::from_channel(rx)
+    }
+
+    /// Read a script with one `release` or `wait <ms>` per line. Blank lines
+    /// and lines starting with `#` are skipped.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let script = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read key script {path:?}"))?;
+        let steps = script
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::parse)
+            .collect::<anyhow::Result<Vec<ScriptStep>>>()?;
+        Ok(Self::from_steps(steps))
+    }
+}
+
+impl KeySource for ScriptedKeySource {
+    fn name(&self) -> &'static str {
+        "scripted"
+    }
+
+    fn next_event(&mut self) -> KeySourceFuture<'_> {
+        Box::pin(async move {
+            while let Some(step) = self.steps.recv().await {
+                match step {
+                    ScriptStep::Release => return Ok(Some(AppEvent::AltReleased)),
+                    ScriptStep::Wait(duration) => tokio::time::sleep(duration).await,
+                }
+            }
+            Ok(None)
+        })
+    }
 }
 
-pub async fn listen_for_alt_release(
+/// Pick the key source for `input.backend`.
+pub fn key_source(input: &InputConfig) -> anyhow::Result<Box<dyn KeySource>> {
+    let keys = ReleaseKeys::new(&input.release_keys);
+    Ok(match input.backend {
+        InputBackend::Evdev => Box::new(EvdevKeySource::start(keys)),
+        InputBackend::Libinput => Box::new(LibinputKeySource::spawn(keys)?),
+        InputBackend::Auto => {
+            if evdev::readable_keyboards().is_empty() {
+                log::warn!(
+                    "No readable keyboard in /dev/input (is the user in the input group?), falling back to libinput"
+                );
+                Box::new(LibinputKeySource::spawn(keys)?)
+            } else {
+                Box::new(EvdevKeySource::start(keys))
+            }
+        }
+    })
+}
+
+/// Forward every event from `source` to the app until the source ends.
+pub async fn forward_key_events(
+    mut source: Box<dyn KeySource>,
     tx: UnboundedSender<AppEvent>,
     ctx: egui::Context,
 ) -> anyhow::Result<()> {
-    let mut child = Command::new("libinput")
-        .args(["debug-events", "--show-keycodes"])
-        .stdout(std::process::Stdio::piped())
-        .spawn()
-        .expect("Failed to start libinput");
+    log::info!("Listening for release keys with {}", source.name());
+    while let Some(event) = source.next_event().await? {
+        log::debug!("Key source event: {event:?}");
+        if tx.send(event).is_err() {
+            break;
+        }
+        ctx.request_repaint();
+    }
+    Ok(())
+}
 
-    let stdout = child.stdout.take().expect("Failed to capture stdout");
-    let reader = BufReader::new(stdout);
-    let mut lines = reader.lines();
+/// Health of the key listener, reported by the `get_state` query.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum KeyListenerStatus {
+    #[default]
+    Starting,
+    Running {
+        source: String,
+    },
+    /// The listener died and is waiting to be restarted. Releases are missed meanwhile.
+    Degraded {
+        restarts: u32,
+        last_error: String,
+        retry_in_ms: u64,
+    },
+}
 
-    while let Some(line) = lines.next_line().await? {
-        if is_alt_release_event(&line) {
-            log::debug!("Alt released");
-            tx.send(AppEvent::AltReleased).ok();
-            ctx.request_repaint();
+impl KeyListenerStatus {
+    pub fn is_degraded(&self) -> bool {
+        matches!(self, Self::Degraded { .. })
+    }
+}
+
+/// Exponential restart delay, reset once a listener has stayed up for `stable_after`.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub stable_after: Duration,
+    next: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_millis(500),
+            Duration::from_secs(30),
+            Duration::from_secs(60),
+        )
+    }
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration, stable_after: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            stable_after,
+            next: initial,
         }
     }
 
-    Ok(())
+    /// Delay before the next restart of a listener that ran for `uptime`.
+    pub fn next_delay(&mut self, uptime: Duration) -> Duration {
+        if uptime >= self.stable_after {
+            self.next = self.initial;
+        }
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+}
+
+/// Keep a key source running: whenever it fails or ends, mark the listener
+/// degraded in `shared` and start a new one from `make_source` after a backoff.
+///
+/// Returns once the app side of `tx` is gone.
+pub async fn supervise_key_source<F>(
+    mut make_source: F,
+    tx: UnboundedSender<AppEvent>,
+    ctx: egui::Context,
+    shared: Arc<Mutex<SharedState>>,
+    mut backoff: Backoff,
+) where
+    F: FnMut() -> anyhow::Result<Box<dyn KeySource>> + Send,
+{
+    let mut restarts = 0;
+    loop {
+        let started = Instant::now();
+        let result = match make_source() {
+            Ok(source) => {
+                shared.lock().unwrap().key_listener = KeyListenerStatus::Running {
+                    source: source.name().to_owned(),
+                };
+                forward_key_events(source, tx.clone(), ctx.clone()).await
+            }
+            Err(e) => Err(e),
+        };
+        if tx.is_closed() {
+            return;
+        }
+
+        let last_error = match result {
+            Ok(()) => "key source ended".to_owned(),
+            Err(e) => format!("{e:#}"),
+        };
+        let delay = backoff.next_delay(started.elapsed());
+        restarts += 1;
+        log::warn!("Key listener stopped ({last_error}), restarting in {delay:?}");
+        shared.lock().unwrap().key_listener = KeyListenerStatus::Degraded {
+            restarts,
+            last_error,
+            retry_in_ms: delay.as_millis() as u64,
+        };
+        ctx.request_repaint();
+        tokio::time::sleep(delay).await;
+    }
 }
 
 #[cfg(test)]
@@ -68,4 +478,280 @@ mod tests {
     fn empty_line_is_not_an_alt_release() {
         assert!(!is_alt_release_event(""));
     }
+
+    fn keys(names: &[&str]) -> ReleaseKeys {
+        ReleaseKeys::new(
+            &names
+                .iter()
+                .map(|name| ModifierKey::Name(name.to_string()))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn super_release_is_detected_when_configured() {
+        let keys = keys(&["super"]);
+        assert!(keys.matches_line("event5  KEYBOARD_KEY  +0.001s  KEY_LEFTMETA (125) released"));
+        assert!(keys.matches_line("event5  KEYBOARD_KEY  +0.001s  KEY_RIGHTMETA (126) released"));
+        assert!(!keys.matches_line("event5  KEYBOARD_KEY  +0.001s  KEY_LEFTALT (56) released"));
+    }
+
+    #[test]
+    fn ctrl_release_is_detected_by_full_name() {
+        let keys = keys(&["KEY_LEFTCTRL"]);
+        assert!(keys.matches_line("event5  KEYBOARD_KEY  +0.001s  KEY_LEFTCTRL (29) released"));
+        assert!(!keys.matches_line("event5  KEYBOARD_KEY  +0.001s  KEY_RIGHTCTRL (97) released"));
+        assert!(!keys.matches_line("event5  KEYBOARD_KEY  +0.001s  KEY_LEFTCTRL (29) pressed"));
+    }
+
+    #[test]
+    fn names_are_case_insensitive_and_prefix_optional() {
+        assert_eq!(keys(&["leftmeta"]).codes(), &[evdev::KEY_LEFTMETA]);
+        assert_eq!(keys(&["Key_RightAlt"]).codes(), &[evdev::KEY_RIGHTALT]);
+    }
+
+    #[test]
+    fn keycodes_are_matched_without_names() {
+        let keys = ReleaseKeys::new(&[ModifierKey::Code(183)]);
+        assert!(keys.matches_line("event5  KEYBOARD_KEY  +0.001s  KEY_F13 (183) released"));
+        assert!(!keys.matches_line("event5  KEYBOARD_KEY  +0.001s  KEY_F14 (184) released"));
+    }
+
+    #[test]
+    fn unknown_names_fall_back_to_alt() {
+        assert_eq!(
+            keys(&["hyper"]).codes(),
+            &[evdev::KEY_LEFTALT, evdev::KEY_RIGHTALT]
+        );
+    }
+
+    #[test]
+    fn alt_is_not_matched_by_a_longer_key_name() {
+        let keys = ReleaseKeys::new(&[ModifierKey::Name("KEY_LEFTALT".into())]);
+        assert!(!keys.matches_line("event5  KEYBOARD_KEY  +0.001s  KEY_LEFTALTX (250) released"));
+    }
+
+    #[test]
+    fn script_steps_parse() {
+        assert_eq!(
+            "release".parse::<ScriptStep>().unwrap(),
+            ScriptStep::Release
+        );
+        assert_eq!(
+            " wait 150 ".parse::<ScriptStep>().unwrap(),
+            ScriptStep::Wait(Duration::from_millis(150))
+        );
+        assert!("wait".parse::<ScriptStep>().is_err());
+        assert!("wait soon".parse::<ScriptStep>().is_err());
+        assert!("press".parse::<ScriptStep>().is_err());
+    }
+
+    #[tokio::test]
+    async fn scripted_source_replays_steps_then_ends() {
+        let mut source = ScriptedKeySource::from_steps([
+            ScriptStep::Release,
+            ScriptStep::Wait(Duration::from_millis(1)),
+            ScriptStep::Release,
+        ]);
+        assert!(matches!(
+            source.next_event().await.unwrap(),
+            Some(AppEvent::AltReleased)
+        ));
+        assert!(matches!(
+            source.next_event().await.unwrap(),
+            Some(AppEvent::AltReleased)
+        ));
+        assert!(source.next_event().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn scripted_source_reads_file() {
+        let path = std::env::temp_dir().join("qalttab_key_script_test");
+        std::fs::write(&path, "# comment\n\nwait 1\nrelease\n").unwrap();
+        let mut source = ScriptedKeySource::from_file(&path).unwrap();
+        assert!(source.next_event().await.unwrap().is_some());
+        assert!(source.next_event().await.unwrap().is_none());
+        std::fs::remove_file(path).ok();
+    }
+
+    #[tokio::test]
+    async fn forward_stops_when_source_ends() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let source = ScriptedKeySource::from_steps([ScriptStep::Release, ScriptStep::Release]);
+        forward_key_events(Box::new(source), tx, egui::Context::default())
+            .await
+            .unwrap();
+        assert!(matches!(rx.recv().await, Some(AppEvent::AltReleased)));
+        assert!(matches!(rx.recv().await, Some(AppEvent::AltReleased)));
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut backoff = Backoff::new(
+            Duration::from_millis(100),
+            Duration::from_millis(350),
+            Duration::from_secs(60),
+        );
+        let delays: Vec<_> = (0..4)
+            .map(|_| backoff.next_delay(Duration::ZERO).as_millis())
+            .collect();
+        assert_eq!(delays, vec![100, 200, 350, 350]);
+    }
+
+    #[test]
+    fn backoff_resets_after_a_stable_run() {
+        let mut backoff = Backoff::default();
+        backoff.next_delay(Duration::ZERO);
+        backoff.next_delay(Duration::ZERO);
+        assert_eq!(
+            backoff.next_delay(Duration::from_secs(120)),
+            Duration::from_millis(500)
+        );
+    }
+
+    fn fast_backoff() -> Backoff {
+        Backoff::new(
+            Duration::from_millis(1),
+            Duration::from_millis(5),
+            Duration::from_secs(60),
+        )
+    }
+
+    #[tokio::test]
+    async fn supervisor_restarts_failed_sources_and_reports_degraded() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let shared = Arc::new(Mutex::new(SharedState::default()));
+        let mut attempts = 0;
+        let supervisor = tokio::spawn(supervise_key_source(
+            move || -> anyhow::Result<Box<dyn KeySource>> {
+                attempts += 1;
+                if attempts < 3 {
+                    bail!("attempt {attempts} failed");
+                }
+                Ok(Box::new(ScriptedKeySource::from_steps([
+                    ScriptStep::Release,
+                ])))
+            },
+            tx,
+            egui::Context::default(),
+            shared.clone(),
+            fast_backoff(),
+        ));
+
+        assert!(matches!(rx.recv().await, Some(AppEvent::AltReleased)));
+        // The scripted source ends after one release, so it gets restarted too.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        match &shared.lock().unwrap().key_listener {
+            KeyListenerStatus::Degraded { restarts, .. } => assert!(*restarts >= 3),
+            KeyListenerStatus::Running { source } => assert_eq!(source, "scripted"),
+            other => panic!("unexpected status {other:?}"),
+        }
+
+        drop(rx);
+        tokio::time::timeout(Duration::from_secs(1), supervisor)
+            .await
+            .expect("supervisor did not stop")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn supervisor_marks_running_while_source_is_alive() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let (_steps_tx, steps_rx) = mpsc::unbounded_channel();
+        let shared = Arc::new(Mutex::new(SharedState::default()));
+        let mut source = Some(ScriptedKeySource::from_channel(steps_rx));
+        tokio::spawn(supervise_key_source(
+            move || -> anyhow::Result<Box<dyn KeySource>> {
+                match source.take() {
+                    Some(source) => Ok(Box::new(source)),
+                    None => bail!("no more sources"),
+                }
+            },
+            tx,
+            egui::Context::default(),
+            shared.clone(),
+            fast_backoff(),
+        ));
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            shared.lock().unwrap().key_listener,
+            KeyListenerStatus::Running {
+                source: "scripted".into()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn supervisor_reports_last_error() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let shared = Arc::new(Mutex::new(SharedState::default()));
+        tokio::spawn(supervise_key_source(
+            || -> anyhow::Result<Box<dyn KeySource>> { bail!("Failed to start libinput") },
+            tx,
+            egui::Context::default(),
+            shared.clone(),
+            Backoff::new(
+                Duration::from_secs(10),
+                Duration::from_secs(10),
+                Duration::from_secs(60),
+            ),
+        ));
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let status = shared.lock().unwrap().key_listener.clone();
+        assert_eq!(
+            status,
+            KeyListenerStatus::Degraded {
+                restarts: 1,
+                last_error: "Failed to start libinput".into(),
+                retry_in_ms: 10_000,
+            }
+        );
+        assert!(status.is_degraded());
+    }
+
+    #[test]
+    fn evdev_alt_release_is_detected() {
+        let keys = ReleaseKeys::default();
+        for code in [evdev::KEY_LEFTALT, evdev::KEY_RIGHTALT] {
+            assert!(keys.matches_event(&KeyEvent {
+                code,
+                state: KeyState::Released
+            }));
+        }
+    }
+
+    #[test]
+    fn evdev_alt_press_and_repeat_are_ignored() {
+        let keys = ReleaseKeys::default();
+        for state in [KeyState::Pressed, KeyState::Repeated] {
+            assert!(!keys.matches_event(&KeyEvent {
+                code: evdev::KEY_LEFTALT,
+                state
+            }));
+        }
+    }
+
+    #[test]
+    fn evdev_configured_key_release_is_detected() {
+        let keys = keys(&["ctrl"]);
+        assert!(keys.matches_event(&KeyEvent {
+            code: evdev::KEY_RIGHTCTRL,
+            state: KeyState::Released
+        }));
+        assert!(!keys.matches_event(&KeyEvent {
+            code: evdev::KEY_LEFTALT,
+            state: KeyState::Released
+        }));
+    }
+
+    #[test]
+    fn evdev_other_key_release_is_ignored() {
+        assert!(!ReleaseKeys::default().matches_event(&KeyEvent {
+            code: evdev::KEY_SPACE,
+            state: KeyState::Released
+        }));
+    }
 }
@@ -0,0 +1,603 @@
+//! Typed calls on Qtile's command graph.
+//!
+//! Every operation qalttab needs is one `QtileCommand` constructor, so the
+//! exact calls can be unit tested and nothing depends on Qtile's Python internals.
+
+use std::fmt::{self, Display};
+use std::sync::Arc;
+
+use crate::config::{PlacementConfig, ScreenPolicy};
+use crate::placement::{ScreenGeometry, pick_screen};
+use crate::ui::QtileClientTrait;
+use crate::wm::{WindowManager, WmFuture, WmOp};
+use anyhow::Context;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Where the overlay waits while hidden. qalttab adds it when it is missing.
+pub const HIDDEN_GROUP: &str = "qalttab";
+
+/// One call on the command graph: `object.function(args)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QtileCommand {
+    pub object: Vec<String>,
+    pub function: String,
+    pub args: Vec<String>,
+}
+
+impl QtileCommand {
+    fn root(function: &str, args: Vec<String>) -> Self {
+        Self {
+            object: vec![],
+            function: function.to_owned(),
+            args,
+        }
+    }
+
+    fn window(wid: impl Display, function: &str, args: Vec<String>) -> Self {
+        Self {
+            object: vec!["window".to_owned(), wid.to_string()],
+            function: function.to_owned(),
+            args,
+        }
+    }
+
+    /// Move a window to [`HIDDEN_GROUP`], which no screen shows.
+    pub fn hide(wid: impl Display) -> Self {
+        Self::to_group(wid, HIDDEN_GROUP)
+    }
+
+    /// Move a window back to the current group.
+    pub fn unhide(wid: impl Display) -> Self {
+        Self::window(wid, "togroup", vec![])
+    }
+
+    pub fn focus(wid: impl Display) -> Self {
+        Self::window(wid, "focus", vec![])
+    }
+
+    pub fn bring_to_front(wid: impl Display) -> Self {
+        Self::window(wid, "bring_to_front", vec![])
+    }
+
+    pub fn keep_above(wid: impl Display, enable: bool) -> Self {
+        let enable = if enable { "True" } else { "False" };
+        Self::window(wid, "keep_above", vec![enable.to_owned()])
+    }
+
+    pub fn kill(wid: impl Display) -> Self {
+        Self::window(wid, "kill", vec![])
+    }
+
+    /// Position, size, group and more of a window.
+    pub fn window_info(wid: impl Display) -> Self {
+        Self::window(wid, "info", vec![])
+    }
+
+    pub fn to_group(wid: impl Display, group: &str) -> Self {
+        Self::window(wid, "togroup", vec![group.to_owned()])
+    }
+
+    /// Add a group, unless one with that name exists.
+    pub fn add_group(name: &str) -> Self {
+        Self::root("addgroup", vec![name.to_owned()])
+    }
+
+    /// Move and resize a window, without a border.
+    pub fn place(wid: impl Display, x: i32, y: i32, width: i32, height: i32) -> Self {
+        let args = [x, y, width, height, 0]
+            .iter()
+            .map(i32::to_string)
+            .chain(["None".to_owned()])
+            .collect();
+        Self::window(wid, "place", args)
+    }
+
+    /// Geometry of the current screen.
+    pub fn screen_info() -> Self {
+        Self {
+            object: vec!["screen".to_owned()],
+            function: "info".to_owned(),
+            args: vec![],
+        }
+    }
+
+    /// Every screen, with the group it shows.
+    pub fn screens() -> Self {
+        Self::root("get_screens", vec![])
+    }
+
+    /// Every window Qtile manages.
+    pub fn windows() -> Self {
+        Self::root("windows", vec![])
+    }
+
+    pub fn fire_user_hook(name: &str) -> Self {
+        Self::root("fire_user_hook", vec![name.to_owned()])
+    }
+
+    pub async fn send(&self, client: &dyn QtileClientTrait) -> anyhow::Result<Value> {
+        client
+            .call(self.clone())
+            .await
+            .with_context(|| format!("Qtile call {self} failed"))
+    }
+}
+
+/// Formats like Qtile's own command graph paths, e.g. `window[42].place(0, 0, 300, 400, 0, None)`.
+impl Display for QtileCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, node) in self.object.iter().enumerate() {
+            if i == 0 {
+                write!(f, "{node}")?;
+            } else if node.chars().all(|c| c.is_ascii_digit()) {
+                write!(f, "[{node}]")?;
+            } else {
+                write!(f, ".{node}")?;
+            }
+        }
+        if !self.object.is_empty() {
+            write!(f, ".")?;
+        }
+        write!(f, "{}({})", self.function, self.args.join(", "))
+    }
+}
+
+/// The parts of a `screen.info` or `get_screens` entry we use.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct QtileScreen {
+    #[serde(default)]
+    pub index: usize,
+    #[serde(flatten)]
+    pub geometry: ScreenGeometry,
+    /// Only in `get_screens`.
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+/// Command results sometimes arrive as a JSON string instead of a value.
+fn decode<T: serde::de::DeserializeOwned>(value: Value) -> anyhow::Result<T> {
+    match value {
+        Value::String(text) => Ok(serde_json::from_str(&text)?),
+        value => Ok(serde_json::from_value(value)?),
+    }
+}
+
+pub async fn screen_info(client: &dyn QtileClientTrait) -> anyhow::Result<QtileScreen> {
+    decode(QtileCommand::screen_info().send(client).await?).context("Unexpected screen.info reply")
+}
+
+pub async fn screens(client: &dyn QtileClientTrait) -> anyhow::Result<Vec<QtileScreen>> {
+    decode(QtileCommand::screens().send(client).await?).context("Unexpected get_screens reply")
+}
+
+pub async fn window_geometry(
+    client: &dyn QtileClientTrait,
+    wid: u64,
+) -> anyhow::Result<ScreenGeometry> {
+    decode(QtileCommand::window_info(wid).send(client).await?)
+        .with_context(|| format!("Unexpected window[{wid}].info reply"))
+}
+
+/// Id of the first window called `name`, if Qtile knows one.
+pub async fn find_window_id(
+    client: &dyn QtileClientTrait,
+    name: &str,
+) -> anyhow::Result<Option<u64>> {
+    #[derive(Deserialize)]
+    struct Window {
+        id: u64,
+        #[serde(default)]
+        name: Option<String>,
+    }
+    let windows: Vec<Window> =
+        decode(QtileCommand::windows().send(client).await?).context("Unexpected windows reply")?;
+    Ok(windows
+        .into_iter()
+        .find(|w| w.name.as_deref() == Some(name))
+        .map(|w| w.id))
+}
+
+/// Move and resize a window to `(x, y)` and raise it above the others.
+pub async fn place_on_top(
+    client: &dyn QtileClientTrait,
+    wid: impl Display + Copy,
+    (x, y): (i32, i32),
+    width: i32,
+    height: i32,
+) -> anyhow::Result<()> {
+    QtileCommand::place(wid, x, y, width, height)
+        .send(client)
+        .await?;
+    QtileCommand::keep_above(wid, true).send(client).await?;
+    QtileCommand::bring_to_front(wid).send(client).await?;
+    Ok(())
+}
+
+/// Drives Qtile through its command graph. Focus changes reach qalttab
+/// through the `qalttab_hooks` hook writing to the IPC socket.
+pub struct QtileBackend {
+    client: Arc<dyn QtileClientTrait>,
+    placement: PlacementConfig,
+}
+
+impl QtileBackend {
+    pub fn new(client: Arc<dyn QtileClientTrait>) -> Self {
+        Self {
+            client,
+            placement: PlacementConfig::default(),
+        }
+    }
+
+    pub fn with_placement(mut self, placement: PlacementConfig) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// The screen `placement` picks, or `None` for the current screen under
+    /// the `focused` policy. Qtile's command graph cannot report the
+    /// pointer, so `pointer` picks the current screen too.
+    async fn target_screen(&self, selected: Option<u64>) -> anyhow::Result<Option<QtileScreen>> {
+        let client = self.client.as_ref();
+        let point = match (self.placement.screen, selected) {
+            (ScreenPolicy::Focused, _) => return Ok(None),
+            (ScreenPolicy::Selection, Some(wid)) => match window_geometry(client, wid).await {
+                Ok(window) => Some(window.center()),
+                Err(e) => {
+                    log::debug!("Placing on the current screen: {e:#}");
+                    None
+                }
+            },
+            _ => None,
+        };
+        let mut screens = screens(client).await?;
+        let geometries: Vec<ScreenGeometry> = screens.iter().map(|s| s.geometry).collect();
+        let index = match pick_screen(&self.placement, &geometries, point) {
+            Some(index) => index,
+            None => screen_info(client).await?.index,
+        };
+        Ok((index < screens.len()).then(|| screens.swap_remove(index)))
+    }
+
+    /// Place our window on the screen `placement` picks. On another screen
+    /// it first joins the group shown there.
+    async fn place(
+        &self,
+        wid: &str,
+        width: i32,
+        height: i32,
+        selected: Option<u64>,
+    ) -> anyhow::Result<()> {
+        let client = self.client.as_ref();
+        let screen = match self.target_screen(selected).await? {
+            Some(screen) => {
+                if let Some(group) = &screen.group {
+                    QtileCommand::to_group(wid, group).send(client).await?;
+                }
+                screen
+            }
+            None => screen_info(client).await?,
+        };
+        let position = screen.geometry.position(width, height, &self.placement);
+        place_on_top(client, wid, position, width, height).await
+    }
+
+    async fn focus(&self, wid: u64) -> anyhow::Result<()> {
+        let client = self.client.as_ref();
+        QtileCommand::focus(wid).send(client).await?;
+        QtileCommand::bring_to_front(wid)
+            .send(client)
+            .await
+            .map(drop)
+    }
+
+    async fn run_op(&self, op: WmOp) -> anyhow::Result<()> {
+        let client = self.client.as_ref();
+        match op {
+            WmOp::Show {
+                wid,
+                width,
+                height,
+                selected,
+            } => {
+                QtileCommand::unhide(&wid).send(client).await?;
+                self.place(&wid, width, height, selected).await
+            }
+            WmOp::Hide { wid } => {
+                // Qtile drops dynamic groups on restart.
+                QtileCommand::add_group(HIDDEN_GROUP).send(client).await?;
+                QtileCommand::hide(&wid).send(client).await.map(drop)
+            }
+            WmOp::Place {
+                wid,
+                width,
+                height,
+                selected,
+            } => self.place(&wid, width, height, selected).await,
+            WmOp::Follow {
+                wid,
+                width,
+                height,
+                selected,
+            } => match self.placement.screen {
+                ScreenPolicy::Selection => self.place(&wid, width, height, Some(selected)).await,
+                _ => Ok(()),
+            },
+            WmOp::Focus { wid } => self.focus(wid).await,
+            WmOp::Kill { wid } => QtileCommand::kill(wid).send(client).await.map(drop),
+            WmOp::Commit { wid } => {
+                if let Some(wid) = wid {
+                    self.focus(wid).await?;
+                }
+                // Only for user config that reacts to the release; the
+                // selection is already focused.
+                if let Err(e) = QtileCommand::fire_user_hook("alt_release")
+                    .send(client)
+                    .await
+                {
+                    log::debug!("{e:#}");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl WindowManager for QtileBackend {
+    fn name(&self) -> &'static str {
+        "qtile"
+    }
+
+    fn run(&self, op: WmOp) -> WmFuture<'_, ()> {
+        Box::pin(self.run_op(op))
+    }
+
+    fn find_window<'a>(&'a self, name: &'a str) -> WmFuture<'a, Option<u64>> {
+        Box::pin(find_window_id(self.client.as_ref(), name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::{OverlayEvent, QtileCallFuture};
+    use crate::wm::{WmQueue, run_queue};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Records calls and answers `screen.info` and `windows` with canned replies.
+    #[derive(Default)]
+    struct Recorder {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl QtileClientTrait for Recorder {
+        fn call(&self, command: QtileCommand) -> QtileCallFuture<'_> {
+            Box::pin(async move {
+                self.calls.lock().unwrap().push(command.to_string());
+                Ok(match command.function.as_str() {
+                    _ if command.object.last().is_some_and(|wid| wid == "13") => {
+                        anyhow::bail!("No such window 13")
+                    }
+                    "info" if command.object[0] == "window" => {
+                        json!({"id": 7, "x": 100, "y": 100, "width": 800, "height": 600})
+                    }
+                    "info" => {
+                        json!({"index": 1, "x": 1920, "y": 0, "width": 1920, "height": 1080})
+                    }
+                    "get_screens" => json!([
+                        {"index": 0, "group": "1", "x": 0, "y": 0, "width": 1920, "height": 1080},
+                        {"index": 1, "group": "2", "x": 1920, "y": 0, "width": 1920, "height": 1080},
+                    ]),
+                    "windows" => json!([
+                        {"id": 7, "name": "firefox"},
+                        {"id": 42, "name": "qalttab"},
+                    ]),
+                    _ => Value::Null,
+                })
+            })
+        }
+    }
+
+    #[test]
+    fn window_commands_target_the_window_node() {
+        let cmd = QtileCommand::focus(42);
+        assert_eq!(cmd.object, vec!["window", "42"]);
+        assert_eq!(cmd.function, "focus");
+        assert!(cmd.args.is_empty());
+        assert_eq!(QtileCommand::kill("42").object, vec!["window", "42"]);
+    }
+
+    #[test]
+    fn commands_display_as_graph_paths() {
+        assert_eq!(
+            QtileCommand::hide(42).to_string(),
+            "window[42].togroup(qalttab)"
+        );
+        assert_eq!(QtileCommand::unhide(42).to_string(), "window[42].togroup()");
+        assert_eq!(
+            QtileCommand::add_group("qalttab").to_string(),
+            "addgroup(qalttab)"
+        );
+        assert_eq!(
+            QtileCommand::bring_to_front(42).to_string(),
+            "window[42].bring_to_front()"
+        );
+        assert_eq!(QtileCommand::kill(42).to_string(), "window[42].kill()");
+        assert_eq!(
+            QtileCommand::keep_above(42, true).to_string(),
+            "window[42].keep_above(True)"
+        );
+        assert_eq!(
+            QtileCommand::place(42, 10, 20, 300, 400).to_string(),
+            "window[42].place(10, 20, 300, 400, 0, None)"
+        );
+        assert_eq!(QtileCommand::screen_info().to_string(), "screen.info()");
+        assert_eq!(QtileCommand::screens().to_string(), "get_screens()");
+        assert_eq!(QtileCommand::window_info(7).to_string(), "window[7].info()");
+        assert_eq!(
+            QtileCommand::to_group(42, "2").to_string(),
+            "window[42].togroup(2)"
+        );
+        assert_eq!(QtileCommand::windows().to_string(), "windows()");
+        assert_eq!(
+            QtileCommand::fire_user_hook("alt_release").to_string(),
+            "fire_user_hook(alt_release)"
+        );
+    }
+
+    #[tokio::test]
+    async fn placement_picks_the_screen_and_joins_its_group() {
+        let recorder = Arc::new(Recorder::default());
+        let placement = |screen| PlacementConfig {
+            screen,
+            screen_index: 0,
+            anchor: crate::config::Anchor::Top,
+            offset_x: 0,
+            offset_y: 10,
+        };
+        let show = |selected| WmOp::Show {
+            wid: "42".into(),
+            width: 300,
+            height: 400,
+            selected,
+        };
+        let calls = |backend: QtileBackend, op: WmOp| {
+            let recorder = recorder.clone();
+            async move {
+                recorder.calls.lock().unwrap().clear();
+                backend.run(op).await.unwrap();
+                recorder.calls.lock().unwrap().clone()
+            }
+        };
+        let backend =
+            |screen| QtileBackend::new(recorder.clone()).with_placement(placement(screen));
+
+        assert_eq!(
+            calls(backend(ScreenPolicy::Focused), show(Some(7))).await,
+            vec![
+                "window[42].togroup()",
+                "screen.info()",
+                "window[42].place(2730, 10, 300, 400, 0, None)",
+                "window[42].keep_above(True)",
+                "window[42].bring_to_front()",
+            ]
+        );
+        // Window 7 is on the left screen.
+        assert_eq!(
+            calls(backend(ScreenPolicy::Selection), show(Some(7))).await[1..5],
+            [
+                "window[7].info()",
+                "get_screens()",
+                "window[42].togroup(1)",
+                "window[42].place(810, 10, 300, 400, 0, None)",
+            ]
+        );
+        assert_eq!(
+            calls(backend(ScreenPolicy::Index), show(None)).await[2],
+            "window[42].togroup(1)"
+        );
+        // Without a selection or a pointer, the current screen.
+        assert_eq!(
+            calls(backend(ScreenPolicy::Selection), show(None)).await[1..4],
+            ["get_screens()", "screen.info()", "window[42].togroup(2)",]
+        );
+        let follow = WmOp::Follow {
+            wid: "42".into(),
+            width: 300,
+            height: 400,
+            selected: 7,
+        };
+        assert!(
+            calls(backend(ScreenPolicy::Index), follow.clone())
+                .await
+                .is_empty()
+        );
+        assert_eq!(
+            calls(backend(ScreenPolicy::Selection), follow).await[0],
+            "window[7].info()"
+        );
+    }
+
+    #[tokio::test]
+    async fn find_window_id_matches_by_name() {
+        let recorder = Recorder::default();
+        assert_eq!(
+            find_window_id(&recorder, "qalttab").await.unwrap(),
+            Some(42)
+        );
+        assert_eq!(find_window_id(&recorder, "missing").await.unwrap(), None);
+        assert_eq!(*recorder.calls.lock().unwrap(), vec!["windows()"; 2]);
+    }
+
+    #[test]
+    fn replies_encoded_as_json_strings_are_decoded() {
+        let screen: QtileScreen =
+            decode(json!(r#"{"x": 0, "y": 0, "width": 800, "height": 600}"#)).unwrap();
+        assert_eq!(screen.geometry.width, 800);
+        assert_eq!(screen.group, None);
+    }
+
+    #[tokio::test]
+    async fn queue_runs_operations_in_order() {
+        let recorder = Arc::new(Recorder::default());
+        let (events, _) = tokio::sync::broadcast::channel(8);
+        let (queue, rx) = WmQueue::new();
+        queue.push(WmOp::Show {
+            wid: "42".into(),
+            width: 300,
+            height: 400,
+            selected: Some(7),
+        });
+        queue.push(WmOp::Hide { wid: "42".into() });
+        queue.push(WmOp::Focus { wid: 7 });
+        queue.push(WmOp::Commit { wid: Some(7) });
+        queue.push(WmOp::Commit { wid: None });
+        drop(queue);
+        let backend = Arc::new(QtileBackend::new(recorder.clone()));
+        run_queue(backend, events, rx).await;
+        assert_eq!(
+            *recorder.calls.lock().unwrap(),
+            vec![
+                "window[42].togroup()",
+                "screen.info()",
+                "window[42].place(2730, 340, 300, 400, 0, None)",
+                "window[42].keep_above(True)",
+                "window[42].bring_to_front()",
+                "addgroup(qalttab)",
+                "window[42].togroup(qalttab)",
+                "window[7].focus()",
+                "window[7].bring_to_front()",
+                "window[7].focus()",
+                "window[7].bring_to_front()",
+                "fire_user_hook(alt_release)",
+                "fire_user_hook(alt_release)",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_operations_are_published_and_the_queue_continues() {
+        let recorder = Arc::new(Recorder::default());
+        let (events, mut failures) = tokio::sync::broadcast::channel(8);
+        let (queue, rx) = WmQueue::new();
+        queue.push(WmOp::Focus { wid: 13 });
+        queue.push(WmOp::Kill { wid: 7 });
+        drop(queue);
+        let backend = Arc::new(QtileBackend::new(recorder.clone()));
+        run_queue(backend, events, rx).await;
+
+        match failures.try_recv().unwrap() {
+            OverlayEvent::CommandFailed { operation, error } => {
+                assert_eq!(operation, "focus window 13");
+                assert!(error.contains("window[13].focus()"), "{error}");
+                assert!(error.contains("No such window 13"), "{error}");
+            }
+            other => panic!("expected command_failed, got {other:?}"),
+        }
+        assert_eq!(
+            *recorder.calls.lock().unwrap(),
+            vec!["window[13].focus()", "window[7].kill()"]
+        );
+    }
+}
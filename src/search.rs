@@ -0,0 +1,147 @@
+//! Fuzzy filtering of the window list while the overlay is open.
+
+use crate::ui::WindowInfo;
+
+/// How well a query matched a piece of text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Higher is better.
+    pub score: i64,
+    /// Char indices of the matched characters in the text.
+    pub positions: Vec<usize>,
+}
+
+const MATCH: i64 = 1;
+const CONSECUTIVE: i64 = 8;
+const WORD_START: i64 = 6;
+const GAP: i64 = 1;
+
+/// Match `query` against `text` as a case-insensitive subsequence.
+///
+/// Runs of consecutive characters and characters at the start of a word
+/// score higher, gaps between matched characters score lower. An empty
+/// query matches everything with a score of 0.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<Match> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let text: Vec<char> = text.chars().collect();
+    let Some(&first) = query.first() else {
+        return Some(Match {
+            score: 0,
+            positions: Vec::new(),
+        });
+    };
+    let same = |c: char, q: char| c.to_lowercase().eq(q.to_lowercase());
+
+    // Greedy from every place the first character matches; keep the best.
+    (0..text.len())
+        .filter(|&start| same(text[start], first))
+        .filter_map(|start| {
+            let mut positions = vec![start];
+            let mut next = start + 1;
+            for &q in &query[1..] {
+                let found = (next..text.len()).find(|&i| same(text[i], q))?;
+                positions.push(found);
+                next = found + 1;
+            }
+            Some(Match {
+                score: score(&text, &positions),
+                positions,
+            })
+        })
+        .max_by_key(|m| (m.score, std::cmp::Reverse(m.positions[0])))
+}
+
+fn score(text: &[char], positions: &[usize]) -> i64 {
+    let mut score = 0;
+    for (n, &i) in positions.iter().enumerate() {
+        score += MATCH;
+        if i == 0 || !text[i - 1].is_alphanumeric() {
+            score += WORD_START;
+        }
+        if n > 0 {
+            let gap = (i - positions[n - 1] - 1) as i64;
+            score += if gap == 0 { CONSECUTIVE } else { -GAP * gap };
+        }
+    }
+    score
+}
+
+/// Best score of `query` against a window's name, class and group name.
+pub fn window_score(query: &str, window: &WindowInfo) -> Option<i64> {
+    [&window.name, &window.class, &window.group_name]
+        .into_iter()
+        .filter_map(|field| fuzzy_match(query, field))
+        .map(|m| m.score)
+        .max()
+}
+
+/// The windows matching `query`, best match first. Equal scores keep the
+/// order of `windows`, so ties stay in most-recently-used order.
+pub fn filter_windows(query: &str, windows: &[WindowInfo]) -> Vec<WindowInfo> {
+    let mut scored: Vec<(i64, &WindowInfo)> = windows
+        .iter()
+        .filter_map(|w| Some((window_score(query, w)?, w)))
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, w)| w.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(id: u64, name: &str, class: &str, group_name: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            name: name.into(),
+            class: class.into(),
+            group_name: group_name.into(),
+            ..WindowInfo::default()
+        }
+    }
+
+    fn positions(query: &str, text: &str) -> Option<Vec<usize>> {
+        fuzzy_match(query, text).map(|m| m.positions)
+    }
+
+    #[test]
+    fn matches_subsequences_ignoring_case() {
+        assert_eq!(positions("ff", "Firefox"), Some(vec![0, 4]));
+        assert_eq!(positions("FOX", "firefox"), Some(vec![4, 5, 6]));
+        assert_eq!(positions("xf", "firefox"), None);
+        assert_eq!(positions("", "anything"), Some(vec![]));
+        assert_eq!(positions("é", "Café"), Some(vec![3]));
+    }
+
+    #[test]
+    fn prefers_word_starts_and_runs() {
+        // The start of "vim" beats the "vi" inside "nvim".
+        assert_eq!(positions("vi", "nvim - vim"), Some(vec![7, 8]));
+        assert_eq!(positions("vim", "dev - vim"), Some(vec![6, 7, 8]));
+        let run = fuzzy_match("term", "terminal").unwrap().score;
+        let scattered = fuzzy_match("term", "the emacs rm").unwrap().score;
+        assert!(run > scattered);
+    }
+
+    #[test]
+    fn filters_on_name_class_and_group() {
+        let windows = vec![
+            window(1, "Inbox", "thunderbird", "mail"),
+            window(2, "~/src", "foot", "term"),
+            window(3, "README.md - qalttab", "code", "dev"),
+            window(4, "term", "xterm", "misc"),
+        ];
+        let ids = |query| {
+            filter_windows(query, &windows)
+                .iter()
+                .map(|w| w.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(""), vec![1, 2, 3, 4]);
+        assert_eq!(ids("thun"), vec![1]);
+        assert_eq!(ids("dev"), vec![3]);
+        // Equal scores keep their order.
+        assert_eq!(ids("term"), vec![2, 4]);
+        assert!(ids("zzz").is_empty());
+    }
+}
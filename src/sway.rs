@@ -0,0 +1,500 @@
+//! sway backend, speaking the i3 IPC protocol over `$SWAYSOCK`.
+//!
+//! Every message is the `i3-ipc` magic, the payload length and the message
+//! type as native-endian `u32`s, then the payload. Our own window is hidden
+//! in the scratchpad and shown again by focusing it.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::{PlacementConfig, ScreenPolicy};
+use crate::placement::{ScreenGeometry, pick_screen};
+use crate::ui::{AppEvent, WindowInfo};
+use crate::wm::{FocusHistory, FocusReporter, OVERLAY_NAME, WindowManager, WmFuture, WmOp};
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const HEADER_LEN: usize = MAGIC.len() + 8;
+/// Replies bigger than this are treated as a corrupt stream.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+pub const RUN_COMMAND: u32 = 0;
+pub const SUBSCRIBE: u32 = 2;
+pub const GET_OUTPUTS: u32 = 3;
+pub const GET_TREE: u32 = 4;
+/// Events have the high bit set; window events are event number 3.
+pub const WINDOW_EVENT: u32 = 0x8000_0003;
+
+pub async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    kind: u32,
+    payload: &[u8],
+) -> anyhow::Result<()> {
+    let mut message = Vec::with_capacity(HEADER_LEN + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&u32::try_from(payload.len())?.to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(payload);
+    writer.write_all(&message).await?;
+    Ok(())
+}
+
+/// Read one message, returning its type and payload.
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<(u32, Vec<u8>)> {
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header).await?;
+    if &header[..MAGIC.len()] != MAGIC {
+        bail!("Not an i3 IPC message");
+    }
+    let field = |at: usize| u32::from_ne_bytes(header[at..at + 4].try_into().unwrap());
+    let len = field(MAGIC.len()) as usize;
+    let kind = field(MAGIC.len() + 4);
+    if len > MAX_MESSAGE_SIZE {
+        bail!("i3 IPC message of {len} bytes is too large");
+    }
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload).await?;
+    Ok((kind, payload))
+}
+
+/// One connection to the sway socket.
+pub struct SwayConnection {
+    stream: UnixStream,
+}
+
+impl SwayConnection {
+    pub async fn connect(path: &Path) -> anyhow::Result<Self> {
+        let stream = UnixStream::connect(path)
+            .await
+            .with_context(|| format!("Failed to connect to sway at {path:?}"))?;
+        Ok(Self { stream })
+    }
+
+    /// Send a request and wait for its reply.
+    pub async fn request(&mut self, kind: u32, payload: &str) -> anyhow::Result<Value> {
+        write_message(&mut self.stream, kind, payload.as_bytes()).await?;
+        loop {
+            let (reply_kind, reply) = read_message(&mut self.stream).await?;
+            if reply_kind == kind {
+                return Ok(serde_json::from_slice(&reply)?);
+            }
+            log::debug!("Skipping sway message {reply_kind:#x} while waiting for {kind}");
+        }
+    }
+
+    /// Next event on a connection that sent `subscribe`.
+    pub async fn next_event(&mut self) -> anyhow::Result<(u32, Value)> {
+        let (kind, payload) = read_message(&mut self.stream).await?;
+        Ok((kind, serde_json::from_slice(&payload)?))
+    }
+}
+
+/// The parts of a `get_tree` node we use.
+#[derive(Deserialize)]
+struct Node {
+    id: u64,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    app_id: Option<String>,
+    /// X11 window id, set for Xwayland windows and on i3.
+    #[serde(default)]
+    window: Option<u64>,
+    #[serde(default)]
+    window_properties: Option<WindowProperties>,
+    #[serde(default)]
+    focused: bool,
+    #[serde(default)]
+    rect: ScreenGeometry,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    floating_nodes: Vec<Node>,
+}
+
+#[derive(Deserialize)]
+struct WindowProperties {
+    #[serde(default)]
+    class: Option<String>,
+}
+
+/// A window found in the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwayWindow {
+    pub info: WindowInfo,
+    pub focused: bool,
+    pub rect: ScreenGeometry,
+}
+
+impl SwayWindow {
+    fn is_overlay(&self) -> bool {
+        self.info.name == OVERLAY_NAME || self.info.class == OVERLAY_NAME
+    }
+}
+
+/// Every window in a `get_tree` reply, in tree order.
+pub fn parse_tree(tree: Value) -> anyhow::Result<Vec<SwayWindow>> {
+    let root: Node = serde_json::from_value(tree).context("Unexpected get_tree reply")?;
+    let mut windows = Vec::new();
+    collect_windows(&root, "", &mut windows);
+    Ok(windows)
+}
+
+fn collect_windows(node: &Node, workspace: &str, out: &mut Vec<SwayWindow>) {
+    let workspace = match node.kind.as_str() {
+        "workspace" => node.name.as_deref().unwrap_or_default(),
+        _ => workspace,
+    };
+    let is_window = matches!(node.kind.as_str(), "con" | "floating_con")
+        && (node.app_id.is_some() || node.window.is_some());
+    if is_window {
+        let class = node
+            .app_id
+            .clone()
+            .or_else(|| node.window_properties.as_ref()?.class.clone())
+            .unwrap_or_default();
+        out.push(SwayWindow {
+            info: WindowInfo {
+                id: node.id,
+                name: node.name.clone().unwrap_or_default(),
+                class,
+                group_name: workspace.to_owned(),
+                ..WindowInfo::default()
+            },
+            focused: node.focused,
+            rect: node.rect,
+        });
+    }
+    for child in node.nodes.iter().chain(&node.floating_nodes) {
+        collect_windows(child, workspace, out);
+    }
+}
+
+/// The parts of a `get_outputs` entry we use.
+#[derive(Debug, Clone, Deserialize)]
+struct Output {
+    #[serde(default)]
+    active: bool,
+    #[serde(default)]
+    focused: bool,
+    rect: ScreenGeometry,
+}
+
+/// The sway command for `op`, if it needs one. Our window goes to
+/// `position`, or the centre of the focused output without one.
+pub fn command_for(op: &WmOp, position: Option<(i32, i32)>) -> Option<String> {
+    let size = |width: &i32, height: &i32| {
+        let position = match position {
+            Some((x, y)) => format!("absolute position {x} px {y} px"),
+            None => "position center".to_owned(),
+        };
+        format!("resize set width {width} px height {height} px, move {position}")
+    };
+    Some(match op {
+        // Focusing a window in the scratchpad shows it.
+        WmOp::Show {
+            wid, width, height, ..
+        } => {
+            format!("[con_id={wid}] focus, {}", size(width, height))
+        }
+        WmOp::Hide { wid } => format!("[con_id={wid}] move scratchpad"),
+        WmOp::Place {
+            wid, width, height, ..
+        }
+        | WmOp::Follow {
+            wid, width, height, ..
+        } => format!("[con_id={wid}] {}", size(width, height)),
+        WmOp::Focus { wid } | WmOp::Commit { wid: Some(wid) } => {
+            format!("[con_id={wid}] focus")
+        }
+        WmOp::Kill { wid } => format!("[con_id={wid}] kill"),
+        WmOp::Commit { wid: None } => return None,
+    })
+}
+
+/// Fail unless every entry of a `run_command` or `subscribe` reply succeeded.
+fn check_success(reply: &Value) -> anyhow::Result<()> {
+    let results = match reply {
+        Value::Array(results) => results.as_slice(),
+        single => std::slice::from_ref(single),
+    };
+    for result in results {
+        if result["success"] != Value::Bool(true) {
+            match result["error"].as_str() {
+                Some(error) => bail!("sway: {error}"),
+                None => bail!("sway rejected the request: {result}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+pub struct SwayBackend {
+    socket: PathBuf,
+    placement: PlacementConfig,
+}
+
+impl SwayBackend {
+    pub fn new(socket: PathBuf) -> Self {
+        Self {
+            socket,
+            placement: PlacementConfig::default(),
+        }
+    }
+
+    pub fn with_placement(mut self, placement: PlacementConfig) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Use `$SWAYSOCK`, or `$I3SOCK` when running under i3.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let socket = std::env::var_os("SWAYSOCK")
+            .or_else(|| std::env::var_os("I3SOCK"))
+            .context("Neither SWAYSOCK nor I3SOCK is set")?;
+        Ok(Self::new(socket.into()))
+    }
+
+    async fn windows(&self) -> anyhow::Result<Vec<SwayWindow>> {
+        let mut conn = SwayConnection::connect(&self.socket).await?;
+        parse_tree(conn.request(GET_TREE, "").await?)
+    }
+
+    /// Where `placement` puts a `width` x `height` overlay, or `None` to let
+    /// sway centre it. The i3 IPC cannot report the pointer, so `pointer`
+    /// picks the focused output.
+    async fn position(
+        &self,
+        selected: Option<u64>,
+        width: i32,
+        height: i32,
+    ) -> anyhow::Result<Option<(i32, i32)>> {
+        if self.placement == PlacementConfig::default() {
+            return Ok(None);
+        }
+        let mut conn = SwayConnection::connect(&self.socket).await?;
+        let outputs: Vec<Output> = serde_json::from_value(conn.request(GET_OUTPUTS, "").await?)
+            .context("Unexpected get_outputs reply")?;
+        let outputs: Vec<Output> = outputs.into_iter().filter(|o| o.active).collect();
+        let point = match (self.placement.screen, selected) {
+            (ScreenPolicy::Selection, Some(wid)) => parse_tree(conn.request(GET_TREE, "").await?)?
+                .into_iter()
+                .find(|w| w.info.id == wid)
+                .map(|w| w.rect.center()),
+            _ => None,
+        };
+        let screens: Vec<ScreenGeometry> = outputs.iter().map(|o| o.rect).collect();
+        let output = pick_screen(&self.placement, &screens, point)
+            .or_else(|| outputs.iter().position(|o| o.focused));
+        Ok(output.map(|i| screens[i].position(width, height, &self.placement)))
+    }
+
+    async fn run_op(&self, op: WmOp) -> anyhow::Result<()> {
+        let position = match op {
+            WmOp::Show {
+                selected,
+                width,
+                height,
+                ..
+            }
+            | WmOp::Place {
+                selected,
+                width,
+                height,
+                ..
+            } => self.position(selected, width, height).await?,
+            WmOp::Follow {
+                selected,
+                width,
+                height,
+                ..
+            } if self.placement.screen == ScreenPolicy::Selection => {
+                self.position(Some(selected), width, height).await?
+            }
+            WmOp::Follow { .. } => return Ok(()),
+            _ => None,
+        };
+        let Some(command) = command_for(&op, position) else {
+            return Ok(());
+        };
+        log::debug!("sway command: {command}");
+        let mut conn = SwayConnection::connect(&self.socket).await?;
+        let reply = conn.request(RUN_COMMAND, &command).await?;
+        check_success(&reply).with_context(|| format!("sway command {command:?} failed"))
+    }
+
+    async fn find(&self, name: &str) -> anyhow::Result<Option<u64>> {
+        Ok(self
+            .windows()
+            .await?
+            .into_iter()
+            .find(|w| w.info.name == name)
+            .map(|w| w.info.id))
+    }
+
+    /// Put the focused window first in `history`, leaving out the overlay.
+    async fn refresh(&self, history: &mut FocusHistory) -> anyhow::Result<()> {
+        let windows: Vec<SwayWindow> = self
+            .windows()
+            .await?
+            .into_iter()
+            .filter(|w| !w.is_overlay())
+            .collect();
+        let focused = windows.iter().find(|w| w.focused).map(|w| w.info.clone());
+        history.sync(windows.into_iter().map(|w| w.info).collect());
+        if let Some(window) = focused {
+            history.focus(window);
+        }
+        Ok(())
+    }
+
+    async fn watch(&self, tx: UnboundedSender<AppEvent>) -> anyhow::Result<()> {
+        let mut events = SwayConnection::connect(&self.socket).await?;
+        check_success(&events.request(SUBSCRIBE, r#"["window"]"#).await?)
+            .context("Failed to subscribe to sway window events")?;
+
+        let mut history = FocusHistory::default();
+        let mut reporter = FocusReporter::new(tx);
+        loop {
+            self.refresh(&mut history).await?;
+            if !reporter.report(&history) {
+                return Ok(());
+            }
+
+            loop {
+                let (kind, event) = events.next_event().await?;
+                if kind == WINDOW_EVENT {
+                    log::debug!("sway window event: {}", event["change"]);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl WindowManager for SwayBackend {
+    fn name(&self) -> &'static str {
+        "sway"
+    }
+
+    fn run(&self, op: WmOp) -> WmFuture<'_, ()> {
+        Box::pin(self.run_op(op))
+    }
+
+    fn find_window<'a>(&'a self, name: &'a str) -> WmFuture<'a, Option<u64>> {
+        Box::pin(self.find(name))
+    }
+
+    fn watch_focus(&self, tx: UnboundedSender<AppEvent>) -> WmFuture<'_, ()> {
+        Box::pin(self.watch(tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tree() -> Value {
+        json!({
+            "id": 1, "type": "root", "name": "root",
+            "nodes": [{
+                "id": 2, "type": "output", "name": "DP-1",
+                "nodes": [{
+                    "id": 3, "type": "workspace", "name": "1",
+                    "nodes": [
+                        {"id": 10, "type": "con", "name": "vim", "app_id": "foot", "focused": true},
+                        {"id": 4, "type": "con", "name": null, "nodes": [
+                            {"id": 11, "type": "con", "name": "Firefox", "app_id": null,
+                             "window": 8388611, "window_properties": {"class": "firefox"}},
+                        ]},
+                    ],
+                    "floating_nodes": [
+                        {"id": 12, "type": "floating_con", "name": "qalttab", "app_id": "qalttab"},
+                    ],
+                }],
+            }],
+        })
+    }
+
+    #[test]
+    fn tree_windows_carry_workspace_and_class() {
+        let windows = parse_tree(tree()).unwrap();
+        let ids: Vec<u64> = windows.iter().map(|w| w.info.id).collect();
+        assert_eq!(ids, vec![10, 11, 12]);
+        assert!(windows[0].focused);
+        assert_eq!(windows[0].info.class, "foot");
+        assert_eq!(windows[1].info.class, "firefox");
+        assert_eq!(windows[1].info.group_name, "1");
+        assert!(windows[2].is_overlay());
+    }
+
+    #[test]
+    fn ops_become_con_id_commands() {
+        let command_for = |op: &WmOp| super::command_for(op, None);
+        assert_eq!(
+            command_for(&WmOp::Hide { wid: "12".into() }).unwrap(),
+            "[con_id=12] move scratchpad"
+        );
+        let show = WmOp::Show {
+            wid: "12".into(),
+            width: 300,
+            height: 400,
+            selected: None,
+        };
+        assert_eq!(
+            command_for(&show).unwrap(),
+            "[con_id=12] focus, resize set width 300 px height 400 px, move position center"
+        );
+        assert_eq!(
+            super::command_for(&show, Some((2730, 40))).unwrap(),
+            "[con_id=12] focus, resize set width 300 px height 400 px, \
+             move absolute position 2730 px 40 px"
+        );
+        assert_eq!(
+            command_for(&WmOp::Kill { wid: 10 }).unwrap(),
+            "[con_id=10] kill"
+        );
+        assert_eq!(
+            command_for(&WmOp::Commit { wid: Some(11) }).unwrap(),
+            "[con_id=11] focus"
+        );
+        assert_eq!(command_for(&WmOp::Commit { wid: None }), None);
+    }
+
+    #[test]
+    fn failed_commands_report_sways_error() {
+        assert!(check_success(&json!([{"success": true}])).is_ok());
+        assert!(check_success(&json!({"success": true})).is_ok());
+        let err = check_success(&json!([
+            {"success": true},
+            {"success": false, "error": "No matching node"},
+        ]))
+        .unwrap_err();
+        assert_eq!(err.to_string(), "sway: No matching node");
+    }
+
+    #[tokio::test]
+    async fn messages_round_trip() {
+        let (mut a, mut b) = tokio::io::duplex(64);
+        write_message(&mut a, GET_TREE, b"{}").await.unwrap();
+        assert_eq!(
+            read_message(&mut b).await.unwrap(),
+            (GET_TREE, b"{}".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn bad_magic_is_rejected() {
+        let (mut a, mut b) = tokio::io::duplex(64);
+        a.write_all(b"i4-ipc\0\0\0\0\0\0\0\0").await.unwrap();
+        assert!(read_message(&mut b).await.is_err());
+    }
+}
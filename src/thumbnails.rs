@@ -0,0 +1,457 @@
+//! Window previews for the `thumbnail` card item.
+//!
+//! A [`CaptureProvider`] turns a window into an encoded image (PNG, JPEG, ...)
+//! that egui's image loaders decode. [`ThumbnailCache`] keeps the latest
+//! capture of every window and refreshes them in the background, so drawing
+//! a card never waits on a capture.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, bail};
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+
+use crate::config::ThumbnailConfig;
+use crate::ui::WindowInfo;
+
+/// Captures running at once. Each one may start a screenshot process.
+const MAX_CONCURRENT_CAPTURES: usize = 3;
+
+pub type CaptureFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<Vec<u8>>> + Send + 'a>>;
+
+/// Something that can capture the contents of a window.
+pub trait CaptureProvider: Send + Sync {
+    /// Short name used in logs.
+    fn name(&self) -> &'static str;
+
+    /// An encoded image of `window`'s contents.
+    fn capture(&self, window: &WindowInfo) -> CaptureFuture<'_>;
+}
+
+/// Runs a command per window and reads the image from its standard output.
+///
+/// `{id}` in any argument is replaced by the window id and `{hex_id}` by the
+/// id in hex with a `0x` prefix, as Hyprland writes addresses. A screenshot
+/// tool such as `grim` makes this a wlr-screencopy capture.
+pub struct CommandCapture {
+    command: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandCapture {
+    pub fn new(command: Vec<String>, timeout: Duration) -> Self {
+        Self { command, timeout }
+    }
+
+    fn args_for(&self, window: &WindowInfo) -> Vec<String> {
+        self.command
+            .iter()
+            .map(|arg| {
+                arg.replace("{id}", &window.id.to_string())
+                    .replace("{hex_id}", &format!("{:#x}", window.id))
+            })
+            .collect()
+    }
+
+    async fn run(&self, window: &WindowInfo) -> anyhow::Result<Vec<u8>> {
+        let args = self.args_for(window);
+        let (program, args) = args.split_first().context("empty capture command")?;
+        let output = tokio::time::timeout(
+            self.timeout,
+            tokio::process::Command::new(program)
+                .args(args)
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        .with_context(|| format!("{program} timed out"))?
+        .with_context(|| format!("Failed to run {program}"))?;
+        if !output.status.success() {
+            bail!(
+                "{program} exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        if output.stdout.is_empty() {
+            bail!("{program} printed no image");
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl CaptureProvider for CommandCapture {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn capture(&self, window: &WindowInfo) -> CaptureFuture<'_> {
+        let window = window.clone();
+        Box::pin(async move { self.run(&window).await })
+    }
+}
+
+/// The provider set up by `thumbnails`, if any.
+pub fn provider(config: &ThumbnailConfig) -> Option<Arc<dyn CaptureProvider>> {
+    if config.command.is_empty() {
+        return None;
+    }
+    Some(Arc::new(CommandCapture::new(
+        config.command.clone(),
+        Duration::from_millis(config.timeout_ms),
+    )))
+}
+
+/// A captured image, ready for `egui::Image::from_bytes`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thumbnail {
+    /// Unique per capture, so egui decodes every new capture.
+    pub uri: String,
+    pub bytes: Arc<[u8]>,
+}
+
+#[derive(Default)]
+struct Entry {
+    thumbnail: Option<Thumbnail>,
+    /// When the last capture finished, whether it worked or not.
+    captured_at: Option<Instant>,
+    pending: bool,
+}
+
+#[derive(Default)]
+struct Entries {
+    windows: HashMap<u64, Entry>,
+    /// URIs of replaced or pruned thumbnails, for the UI to release.
+    replaced: Vec<String>,
+    /// Numbers captures, so no URI is ever used twice.
+    captures: u64,
+}
+
+/// Latest thumbnail of every window, refreshed in the background.
+#[derive(Clone)]
+pub struct ThumbnailCache {
+    provider: Arc<dyn CaptureProvider>,
+    runtime: Handle,
+    max_age: Duration,
+    entries: Arc<Mutex<Entries>>,
+    permits: Arc<Semaphore>,
+}
+
+impl ThumbnailCache {
+    /// A cache that captures on the current tokio runtime and considers
+    /// captures older than `max_age` stale.
+    pub fn new(provider: Arc<dyn CaptureProvider>, max_age: Duration) -> Self {
+        Self {
+            provider,
+            runtime: Handle::current(),
+            max_age,
+            entries: Arc::default(),
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_CAPTURES)),
+        }
+    }
+
+    /// The latest capture of window `id`, if one has finished.
+    pub fn get(&self, id: u64) -> Option<Thumbnail> {
+        let entries = self.entries.lock().unwrap();
+        entries.windows.get(&id)?.thumbnail.clone()
+    }
+
+    /// Start capturing every window in `windows` without a fresh thumbnail,
+    /// and drop the thumbnails of windows not in the list. Returns at once;
+    /// `on_ready` runs after each capture that produced an image.
+    ///
+    /// Windows are captured in the order given, a few at a time, so the
+    /// cards on screen should come first. A window may appear more than once.
+    pub fn refresh(&self, windows: &[WindowInfo], on_ready: impl Fn() + Clone + Send + 'static) {
+        let mut entries = self.entries.lock().unwrap();
+        let Entries {
+            windows: cached,
+            replaced,
+            ..
+        } = &mut *entries;
+        cached.retain(|id, entry| {
+            let keep = windows.iter().any(|w| w.id == *id);
+            if !keep && let Some(thumbnail) = entry.thumbnail.take() {
+                replaced.push(thumbnail.uri);
+            }
+            keep
+        });
+
+        let now = Instant::now();
+        let mut queued = Vec::new();
+        for window in windows {
+            let entry = cached.entry(window.id).or_default();
+            let fresh = entry
+                .captured_at
+                .is_some_and(|at| now.duration_since(at) < self.max_age);
+            if entry.pending || fresh {
+                continue;
+            }
+            entry.pending = true;
+            queued.push(window.clone());
+        }
+        if queued.is_empty() {
+            return;
+        }
+        let cache = self.clone();
+        // Permits are taken in queue order, so earlier windows start first.
+        self.runtime.spawn(async move {
+            for window in queued {
+                if !cache
+                    .entries
+                    .lock()
+                    .unwrap()
+                    .windows
+                    .contains_key(&window.id)
+                {
+                    continue;
+                }
+                let Ok(permit) = cache.permits.clone().acquire_owned().await else {
+                    return;
+                };
+                let (cache, on_ready) = (cache.clone(), on_ready.clone());
+                tokio::spawn(async move {
+                    let result = cache.provider.capture(&window).await;
+                    drop(permit);
+                    if cache.store(window.id, result) {
+                        on_ready();
+                    }
+                });
+            }
+        });
+    }
+
+    /// Record the outcome of a capture. Returns whether there is a new image.
+    fn store(&self, id: u64, result: anyhow::Result<Vec<u8>>) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let Entries {
+            windows,
+            replaced,
+            captures,
+        } = &mut *entries;
+        // Pruned while the capture ran.
+        let Some(entry) = windows.get_mut(&id) else {
+            return false;
+        };
+        entry.pending = false;
+        entry.captured_at = Some(Instant::now());
+        match result {
+            Ok(bytes) => {
+                *captures += 1;
+                let uri = format!("bytes://qalttab/thumbnail/{id}/{captures}");
+                let new = Thumbnail {
+                    uri,
+                    bytes: bytes.into(),
+                };
+                if let Some(old) = entry.thumbnail.replace(new) {
+                    replaced.push(old.uri);
+                }
+                true
+            }
+            Err(e) => {
+                // Keep the previous image; try again once this one is stale.
+                log::debug!(
+                    "Failed to capture window {id} with {}: {e:#}",
+                    self.provider.name()
+                );
+                false
+            }
+        }
+    }
+
+    /// Drop every thumbnail and return all the URIs egui may still hold,
+    /// current and replaced ones alike.
+    pub fn clear(&self) -> Vec<String> {
+        let mut entries = self.entries.lock().unwrap();
+        let mut uris = std::mem::take(&mut entries.replaced);
+        uris.extend(
+            entries
+                .windows
+                .drain()
+                .filter_map(|(_, entry)| entry.thumbnail.map(|t| t.uri)),
+        );
+        uris
+    }
+
+    /// URIs of thumbnails no longer in use, so their textures can be freed.
+    pub fn take_replaced(&self) -> Vec<String> {
+        std::mem::take(&mut self.entries.lock().unwrap().replaced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Returns `"<id>:<n>"` for the n-th capture; window 13 cannot be captured.
+    #[derive(Default)]
+    struct FakeCapture {
+        calls: AtomicUsize,
+    }
+
+    impl CaptureProvider for FakeCapture {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn capture(&self, window: &WindowInfo) -> CaptureFuture<'_> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let id = window.id;
+            Box::pin(async move {
+                if id == 13 {
+                    bail!("window 13 is not mapped");
+                }
+                Ok(format!("{id}:{n}").into_bytes())
+            })
+        }
+    }
+
+    fn window(id: u64) -> WindowInfo {
+        WindowInfo {
+            id,
+            ..WindowInfo::default()
+        }
+    }
+
+    /// Refresh `windows` and wait until every capture has finished.
+    async fn refresh(cache: &ThumbnailCache, windows: &[WindowInfo]) -> usize {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        cache.refresh(windows, move || {
+            let _ = tx.send(());
+        });
+        let mut ready = 0;
+        while tokio::time::timeout(Duration::from_millis(100), rx.recv())
+            .await
+            .is_ok_and(|r| r.is_some())
+        {
+            ready += 1;
+        }
+        ready
+    }
+
+    fn bytes(cache: &ThumbnailCache, id: u64) -> Option<String> {
+        cache
+            .get(id)
+            .map(|t| String::from_utf8(t.bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn captures_once_until_stale() {
+        let provider = Arc::new(FakeCapture::default());
+        let cache = ThumbnailCache::new(provider.clone(), Duration::from_secs(60));
+        assert!(cache.get(1).is_none());
+
+        assert_eq!(refresh(&cache, &[window(1), window(2)]).await, 2);
+        assert!(bytes(&cache, 1).is_some());
+        assert!(bytes(&cache, 2).is_some());
+        assert_eq!(refresh(&cache, &[window(1), window(2)]).await, 0);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn stale_captures_are_replaced_with_a_new_uri() {
+        let cache = ThumbnailCache::new(Arc::new(FakeCapture::default()), Duration::ZERO);
+        refresh(&cache, &[window(1)]).await;
+        let first = cache.get(1).unwrap();
+        refresh(&cache, &[window(1)]).await;
+        let second = cache.get(1).unwrap();
+        assert_ne!(first.uri, second.uri);
+        assert_eq!(bytes(&cache, 1).as_deref(), Some("1:2"));
+        assert_eq!(cache.take_replaced(), vec![first.uri]);
+        assert!(cache.take_replaced().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failures_keep_nothing_and_closed_windows_are_pruned() {
+        let cache = ThumbnailCache::new(Arc::new(FakeCapture::default()), Duration::from_secs(60));
+        assert_eq!(refresh(&cache, &[window(1), window(13)]).await, 1);
+        assert!(cache.get(13).is_none());
+        // The failure counts as an attempt, so it is not retried right away.
+        assert_eq!(refresh(&cache, &[window(1), window(13)]).await, 0);
+
+        let uri = cache.get(1).unwrap().uri;
+        refresh(&cache, &[window(13)]).await;
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.take_replaced(), vec![uri]);
+    }
+
+    /// Records the order of captures and how many ran at once.
+    #[derive(Default)]
+    struct SlowCapture {
+        order: Mutex<Vec<u64>>,
+        running: AtomicUsize,
+        most_running: AtomicUsize,
+    }
+
+    impl CaptureProvider for SlowCapture {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn capture(&self, window: &WindowInfo) -> CaptureFuture<'_> {
+            let id = window.id;
+            Box::pin(async move {
+                self.order.lock().unwrap().push(id);
+                let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+                self.most_running.fetch_max(running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                Ok(id.to_string().into_bytes())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn captures_run_a_few_at_a_time_in_the_order_given() {
+        let provider = Arc::new(SlowCapture::default());
+        let cache = ThumbnailCache::new(provider.clone(), Duration::from_secs(60));
+        let windows: Vec<_> = [5, 1, 2, 3, 4, 5, 6].map(window).into();
+        assert_eq!(refresh(&cache, &windows).await, 6);
+        assert_eq!(*provider.order.lock().unwrap(), vec![5, 1, 2, 3, 4, 6]);
+        assert_eq!(
+            provider.most_running.load(Ordering::SeqCst),
+            MAX_CONCURRENT_CAPTURES
+        );
+    }
+
+    #[tokio::test]
+    async fn clear_returns_every_uri_in_use() {
+        let cache = ThumbnailCache::new(Arc::new(FakeCapture::default()), Duration::ZERO);
+        refresh(&cache, &[window(1)]).await;
+        let replaced = cache.get(1).unwrap().uri;
+        refresh(&cache, &[window(1), window(2)]).await;
+        let current = [cache.get(1).unwrap().uri, cache.get(2).unwrap().uri];
+
+        let uris = cache.clear();
+        assert_eq!(uris.len(), 3);
+        assert!(uris.contains(&replaced));
+        assert!(current.iter().all(|uri| uris.contains(uri)));
+        assert!(cache.get(1).is_none());
+        assert!(cache.clear().is_empty());
+    }
+
+    #[tokio::test]
+    async fn command_capture_substitutes_the_window_id() {
+        let capture = CommandCapture::new(
+            vec!["printf".into(), "{id} {hex_id}".into()],
+            Duration::from_secs(5),
+        );
+        assert_eq!(capture.capture(&window(255)).await.unwrap(), b"255 0xff");
+
+        let failing = CommandCapture::new(
+            vec![
+                "sh".into(),
+                "-c".into(),
+                "echo no such window >&2; exit 1".into(),
+            ],
+            Duration::from_secs(5),
+        );
+        let error = failing.capture(&window(1)).await.unwrap_err().to_string();
+        assert!(error.contains("no such window"), "{error}");
+    }
+}
@@ -1,10 +1,21 @@
 use std::{
     collections::HashMap,
+    future::Future,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 
-use crate::config::{Config, Font, Orientation};
+use crate::config::{
+    Colors, Config, Font, GroupOrder, Layout, LiveConfig, Orientation, OverlayMode,
+};
+use crate::qaltd::KeyListenerStatus;
+use crate::qtile::QtileCommand;
+use crate::thumbnails::ThumbnailCache;
+use crate::wm::{FocusHistory, OVERLAY_NAME, WindowManager, WmOp, WmQueue};
 use anyhow::bail;
 use egui::{
     Color32, FontData, FontDefinitions, FontFamily, Image, ImageSource, Label, Sense, Stroke, Ui,
@@ -12,74 +23,712 @@ use egui::{
 };
 use freedesktop_icons::lookup;
 use qtile_client_lib::utils::client::{CallResult, InteractiveCommandClient};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
-use sysinfo::{Pid, System};
-use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
 
-/// Abstraction over the Qtile IPC client.
-///
-/// The current impl (`IccQtileClient`) wraps the synchronous `QtileClient`
-/// from the framing-support API and is intended to be called from inside
-/// `tokio::task::spawn_blocking`.
-///
-/// TODO(#186): once the framing protocol is stable, make this trait async
-/// and remove the `spawn_blocking` wrappers.
+pub type QtileCallFuture<'a> =
+    Pin<Box<dyn Future<Output = anyhow::Result<serde_json::Value>> + Send + 'a>>;
+
+/// Abstraction over the Qtile IPC client, used by
+/// [`QtileBackend`](crate::qtile::QtileBackend).
 pub trait QtileClientTrait: Send + Sync {
-    fn call(
-        &self,
-        object: Option<Vec<String>>,
-        function: Option<String>,
-        args: Option<Vec<String>>,
-    ) -> anyhow::Result<serde_json::Value>;
+    fn call(&self, command: QtileCommand) -> QtileCallFuture<'_>;
 }
 
 /// Production implementation using `InteractiveCommandClient` from qtile-cmd-client main.
+///
+/// That client is synchronous, so each call runs on tokio's blocking pool.
 pub struct IccQtileClient;
 
 impl QtileClientTrait for IccQtileClient {
-    fn call(
-        &self,
-        object: Option<Vec<String>>,
-        function: Option<String>,
-        args: Option<Vec<String>>,
-    ) -> anyhow::Result<serde_json::Value> {
-        match InteractiveCommandClient::call(object, function, args, false)? {
-            CallResult::Value(v) => Ok(v),
-            CallResult::Text(t) => Ok(serde_json::Value::String(t)),
-        }
+    fn call(&self, command: QtileCommand) -> QtileCallFuture<'_> {
+        Box::pin(async move {
+            let QtileCommand {
+                object,
+                function,
+                args,
+            } = command;
+            let result = tokio::task::spawn_blocking(move || {
+                InteractiveCommandClient::call(Some(object), Some(function), Some(args), false)
+            })
+            .await??;
+            match result {
+                CallResult::Value(v) => Ok(v),
+                CallResult::Text(t) => Ok(serde_json::Value::String(t)),
+            }
+        })
+    }
+}
+
+/// A single entry of the window list sent by the Qtile hook.
+///
+/// Only `id` is required; the other fields fall back to an empty string so a
+/// hook that omits one of them degrades the card instead of crashing the overlay.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WindowInfo {
+    #[serde(deserialize_with = "deserialize_window_id")]
+    pub id: u64,
+    #[serde(default, deserialize_with = "deserialize_lenient_string")]
+    pub name: String,
+    #[serde(default, deserialize_with = "deserialize_lenient_string")]
+    pub class: String,
+    #[serde(default, deserialize_with = "deserialize_lenient_string")]
+    pub group_name: String,
+    #[serde(default, deserialize_with = "deserialize_lenient_string")]
+    pub group_label: String,
+    /// Any additional keys sent by the hook, kept verbatim.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// Accept the window ID either as a JSON number or as a numeric string.
+fn deserialize_window_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Number(n) => n
+            .as_u64()
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid window id {n}"))),
+        Value::String(s) => s
+            .trim()
+            .parse()
+            .map_err(|_| serde::de::Error::custom(format!("invalid window id {s:?}"))),
+        other => Err(serde::de::Error::custom(format!(
+            "invalid window id {other}"
+        ))),
+    }
+}
+
+/// Accept strings, treat `null` as empty and stringify numbers and booleans.
+fn deserialize_lenient_string<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<String, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(s) => Ok(s),
+        Value::Null => Ok(String::new()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(serde::de::Error::custom(format!(
+            "expected a string, got {other}"
+        ))),
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Response {
     pub message_type: MessageType,
-    pub windows: Vec<HashMap<String, String>>,
+    pub windows: Vec<WindowInfo>,
     pub focus_index: Option<usize>,
 }
 
+/// Commands that drive the overlay directly, sent by `qalttab ctl` or by
+/// keys pressed while the overlay has focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlCommand {
+    Next,
+    Prev,
+    Show,
+    Hide,
+    Select(usize),
+    /// Focus the selected window and hide the overlay.
+    Accept,
+    /// Close the selected window and keep the overlay open on the rest.
+    Close,
+}
+
+/// Overlay activity pushed to IPC connections that sent `subscribe`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum OverlayEvent {
+    Shown {
+        focus_index: usize,
+        window: Option<WindowInfo>,
+    },
+    Hidden,
+    SelectionChanged {
+        focus_index: usize,
+        window: Option<WindowInfo>,
+    },
+    WindowFocused {
+        window: WindowInfo,
+    },
+    WindowClosed {
+        window: WindowInfo,
+    },
+    /// A queued window manager operation failed, e.g. the window to focus was already gone.
+    CommandFailed {
+        operation: String,
+        error: String,
+    },
+}
+
+/// Publish an overlay event to IPC subscribers. Having none is not an error.
+fn publish(events: &broadcast::Sender<OverlayEvent>, event: OverlayEvent) {
+    log::debug!("Overlay event: {event:?}");
+    let _ = events.send(event);
+}
+
 #[derive(Debug, Clone)]
 pub enum AppEvent {
     AltReleased,
     UnixSocketMsg(Response),
+    Control(ControlCommand),
     OurWindowId(String),
+    Search(SearchEdit),
+}
+
+/// Edits to the search query, typed while the overlay has focus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchEdit {
+    Type(String),
+    Backspace,
 }
 
 /// Shared state between the tokio event processor and the egui render loop.
 #[derive(Default)]
 pub struct SharedState {
     pub current_focus_history: Option<Response>,
+    /// Windows from most to least recently focused, built from `client_focus` messages.
+    pub focus_history: FocusHistory,
+    /// Window list the switcher opens on, kept while the overlay is hidden.
+    pub last_windows: Vec<WindowInfo>,
     pub is_visible: bool,
     pub cached_wid: Option<String>,
     pub last_placed_height: f32,
     pub last_width: i32,
     pub last_height: i32,
+    /// Index into [`SharedState::visible_windows`].
     pub focus_index: usize,
+    /// Search typed since the overlay opened; filters the cards.
+    pub query: String,
+    pub key_listener: KeyListenerStatus,
+}
+
+impl SharedState {
+    /// Record the focus change reported by a `client_focus` message and make
+    /// the updated focus history the list the switcher opens on.
+    pub fn record_focus(&mut self, response: Response) {
+        let focused = response.focus_index.unwrap_or(0);
+        self.focus_history.observe(response.windows, focused);
+        self.last_windows = self.focus_history.windows().to_vec();
+        self.current_focus_history = Some(Response {
+            message_type: MessageType::ClientFocus,
+            windows: self.last_windows.clone(),
+            focus_index: Some(0),
+        });
+    }
+
+    /// Forget a window we closed, without waiting for the next focus change.
+    /// If the overlay lists it, the selection stays on the same window, or
+    /// on its neighbour when the selected one goes.
+    pub fn forget_window(&mut self, id: u64) {
+        self.focus_history.remove(id);
+        self.last_windows.retain(|w| w.id != id);
+        let index = self.visible_windows().iter().position(|w| w.id == id);
+        if let Some(current) = &mut self.current_focus_history {
+            current.windows.retain(|w| w.id != id);
+        }
+        if let Some(index) = index {
+            if index < self.focus_index {
+                self.focus_index -= 1;
+            }
+            self.focus_index = self
+                .focus_index
+                .min(self.visible_windows().len().saturating_sub(1));
+        }
+    }
+
+    /// The cards the overlay shows: its window list, filtered by the query.
+    pub fn visible_windows(&self) -> Vec<WindowInfo> {
+        let windows = self
+            .current_focus_history
+            .as_ref()
+            .map_or(&[][..], |h| &h.windows);
+        crate::search::filter_windows(&self.query, windows)
+    }
+
+    /// The window the overlay has selected, if it shows any.
+    pub fn selected_window(&self) -> Option<WindowInfo> {
+        self.visible_windows().get(self.focus_index).cloned()
+    }
+
+    /// Size of our window as last placed, or 300x400 before the first placement.
+    pub fn overlay_size(&self) -> (i32, i32) {
+        let w = if self.last_width > 0 {
+            self.last_width
+        } else {
+            300
+        };
+        let h = if self.last_height > 0 {
+            self.last_height
+        } else {
+            400
+        };
+        (w, h)
+    }
+
+    /// Snapshot of the state as reported by the `get_state` query.
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "visible": self.is_visible,
+            "focus_index": self.focus_index,
+            "cached_wid": self.cached_wid,
+            "windows": self.last_windows,
+            "query": self.query,
+            "key_listener": self.key_listener,
+            "degraded": self.key_listener.is_degraded(),
+        })
+    }
+}
+
+/// Selection resulting from a control command on a list of `len` windows.
+///
+/// While hidden, `next` and `prev` open the overlay on the window after or
+/// before the focused one (index 0), like a regular alt-tab press.
+pub fn control_focus_index(
+    command: ControlCommand,
+    is_visible: bool,
+    focus_index: usize,
+    len: usize,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    match command {
+        ControlCommand::Next if is_visible => (focus_index + 1) % len,
+        ControlCommand::Next => 1 % len,
+        ControlCommand::Prev if is_visible => (focus_index + len - 1) % len,
+        ControlCommand::Prev => len - 1,
+        ControlCommand::Select(index) => index.min(len - 1),
+        ControlCommand::Show
+        | ControlCommand::Hide
+        | ControlCommand::Accept
+        | ControlCommand::Close
+            if is_visible =>
+        {
+            focus_index.min(len - 1)
+        }
+        ControlCommand::Show
+        | ControlCommand::Hide
+        | ControlCommand::Accept
+        | ControlCommand::Close => 0,
+    }
+}
+
+/// Mark the overlay visible with `response`, then unhide our window and
+/// place it with its last known size.
+fn show_overlay(
+    wm: &WmQueue,
+    shared: &Arc<Mutex<SharedState>>,
+    events: &broadcast::Sender<OverlayEvent>,
+    response: Response,
+    focus_index: usize,
+) {
+    let window = response.windows.get(focus_index).cloned();
+    let selected = window.as_ref().map(|w| w.id);
+    let (wid, (w, h)) = {
+        let mut state = shared.lock().unwrap();
+        if !state.is_visible {
+            state.query.clear();
+            publish(
+                events,
+                OverlayEvent::Shown {
+                    focus_index,
+                    window,
+                },
+            );
+        } else if state.focus_index != focus_index {
+            publish(
+                events,
+                OverlayEvent::SelectionChanged {
+                    focus_index,
+                    window,
+                },
+            );
+        }
+        state.is_visible = true;
+        state.last_placed_height = 0.0;
+        state.focus_index = focus_index;
+        state.current_focus_history = Some(response);
+        (state.cached_wid.clone(), state.overlay_size())
+    };
+    if let Some(wid) = wid {
+        wm.push(WmOp::Show {
+            wid,
+            width: w,
+            height: h,
+            selected,
+        });
+    }
+}
+
+/// Let our window follow a new selection to its screen, for the
+/// `selection` placement policy.
+fn follow_selection(wm: &WmQueue, shared: &Arc<Mutex<SharedState>>) {
+    let state = shared.lock().unwrap();
+    if let (Some(wid), Some(window)) = (state.cached_wid.clone(), state.selected_window()) {
+        let (width, height) = state.overlay_size();
+        wm.push(WmOp::Follow {
+            wid,
+            width,
+            height,
+            selected: window.id,
+        });
+    }
+}
+
+/// Hide our window through the window manager.
+fn hide_window(wm: &WmQueue, wid: String) {
+    wm.push(WmOp::Hide { wid });
+}
+
+/// Mark the overlay hidden and hide our window, without focusing anything.
+fn close_overlay(
+    wm: &WmQueue,
+    shared: &Arc<Mutex<SharedState>>,
+    events: &broadcast::Sender<OverlayEvent>,
+) {
+    let mut state = shared.lock().unwrap();
+    if state.is_visible {
+        publish(events, OverlayEvent::Hidden);
+    }
+    state.is_visible = false;
+    state.current_focus_history = None;
+    state.query.clear();
+    state.last_placed_height = 0.0;
+    if let Some(wid) = state.cached_wid.clone() {
+        drop(state);
+        hide_window(wm, wid);
+    }
+}
+
+/// Control command bound to `key` while the overlay has keyboard focus.
+pub fn key_command(key: egui::Key, modifiers: egui::Modifiers) -> Option<ControlCommand> {
+    use egui::Key;
+    Some(match key {
+        Key::Tab if modifiers.shift => ControlCommand::Prev,
+        Key::Tab | Key::ArrowRight | Key::ArrowDown => ControlCommand::Next,
+        Key::ArrowLeft | Key::ArrowUp => ControlCommand::Prev,
+        Key::Enter => ControlCommand::Accept,
+        Key::Escape => ControlCommand::Hide,
+        Key::Delete => ControlCommand::Close,
+        _ => return None,
+    })
+}
+
+/// Find our own window through the window manager and remember its id,
+/// retrying until it shows up. The window is hidden once found.
+pub async fn discover_window_id(
+    wm: &dyn WindowManager,
+    queue: &WmQueue,
+    shared: &Arc<Mutex<SharedState>>,
+) {
+    log::debug!("Starting background WID discovery...");
+    loop {
+        let res = wm.find_window(OVERLAY_NAME).await;
+
+        log::debug!("WID discovery result: {:?}", res);
+        if let Ok(Some(wid)) = res {
+            let wid = wid.to_string();
+            log::info!("Discovered our Window ID: {}", wid);
+            shared.lock().unwrap().cached_wid = Some(wid.clone());
+            // Hide off-screen initially
+            hide_window(queue, wid);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+}
+
+/// Place our window for a `width` x `height` overlay, unless it was already
+/// placed at that height. Does nothing until our window id is known.
+pub fn resize_overlay(wm: &WmQueue, shared: &Arc<Mutex<SharedState>>, width: i32, height: i32) {
+    let mut state = shared.lock().unwrap();
+    if (state.last_placed_height - height as f32).abs() < 1.0 {
+        return;
+    }
+
+    if let Some(wid) = state.cached_wid.clone() {
+        log::debug!("Resizing window ({wid}) to {width}x{height}");
+        let selected = state.selected_window().map(|w| w.id);
+        wm.push(WmOp::Place {
+            wid,
+            width,
+            height,
+            selected,
+        });
+        state.last_placed_height = height as f32;
+        state.last_width = width;
+        state.last_height = height;
+    }
+}
+
+/// Background event processor: reacts to hook messages, modifier releases
+/// and control commands until the event channel closes.
+///
+/// Expects `shared.cached_wid` to be set already; without it the overlay
+/// state still changes but our window is never moved. Window-manager calls
+/// are queued on `wm` and run in the order the events arrived.
+pub async fn process_events(
+    wm: WmQueue,
+    shared: Arc<Mutex<SharedState>>,
+    events: broadcast::Sender<OverlayEvent>,
+    ctx: egui::Context,
+    mut rx: UnboundedReceiver<AppEvent>,
+) {
+    let mut cycle_active = false;
+    #[allow(unused_assignments)]
+    let mut pending_hide: Option<tokio::task::JoinHandle<()>> = None;
+
+    while let Some(event) = rx.recv().await {
+        match event {
+            AppEvent::AltReleased => {
+                log::debug!("AltReleased event | cycle_active={}", cycle_active);
+                if cycle_active {
+                    // Schedule hide after delay — cancelled if new CycleWindows arrives
+                    if let Some(ref handle) = pending_hide
+                        && !handle.is_finished()
+                    {
+                        continue; // still running
+                    }
+                    let shared_hide = shared.clone();
+                    let wid_hide = shared.lock().unwrap().cached_wid.clone();
+                    let wm_hide = wm.clone();
+                    let events_hide = events.clone();
+                    pending_hide = Some(tokio::spawn(async move {
+                        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+                        log::debug!("Delayed hide executing");
+                        if let Some(wid) = wid_hide {
+                            hide_window(&wm_hide, wid);
+                        }
+                        let selected = {
+                            let mut state = shared_hide.lock().unwrap();
+                            let selected = state.selected_window();
+                            if let Some(window) = selected.clone() {
+                                publish(&events_hide, OverlayEvent::WindowFocused { window });
+                            }
+                            publish(&events_hide, OverlayEvent::Hidden);
+                            state.is_visible = false;
+                            state.current_focus_history = None;
+                            state.query.clear();
+                            state.last_placed_height = 0.0;
+                            selected
+                        };
+                        wm_hide.push(WmOp::Commit {
+                            wid: selected.map(|w| w.id),
+                        });
+                    }));
+                    cycle_active = false;
+                }
+            }
+            AppEvent::UnixSocketMsg(response) => {
+                log::debug!("UnixSocketMsg: {:?}", response.message_type);
+                match response.message_type {
+                    MessageType::CycleWindows => {
+                        // Cancel pending hide if user pressed tab again
+                        if let Some(handle) = pending_hide.take() {
+                            handle.abort();
+                            log::debug!("Cancelled pending hide");
+                        }
+                        cycle_active = true;
+                        let fi = response.focus_index.unwrap_or(0);
+                        shared.lock().unwrap().last_windows = response.windows.clone();
+                        show_overlay(&wm, &shared, &events, response, fi);
+                        ctx.request_repaint();
+                    }
+                    MessageType::ClientFocus => {
+                        if cycle_active {
+                            // User clicked a window outside the overlay — cancel cycle and hide
+                            if let Some(handle) = pending_hide.take() {
+                                handle.abort();
+                            }
+                            cycle_active = false;
+                            let mut state = shared.lock().unwrap();
+                            if state.is_visible {
+                                publish(&events, OverlayEvent::Hidden);
+                            }
+                            state.is_visible = false;
+                            state.record_focus(response);
+                            state.last_placed_height = 0.0;
+                            if let Some(wid) = state.cached_wid.clone() {
+                                drop(state);
+                                hide_window(&wm, wid);
+                            }
+                        } else {
+                            shared.lock().unwrap().record_focus(response);
+                        }
+                    }
+                    MessageType::None => {}
+                }
+                ctx.request_repaint();
+            }
+            AppEvent::Control(command) => {
+                log::debug!("Control: {command:?}");
+                let (is_visible, focus_index, windows) = {
+                    let state = shared.lock().unwrap();
+                    let windows = if state.is_visible {
+                        state.visible_windows()
+                    } else {
+                        state.last_windows.clone()
+                    };
+                    (state.is_visible, state.focus_index, windows)
+                };
+                if command == ControlCommand::Hide {
+                    if let Some(handle) = pending_hide.take() {
+                        handle.abort();
+                    }
+                    cycle_active = false;
+                    close_overlay(&wm, &shared, &events);
+                } else if matches!(command, ControlCommand::Accept | ControlCommand::Close)
+                    && !is_visible
+                {
+                    log::debug!("Ignoring {command:?}: overlay is hidden");
+                } else if command == ControlCommand::Accept {
+                    if let Some(handle) = pending_hide.take() {
+                        handle.abort();
+                    }
+                    cycle_active = false;
+                    let selected = shared.lock().unwrap().selected_window();
+                    if let Some(window) = selected.clone() {
+                        publish(&events, OverlayEvent::WindowFocused { window });
+                    }
+                    close_overlay(&wm, &shared, &events);
+                    if let Some(window) = selected {
+                        wm.push(WmOp::Focus { wid: window.id });
+                    }
+                } else if command == ControlCommand::Close {
+                    let mut state = shared.lock().unwrap();
+                    if let Some(window) = state.selected_window() {
+                        publish(
+                            &events,
+                            OverlayEvent::WindowClosed {
+                                window: window.clone(),
+                            },
+                        );
+                        state.forget_window(window.id);
+                        let (focus_index, selected) = (state.focus_index, state.selected_window());
+                        drop(state);
+                        wm.push(WmOp::Kill { wid: window.id });
+                        if selected.is_some() {
+                            publish(
+                                &events,
+                                OverlayEvent::SelectionChanged {
+                                    focus_index,
+                                    window: selected,
+                                },
+                            );
+                            follow_selection(&wm, &shared);
+                        } else {
+                            cycle_active = false;
+                            close_overlay(&wm, &shared, &events);
+                        }
+                    }
+                } else if windows.is_empty() {
+                    log::debug!("Ignoring {command:?}: no window list received yet");
+                } else {
+                    let fi = control_focus_index(command, is_visible, focus_index, windows.len());
+                    if is_visible {
+                        shared.lock().unwrap().focus_index = fi;
+                        if fi != focus_index {
+                            publish(
+                                &events,
+                                OverlayEvent::SelectionChanged {
+                                    focus_index: fi,
+                                    window: windows.get(fi).cloned(),
+                                },
+                            );
+                            follow_selection(&wm, &shared);
+                        }
+                    } else {
+                        if let Some(handle) = pending_hide.take() {
+                            handle.abort();
+                        }
+                        cycle_active = true;
+                        let response = Response {
+                            message_type: MessageType::CycleWindows,
+                            windows,
+                            focus_index: Some(fi),
+                        };
+                        show_overlay(&wm, &shared, &events, response, fi);
+                    }
+                }
+                ctx.request_repaint();
+            }
+            AppEvent::Search(edit) => {
+                let mut state = shared.lock().unwrap();
+                if !state.is_visible {
+                    continue;
+                }
+                match edit {
+                    SearchEdit::Type(text) => state.query.push_str(&text),
+                    SearchEdit::Backspace => {
+                        state.query.pop();
+                    }
+                }
+                log::debug!("Search query: {:?}", state.query);
+                // Matches are sorted best first.
+                state.focus_index = 0;
+                let window = state.selected_window();
+                drop(state);
+                publish(
+                    &events,
+                    OverlayEvent::SelectionChanged {
+                        focus_index: 0,
+                        window,
+                    },
+                );
+                follow_selection(&wm, &shared);
+                ctx.request_repaint();
+            }
+            AppEvent::OurWindowId(_) => {
+                // Handled during WID discovery above
+            }
+        }
+    }
 }
 
 pub struct AsyncApp {
+    tx: UnboundedSender<AppEvent>,
     shared: Arc<Mutex<SharedState>>,
-    config: Config,
-    qtile: Arc<dyn QtileClientTrait>,
+    /// Snapshot of `live_config` the last frame was drawn with.
+    config: Arc<Config>,
+    /// `config.colors`, parsed when the config is applied.
+    colors: Palette,
+    live_config: LiveConfig,
+    /// Set when the cards show thumbnails and a capture provider is configured.
+    thumbnails: Option<ThumbnailCache>,
+    wm: WmQueue,
+    events: broadcast::Sender<OverlayEvent>,
+    /// Set when a `quit` request asks this instance to make way.
+    shutdown: Arc<AtomicBool>,
+}
+
+/// The configured colours as egui colours.
+#[derive(Clone, Copy, Debug)]
+struct Palette {
+    bg: Color32,
+    text: Color32,
+    normal_group: Color32,
+    group_hover: Color32,
+}
+
+impl Palette {
+    /// A colour that does not parse gets its default. `config::parse` has
+    /// already replaced those, but a `Config` can be built without it.
+    fn new(colors: &Colors) -> Self {
+        let defaults = Colors::default();
+        let parse = |color: &str, default: &str| {
+            Color32::from_hex(color)
+                .or_else(|_| Color32::from_hex(default))
+                .unwrap_or(Color32::GRAY)
+        };
+        Self {
+            bg: parse(&colors.bg_color, &defaults.bg_color),
+            text: parse(&colors.text_color, &defaults.text_color),
+            normal_group: parse(&colors.normal_group_color, &defaults.normal_group_color),
+            group_hover: parse(&colors.group_hover_color, &defaults.group_hover_color),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -89,6 +738,61 @@ pub enum MessageType {
     None,
 }
 
+/// The cards under one group header in the `grouped` layout.
+#[derive(Debug, PartialEq)]
+pub struct Section<'a> {
+    pub name: &'a str,
+    pub label: &'a str,
+    /// The group's windows, each with its index in the full list.
+    pub windows: Vec<(usize, &'a WindowInfo)>,
+}
+
+/// Split `windows` into one section per group. Windows keep their order
+/// within a section. With [`GroupOrder::Mru`] the sections come in the order
+/// their first window appears, otherwise sorted by group name.
+pub fn group_sections(windows: &[WindowInfo], order: GroupOrder) -> Vec<Section<'_>> {
+    let mut sections: Vec<Section<'_>> = Vec::new();
+    for (index, win) in windows.iter().enumerate() {
+        match sections.iter_mut().find(|s| s.name == win.group_name) {
+            Some(section) => section.windows.push((index, win)),
+            None => sections.push(Section {
+                name: &win.group_name,
+                label: &win.group_label,
+                windows: vec![(index, win)],
+            }),
+        }
+    }
+    if order == GroupOrder::Group {
+        sections.sort_by(|a, b| compare_group_names(a.name, b.name));
+    }
+    sections
+}
+
+/// Numeric names first and in numeric order, then the rest alphabetically.
+fn compare_group_names(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// Split `text` into runs of characters that are or are not at `positions`
+/// (char indices), in order. Positions past the end of `text` are ignored.
+pub fn highlight_runs(text: &str, positions: &[usize]) -> Vec<(String, bool)> {
+    let mut runs: Vec<(String, bool)> = Vec::new();
+    for (i, c) in text.chars().enumerate() {
+        let matched = positions.contains(&i);
+        match runs.last_mut() {
+            Some((run, m)) if *m == matched => run.push(c),
+            _ => runs.push((c.to_string(), matched)),
+        }
+    }
+    runs
+}
+
 /// Truncate `name` to at most `max_chars` characters, respecting Unicode char boundaries.
 pub fn truncate_window_name(name: &str, max_chars: usize) -> String {
     if name.chars().count() > max_chars {
@@ -130,297 +834,161 @@ impl AsyncApp {
             .extend([(FontFamily::Name(font_family_name.into()), Vec::new())]);
     }
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        Self::new_with_client(cc, Arc::new(IccQtileClient))
+        Self::new_with_backend(cc, crate::wm::backend)
     }
 
-    pub fn new_with_client(
+    /// Like [`AsyncApp::new`], with the window manager picked by `backend` from the loaded config.
+    pub fn new_with_backend(
         cc: &eframe::CreationContext<'_>,
-        qtile: Arc<dyn QtileClientTrait>,
+        backend: impl FnOnce(&Config) -> Arc<dyn WindowManager>,
+    ) -> Self {
+        Self::with_config(&cc.egui_ctx, crate::config::load(), backend)
+    }
+
+    /// Fonts named by `config`, on top of egui's own.
+    pub fn font_definitions(config: &Config) -> FontDefinitions {
+        let mut fonts = FontDefinitions::default();
+        for family in [&config.fonts.text_font, &config.fonts.icon_font] {
+            Self::add_font_family(&mut fonts, &family.family_name);
+            for font in &family.fonts {
+                Self::add_font(&mut fonts, &family.family_name, font);
+            }
+        }
+        fonts
+    }
+
+    /// Set up the app on `ctx`, whichever host draws it, and start its
+    /// background tasks.
+    pub fn with_config(
+        ctx: &egui::Context,
+        config: Config,
+        backend: impl FnOnce(&Config) -> Arc<dyn WindowManager>,
     ) -> Self {
-        let (tx, mut rx) = unbounded_channel::<AppEvent>();
+        let (tx, rx) = unbounded_channel::<AppEvent>();
         let shared = Arc::new(Mutex::new(SharedState::default()));
+        let fonts = Self::font_definitions(&config);
+
+        let wm = backend(&config);
+        log::info!("Using the {} window manager backend", wm.name());
+        let live_config = LiveConfig::new(config);
+        let config = live_config.get();
+        let repaint = ctx.clone();
+        tokio::spawn(crate::config::watch(live_config.clone(), move || {
+            repaint.request_repaint()
+        }));
 
         // Spawn listeners
-        let tx_socket = tx.clone();
-        let ctx_socket = cc.egui_ctx.clone();
+        let ipc = crate::ipc::IpcContext::new(
+            tx.clone(),
+            ctx.clone(),
+            shared.clone(),
+            live_config.clone(),
+        );
+        let events = ipc.events.clone();
+        let shutdown = ipc.shutdown.clone();
+        let queue = WmQueue::start(Arc::clone(&wm), events.clone());
+        let max_frame_size = config.ipc.max_frame_size;
         tokio::spawn(async move {
-            if let Err(e) = crate::ipc::listen(tx_socket, ctx_socket, None).await {
+            if let Err(e) = crate::ipc::listen(ipc, None, max_frame_size).await {
                 log::error!("Unix socket listener error: {e:?}");
             }
         });
 
-        let tx_alt = tx.clone();
-        let ctx_alt = cc.egui_ctx.clone();
+        let input = config.input.clone();
+        tokio::spawn(crate::qaltd::supervise_key_source(
+            move || crate::qaltd::key_source(&input),
+            tx.clone(),
+            ctx.clone(),
+            shared.clone(),
+            crate::qaltd::Backoff::default(),
+        ));
+
+        let wm_focus = Arc::clone(&wm);
+        let tx_focus = tx.clone();
         tokio::spawn(async move {
-            if let Err(e) = crate::qaltd::listen_for_alt_release(tx_alt, ctx_alt).await {
-                log::error!("qaltd listener error: {e:?}");
+            if let Err(e) = wm_focus.watch_focus(tx_focus).await {
+                log::error!("Lost focus events from {}: {e:#}", wm_focus.name());
             }
         });
 
         // Background event processor — runs independently of egui's render loop
-        let qtile_bg = Arc::clone(&qtile);
-        let shared_clone = shared.clone();
-        let ctx_events = cc.egui_ctx.clone();
+        let queue_bg = queue.clone();
+        let shared_bg = shared.clone();
+        let ctx_events = ctx.clone();
+        let events_bg = events.clone();
         tokio::spawn(async move {
-            #[allow(unused_assignments)]
-            let mut cached_wid: Option<String> = None;
-            let mut cycle_active = false;
-            #[allow(unused_assignments)]
-            let mut pending_hide: Option<tokio::task::JoinHandle<()>> = None;
-
-            // Wait for eframe/winit to initialize to avoid Xwayland/IPC deadlock with Qtile
-            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-
-            // First, discover our WID
-            log::debug!("Starting background WID discovery...");
-            loop {
-                let qtile_c = Arc::clone(&qtile_bg);
-                let res = tokio::task::spawn_blocking(move || {
-                    qtile_c.call(
-                        Some(vec![]),
-                        Some("eval".into()),
-                        Some(vec![
-                            r#"__import__("json").dumps(
-                        [
-                            {
-                                "wid": str(self.windows_map[wid].wid),
-                                "name": self.windows_map[wid].name or ""
-                            }
-                            for wid in self.windows_map
-                            if hasattr(self.windows_map[wid], "wid")
-                        ]
-                    )"#
-                            .into(),
-                        ]),
-                    )
-                })
-                .await;
-
-                log::debug!("WID discovery result: {:?}", res);
-                if let Ok(Ok(val)) = res {
-                    let val = match val {
-                        Value::Array(mut a) if a.len() == 2 => a.remove(1),
-                        _ => val,
-                    };
-                    if let Ok(json_str) = serde_json::from_value::<String>(val)
-                        && let Ok(windows) =
-                            serde_json::from_str::<Vec<HashMap<String, String>>>(&json_str)
-                        && let Some(win) = windows
-                            .iter()
-                            .find(|m| m.get("name").map(|s| s.as_str()) == Some("qalttab"))
-                        && let Some(wid) = win.get("wid")
-                    {
-                        log::info!("Discovered our Window ID: {}", wid);
-                        cached_wid = Some(wid.clone());
-                        shared_clone.lock().unwrap().cached_wid = Some(wid.clone());
-                        // Hide off-screen initially
-                        let wid_c = wid.clone();
-                        let qtile_c = Arc::clone(&qtile_bg);
-                        tokio::task::spawn_blocking(move || {
-                            let _ = qtile_c.call(
-                                Some(vec![]),
-                                Some("eval".into()),
-                                Some(vec![format!("self.windows_map[{wid_c}].hide()")]),
-                            );
-                        });
-                        break;
-                    }
-                }
-                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-            }
-
-            // Main event loop — processes events from channel
-            while let Some(event) = rx.recv().await {
-                match event {
-                    AppEvent::AltReleased => {
-                        log::debug!("AltReleased event | cycle_active={}", cycle_active);
-                        if cycle_active {
-                            // Schedule hide after delay — cancelled if new CycleWindows arrives
-                            if let Some(ref handle) = pending_hide
-                                && !handle.is_finished()
-                            {
-                                continue; // still running
-                            }
-                            let shared_hide = shared_clone.clone();
-                            let wid_hide = cached_wid.clone();
-                            let qtile_hide = Arc::clone(&qtile_bg);
-                            pending_hide = Some(tokio::spawn(async move {
-                                tokio::time::sleep(std::time::Duration::from_millis(150)).await;
-                                log::debug!("Delayed hide executing");
-                                if let Some(wid) = wid_hide {
-                                    let qtile_c = Arc::clone(&qtile_hide);
-                                    tokio::task::spawn_blocking(move || {
-                                        let _ = qtile_c.call(
-                                            Some(vec![]),
-                                            Some("eval".into()),
-                                            Some(vec![format!("self.windows_map[{wid}].hide()")]),
-                                        );
-                                    })
-                                    .await
-                                    .ok();
-                                }
-                                {
-                                    let mut state = shared_hide.lock().unwrap();
-                                    state.is_visible = false;
-                                    state.current_focus_history = None;
-                                    state.last_placed_height = 0.0;
-                                }
-                                let qtile_c = Arc::clone(&qtile_hide);
-                                tokio::task::spawn_blocking(move || {
-                                    let _ = qtile_c.call(
-                                        Some(vec![]),
-                                        Some("fire_user_hook".into()),
-                                        Some(vec!["alt_release".to_owned()]),
-                                    );
-                                })
-                                .await
-                                .ok();
-                            }));
-                            cycle_active = false;
-                        }
-                    }
-                    AppEvent::UnixSocketMsg(response) => {
-                        log::debug!("UnixSocketMsg: {:?}", response.message_type);
-                        match response.message_type {
-                            MessageType::CycleWindows => {
-                                // Cancel pending hide if user pressed tab again
-                                if let Some(handle) = pending_hide.take() {
-                                    handle.abort();
-                                    log::debug!("Cancelled pending hide");
-                                }
-                                cycle_active = true;
-                                let fi = response.focus_index.unwrap_or(0);
-                                {
-                                    let mut state = shared_clone.lock().unwrap();
-                                    state.is_visible = true;
-                                    state.last_placed_height = 0.0;
-                                    state.focus_index = fi;
-                                    state.current_focus_history = Some(response);
-                                }
-                                // Unhide + place centered with last known size
-                                let (w, h) = {
-                                    let st = shared_clone.lock().unwrap();
-                                    let w = if st.last_width > 0 {
-                                        st.last_width
-                                    } else {
-                                        300
-                                    };
-                                    let h = if st.last_height > 0 {
-                                        st.last_height
-                                    } else {
-                                        400
-                                    };
-                                    (w, h)
-                                };
-                                if let Some(wid) = cached_wid.clone() {
-                                    let qtile_c = Arc::clone(&qtile_bg);
-                                    tokio::task::spawn_blocking(move || {
-                                        let _ = qtile_c.call(
-                                            Some(vec![]),
-                                            Some("eval".into()),
-                                            Some(vec![format!(
-                                                "w = self.windows_map[{wid}]; \
-                                                 w.unhide(); \
-                                                 s = self.current_screen; \
-                                                 x = s.dx + (s.dwidth - {w}) // 2; \
-                                                 y = s.dy + (s.dheight - {h}) // 2; \
-                                                 w.place(x, y, {w}, {h}, 0, None); \
-                                                 w.keep_above(); \
-                                                 w.bring_to_front()"
-                                            )]),
-                                        );
-                                    });
-                                }
-                                ctx_events.request_repaint();
-                            }
-                            MessageType::ClientFocus => {
-                                if cycle_active {
-                                    // User clicked a window outside the overlay — cancel cycle and hide
-                                    if let Some(handle) = pending_hide.take() {
-                                        handle.abort();
-                                    }
-                                    cycle_active = false;
-                                    let mut state = shared_clone.lock().unwrap();
-                                    state.is_visible = false;
-                                    state.current_focus_history = Some(response);
-                                    state.last_placed_height = 0.0;
-                                    if let Some(wid) = state.cached_wid.clone() {
-                                        drop(state);
-                                        let qtile_c = Arc::clone(&qtile_bg);
-                                        tokio::task::spawn_blocking(move || {
-                                            let _ = qtile_c.call(
-                                                Some(vec![]),
-                                                Some("eval".into()),
-                                                Some(vec![format!(
-                                                    "self.windows_map[{wid}].hide()"
-                                                )]),
-                                            );
-                                        });
-                                    }
-                                } else {
-                                    let mut state = shared_clone.lock().unwrap();
-                                    state.current_focus_history = Some(response);
-                                }
-                            }
-                            MessageType::None => {}
-                        }
-                        ctx_events.request_repaint();
-                    }
-                    AppEvent::OurWindowId(_) => {
-                        // Handled during WID discovery above
-                    }
-                }
+            if let Some(wid) = wm.overlay_id() {
+                // Nothing to discover, and the overlay starts hidden.
+                shared_bg.lock().unwrap().cached_wid = Some(wid.to_string());
+            } else {
+                // Wait for eframe/winit to initialize to avoid Xwayland/IPC deadlock with Qtile
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                discover_window_id(wm.as_ref(), &queue_bg, &shared_bg).await;
             }
+            process_events(queue_bg, shared_bg, events_bg, ctx_events, rx).await;
         });
 
-        let cfg: Result<Config, confy::ConfyError> = confy::load("qalttab", Some("config"));
-        let mut fonts = FontDefinitions::default();
-        let config = match cfg {
-            Ok(cfg) => {
-                log::debug!("Loaded config: {cfg:#?}");
-                let (cfg_family, cfg_text_fonts) =
-                    (&cfg.fonts.text_font.family_name, &cfg.fonts.text_font.fonts);
-                Self::add_font_family(&mut fonts, cfg_family.as_str());
-                for font in cfg_text_fonts {
-                    Self::add_font(&mut fonts, cfg_family.as_str(), font);
-                }
-                let (cfg_family, cfg_icon_fonts) =
-                    (&cfg.fonts.icon_font.family_name, &cfg.fonts.icon_font.fonts);
-                Self::add_font_family(&mut fonts, cfg_family.as_str());
-                for font in cfg_icon_fonts {
-                    Self::add_font(&mut fonts, cfg_family.as_str(), font);
-                }
-                cfg
-            }
-            Err(e) => {
-                log::debug!("Failed to load config: {e}");
-                let def_cfg = Config::default();
-                let (def_cfg_family, def_cfg_text_fonts) = (
-                    &def_cfg.fonts.text_font.family_name,
-                    &def_cfg.fonts.text_font.fonts,
-                );
-                Self::add_font_family(&mut fonts, def_cfg_family.as_str());
-                for font in def_cfg_text_fonts {
-                    Self::add_font(&mut fonts, def_cfg_family.as_str(), font);
-                }
-                let (def_cfg_icon_family, def_cfg_icon_fonts) = (
-                    &def_cfg.fonts.icon_font.family_name,
-                    &def_cfg.fonts.icon_font.fonts,
-                );
-                Self::add_font_family(&mut fonts, def_cfg_icon_family.as_str());
-                for font in def_cfg_icon_fonts {
-                    Self::add_font(&mut fonts, def_cfg_icon_family.as_str(), font);
-                }
-                def_cfg
-            }
-        };
-        cc.egui_ctx.set_fonts(fonts);
-        egui_extras::install_image_loaders(&cc.egui_ctx);
+        let thumbnails = Self::thumbnail_cache(&config);
+        ctx.set_fonts(fonts);
+        egui_extras::install_image_loaders(ctx);
         Self {
+            tx,
             shared,
+            colors: Palette::new(&config.colors),
             config,
-            qtile,
+            live_config,
+            thumbnails,
+            wm: queue,
+            events,
+            shutdown,
+        }
+    }
+
+    /// Whether the host should close, after a `quit` request.
+    pub fn quit_requested(&self) -> bool {
+        self.shutdown.load(Ordering::Relaxed)
+    }
+
+    /// The config as last loaded, for hosts that read it themselves.
+    pub fn live_config(&self) -> LiveConfig {
+        self.live_config.clone()
+    }
+
+    /// A capture cache, if the cards show thumbnails and a provider is configured.
+    fn thumbnail_cache(config: &Config) -> Option<ThumbnailCache> {
+        if !config.ui.items.contains(&crate::config::UiItem::Thumbnail) {
+            return None;
         }
+        let provider = crate::thumbnails::provider(&config.thumbnails);
+        if provider.is_none() {
+            log::warn!("Cards show thumbnails, but thumbnails.command is not set");
+        }
+        provider.map(|provider| {
+            ThumbnailCache::new(
+                provider,
+                std::time::Duration::from_millis(config.thumbnails.refresh_ms),
+            )
+        })
+    }
+
+    /// Switch to a reloaded config. Everything drawn from the config follows
+    /// on the next frame; the backend, key source and socket keep the config
+    /// they started with.
+    fn apply_config(&mut self, ctx: &egui::Context, config: Arc<Config>) {
+        ctx.set_fonts(Self::font_definitions(&config));
+        // Keep the captures unless something that makes them changed.
+        if config.ui.items != self.config.ui.items || config.thumbnails != self.config.thumbnails {
+            if let Some(old) = self.thumbnails.take() {
+                for uri in old.clear() {
+                    ctx.forget_image(&uri);
+                }
+            }
+            self.thumbnails = Self::thumbnail_cache(&config);
+        }
+        self.colors = Palette::new(&config.colors);
+        self.config = config;
+        // Sizes may have changed, so place the overlay again.
+        self.shared.lock().unwrap().last_placed_height = 0.0;
     }
 
     pub fn find_icon(&self, wm_class: &str) -> Option<PathBuf> {
@@ -444,8 +1012,11 @@ impl AsyncApp {
         .interact(Sense::hover())
     }
 
-    pub fn window_icon(&self, ui: &mut Ui, win: &HashMap<String, String>) -> egui::Response {
-        let wm_class = win.get("class").expect("qtile sends correct format");
+    pub fn window_icon(&self, ui: &mut Ui, win: &WindowInfo) -> egui::Response {
+        let wm_class = win.class.as_str();
+        if wm_class.is_empty() {
+            return self.new_image(ui, &self.config.icons.default_icon);
+        }
         let lowercase_wm_class = wm_class.to_lowercase();
         let path = self.find_icon(&lowercase_wm_class);
         match path {
@@ -472,23 +1043,97 @@ impl AsyncApp {
         &self,
         ui: &mut Ui,
         text_font_id: &egui::FontId,
-        win: &HashMap<String, String>,
+        win: &WindowInfo,
     ) -> egui::Response {
-        let name = truncate_window_name(win.get("name").expect("qtile sends correct format"), 31);
+        let name = truncate_window_name(&win.name, 31);
         self.new_label(ui, &name, text_font_id)
     }
 
+    /// The latest preview of `win`, or empty space of the same size until
+    /// the first capture arrives, so cards do not jump.
+    pub fn window_thumbnail(&self, ui: &mut Ui, win: &WindowInfo) {
+        let size = Vec2 {
+            x: self.config.thumbnails.width,
+            y: self.config.thumbnails.height,
+        };
+        match self.thumbnails.as_ref().and_then(|t| t.get(win.id)) {
+            Some(thumbnail) => {
+                ui.add(
+                    Image::from_bytes(thumbnail.uri, egui::load::Bytes::Shared(thumbnail.bytes))
+                        .max_size(size)
+                        .corner_radius(6),
+                );
+            }
+            None => {
+                ui.allocate_space(size);
+            }
+        }
+    }
+
+    /// The label glyph and name of a group, above its cards.
+    fn section_header(
+        &self,
+        ui: &mut Ui,
+        section: &Section<'_>,
+        text_font_id: &egui::FontId,
+        icon_font_id: &egui::FontId,
+    ) {
+        let color = self.colors.text;
+        ui.horizontal(|ui| {
+            if !section.label.is_empty() {
+                ui.label(
+                    egui::RichText::new(section.label)
+                        .font(icon_font_id.clone())
+                        .color(color),
+                );
+            }
+            ui.label(
+                egui::RichText::new(section.name)
+                    .font(text_font_id.clone())
+                    .color(color)
+                    .strong(),
+            );
+        });
+        ui.add_space(self.config.sizes.group_spacing / 2.0);
+    }
+
+    /// `name` with the characters at `positions` underlined in the highlight colour.
+    fn highlighted_name(
+        &self,
+        name: &str,
+        positions: &[usize],
+        font_id: &egui::FontId,
+        color: Color32,
+    ) -> egui::text::LayoutJob {
+        let highlight = self.colors.group_hover;
+        let mut job = egui::text::LayoutJob::default();
+        for (text, matched) in highlight_runs(name, positions) {
+            let format = if matched {
+                egui::TextFormat {
+                    font_id: font_id.clone(),
+                    color: highlight,
+                    underline: Stroke::new(1.0, highlight),
+                    ..Default::default()
+                }
+            } else {
+                egui::TextFormat::simple(font_id.clone(), color)
+            };
+            job.append(&text, 0.0, format);
+        }
+        job
+    }
+
     pub fn render_ui(
         &mut self,
         ui: &mut egui::Ui,
         ctx: &eframe::egui::Context,
-        windows: &[HashMap<String, String>],
+        windows: &[WindowInfo],
+        query: &str,
         is_visible: bool,
         focus_index: usize,
     ) {
         ctx.all_styles_mut(|style| {
-            style.visuals.panel_fill =
-                Color32::from_hex(self.config.colors.bg_color.as_str()).expect("color from hex");
+            style.visuals.panel_fill = self.colors.bg;
         });
 
         let text_font_id = egui::FontId {
@@ -506,24 +1151,22 @@ impl AsyncApp {
         egui::CentralPanel::default().show(ui, |ui| {
             ui.style_mut().visuals.widgets.noninteractive.bg_stroke = Stroke {
                 width: 0.0,
-                color: Color32::from_hex(self.config.colors.text_color.as_str())
-                    .expect("color from hex"),
+                color: self.colors.text,
             };
             ui.style_mut().visuals.widgets.noninteractive.fg_stroke = Stroke {
                 width: 0.0,
-                color: Color32::from_hex(self.config.colors.text_color.as_str())
-                    .expect("color from hex"),
+                color: self.colors.text,
             };
             ui.style_mut().interaction.selectable_labels = false;
 
-            let render_cards = |ui: &mut egui::Ui| {
-                for (index, win) in windows.iter().enumerate() {
+            // Cards are given with their index in `windows`, which is what
+            // `focus_index` and keyboard navigation count in.
+            let render_cards = |ui: &mut egui::Ui, cards: &[(usize, &WindowInfo)]| {
+                for (n, &(index, win)) in cards.iter().enumerate() {
                     let is_selected = index == focus_index;
 
                     let bg_color = if is_selected {
-                        Color32::from_hex(&self.config.colors.group_hover_color)
-                            .unwrap_or(Color32::WHITE)
-                            .gamma_multiply(0.15)
+                        self.colors.group_hover.gamma_multiply(0.15)
                     } else {
                         Color32::TRANSPARENT
                     };
@@ -531,11 +1174,9 @@ impl AsyncApp {
                     let stroke_color = if is_selected
                         || ui.rect_contains_pointer(ui.available_rect_before_wrap())
                     {
-                        Color32::from_hex(&self.config.colors.group_hover_color)
-                            .unwrap_or(Color32::WHITE)
+                        self.colors.group_hover
                     } else {
-                        Color32::from_hex(&self.config.colors.normal_group_color)
-                            .unwrap_or(Color32::GRAY)
+                        self.colors.normal_group
                     };
 
                     let frame = egui::Frame::NONE
@@ -571,10 +1212,7 @@ impl AsyncApp {
                                         match item {
                                             crate::config::UiItem::Icon => {} // Already handled
                                             crate::config::UiItem::Name => {
-                                                let mut name = win
-                                                    .get("name")
-                                                    .unwrap_or(&String::new())
-                                                    .clone();
+                                                let mut name = win.name.clone();
                                                 // Truncate to look clean inside cards
                                                 if name.len() > 35 {
                                                     let upto = name
@@ -585,30 +1223,35 @@ impl AsyncApp {
                                                     name.truncate(upto);
                                                     name.push_str("...");
                                                 }
-                                                let base_color = Color32::from_hex(
-                                                    &self.config.colors.text_color,
-                                                )
-                                                .unwrap_or(Color32::GRAY);
+                                                let base_color = self.colors.text;
                                                 let color = if is_selected {
-                                                    Color32::from_hex(
-                                                        &self.config.colors.group_hover_color,
-                                                    )
-                                                    .unwrap_or(Color32::WHITE)
+                                                    self.colors.group_hover
                                                 } else {
                                                     base_color
                                                 };
-                                                ui.label(
-                                                    egui::RichText::new(name)
-                                                        .font(text_font_id.clone())
-                                                        .color(color)
-                                                        .strong(),
-                                                );
+                                                if query.is_empty() {
+                                                    ui.label(
+                                                        egui::RichText::new(name)
+                                                            .font(text_font_id.clone())
+                                                            .color(color)
+                                                            .strong(),
+                                                    );
+                                                } else {
+                                                    let positions = crate::search::fuzzy_match(
+                                                        query, &win.name,
+                                                    )
+                                                    .map(|m| m.positions)
+                                                    .unwrap_or_default();
+                                                    ui.label(self.highlighted_name(
+                                                        &name,
+                                                        &positions,
+                                                        &text_font_id,
+                                                        color,
+                                                    ));
+                                                }
                                             }
                                             crate::config::UiItem::GroupName => {
-                                                let text = win
-                                                    .get("group_name")
-                                                    .cloned()
-                                                    .unwrap_or_default();
+                                                let text = win.group_name.clone();
                                                 ui.label(
                                                     egui::RichText::new(text)
                                                         .font(egui::FontId::new(
@@ -616,31 +1259,23 @@ impl AsyncApp {
                                                             text_font_id.family.clone(),
                                                         ))
                                                         .color(
-                                                            Color32::from_hex(
-                                                                &self.config.colors.text_color,
-                                                            )
-                                                            .unwrap_or(Color32::GRAY)
-                                                            .gamma_multiply(0.7),
+                                                            self.colors.text.gamma_multiply(0.7),
                                                         ),
                                                 );
                                             }
                                             crate::config::UiItem::GroupLabel => {
-                                                let text = win
-                                                    .get("group_label")
-                                                    .cloned()
-                                                    .unwrap_or_default();
+                                                let text = win.group_label.clone();
                                                 ui.label(
                                                     egui::RichText::new(text)
                                                         .font(icon_font_id.clone())
                                                         .color(
-                                                            Color32::from_hex(
-                                                                &self.config.colors.text_color,
-                                                            )
-                                                            .unwrap_or(Color32::GRAY)
-                                                            .gamma_multiply(0.7),
+                                                            self.colors.text.gamma_multiply(0.7),
                                                         ),
                                                 );
                                             }
+                                            crate::config::UiItem::Thumbnail => {
+                                                self.window_thumbnail(ui, win);
+                                            }
                                         }
                                     }
                                 });
@@ -659,32 +1294,58 @@ impl AsyncApp {
                         let shared = self.shared.clone();
                         let mut state = shared.lock().unwrap();
                         if let Some(wid) = state.cached_wid.clone() {
-                            let qtile_c = Arc::clone(&self.qtile);
-                            tokio::task::spawn_blocking(move || {
-                                let _ = qtile_c.call(
-                                    Some(vec![]),
-                                    Some("eval".into()),
-                                    Some(vec![format!("self.windows_map[{wid}].hide()")]),
-                                );
-                            });
+                            hide_window(&self.wm, wid);
+                        }
+                        if state.is_visible {
+                            publish(&self.events, OverlayEvent::Hidden);
                         }
                         state.is_visible = false;
                         state.current_focus_history = None;
                         state.last_placed_height = 0.0;
                     }
 
-                    if index < windows.len() - 1 {
+                    if n < cards.len() - 1 {
                         ui.add_space(self.config.sizes.group_spacing);
                     }
                 }
             };
-
-            let response = if self.config.ui.orientation == Orientation::Horizontal {
-                ui.horizontal(render_cards).response
-            } else {
-                ui.vertical(render_cards).response
+            let render_row = |ui: &mut egui::Ui, cards: &[(usize, &WindowInfo)]| {
+                if self.config.ui.orientation == Orientation::Horizontal {
+                    ui.horizontal(|ui| render_cards(ui, cards));
+                } else {
+                    ui.vertical(|ui| render_cards(ui, cards));
+                }
             };
 
+            let response = ui
+                .vertical(|ui| {
+                    if !query.is_empty() {
+                        ui.label(
+                            egui::RichText::new(format!("\u{f002}  {query}"))
+                                .font(text_font_id.clone())
+                                .color(self.colors.text),
+                        );
+                        ui.add_space(self.config.sizes.group_spacing);
+                    }
+                    match self.config.ui.layout {
+                        Layout::List => {
+                            let cards: Vec<_> = windows.iter().enumerate().collect();
+                            render_row(ui, &cards);
+                        }
+                        Layout::Grouped => {
+                            let sections = group_sections(windows, self.config.ui.group_order);
+                            for (n, section) in sections.iter().enumerate() {
+                                self.section_header(ui, section, &text_font_id, &icon_font_id);
+                                render_row(ui, &section.windows);
+                                if n < sections.len() - 1 {
+                                    ui.add_space(self.config.sizes.group_spacing * 2.0);
+                                }
+                            }
+                        }
+                    }
+                })
+                .response;
+
             final_width = response.rect.width();
             final_height = response.rect.height();
         });
@@ -712,107 +1373,151 @@ impl AsyncApp {
         }
     }
 
-    pub fn focus_window(&self, win: &HashMap<String, String>) {
-        let wid = win
-            .get("id")
-            .unwrap_or_else(|| panic!("qtile sends correct format {:?}", win.get("id")))
-            .to_string();
-        let qtile = Arc::clone(&self.qtile);
-        tokio::task::spawn_blocking(move || {
-            let _ = qtile.call(
-                Some(vec![]),
-                Some("eval".into()),
-                Some(vec![format!(
-                    "self.windows_map[{}].focus(); self.windows_map[{}].bring_to_front()",
-                    wid, wid
-                )]),
-            );
-        });
+    pub fn focus_window(&self, win: &WindowInfo) {
+        publish(
+            &self.events,
+            OverlayEvent::WindowFocused {
+                window: win.clone(),
+            },
+        );
+        self.wm.push(WmOp::Focus { wid: win.id });
     }
 
     pub fn resize_and_center(&self, width: i32, height: i32) {
-        let mut state = self.shared.lock().unwrap();
-        if (state.last_placed_height - height as f32).abs() < 1.0 {
-            return;
-        }
+        resize_overlay(&self.wm, &self.shared, width, height);
+    }
 
-        if let Some(wid) = state.cached_wid.clone() {
-            log::debug!("Resizing window ({wid}) to {width}x{height}");
-            let qtile = Arc::clone(&self.qtile);
-            tokio::task::spawn_blocking(move || {
-                let _ = qtile.call(
-                    Some(vec![]),
-                    Some("eval".into()),
-                    Some(vec![format!(
-                        "s = self.current_screen; \
-                         x = s.dx + (s.dwidth - {width}) // 2; \
-                         y = s.dy + (s.dheight - {height}) // 2; \
-                         self.windows_map[{wid}].place(x, y, {width}, {height}, 0, None); \
-                         self.windows_map[{wid}].keep_above(); \
-                         self.windows_map[{wid}].bring_to_front()"
-                    )]),
-                );
-            });
-            state.last_placed_height = height as f32;
-            state.last_width = width;
-            state.last_height = height;
+    /// Turn key presses into control commands and typed text into search
+    /// edits for the event processor.
+    fn handle_keys(&self, ctx: &egui::Context) {
+        let app_events: Vec<AppEvent> = ctx.input(|input| {
+            input
+                .events
+                .iter()
+                .filter_map(|event| match event {
+                    egui::Event::Key {
+                        key: egui::Key::Backspace,
+                        pressed: true,
+                        ..
+                    } => Some(AppEvent::Search(SearchEdit::Backspace)),
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } => key_command(*key, *modifiers).map(AppEvent::Control),
+                    egui::Event::Text(text) if !text.chars().any(char::is_control) => {
+                        Some(AppEvent::Search(SearchEdit::Type(text.clone())))
+                    }
+                    _ => None,
+                })
+                .collect()
+        });
+        for event in app_events {
+            if let Err(e) = self.tx.send(event) {
+                log::warn!("Event processor is gone, dropping {:?}", e.0);
+            }
         }
     }
 
-    fn close_window(&self, win: &HashMap<String, String>) {
-        let wid = win.get("id").expect("qtile sends correct format").clone();
-        let qtile = Arc::clone(&self.qtile);
-        tokio::task::spawn_blocking(move || {
-            let _ = qtile.call(
-                Some(vec!["window".to_owned(), wid]),
-                Some("kill".into()),
-                Some(vec![]),
-            );
-        });
+    fn close_window(&self, win: &WindowInfo) {
+        publish(
+            &self.events,
+            OverlayEvent::WindowClosed {
+                window: win.clone(),
+            },
+        );
+        self.shared.lock().unwrap().forget_window(win.id);
+        self.wm.push(WmOp::Kill { wid: win.id });
     }
 }
 
 impl eframe::App for AsyncApp {
     fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        if self.quit_requested() {
+            ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+            return;
+        }
+        self.draw(ui);
+    }
+}
+
+impl AsyncApp {
+    /// Draw one frame of the overlay into `ui`.
+    pub fn draw(&mut self, ui: &mut egui::Ui) {
+        let latest = self.live_config.get();
+        if !Arc::ptr_eq(&latest, &self.config) {
+            self.apply_config(ui.ctx(), latest);
+        }
         let state = self.shared.lock().unwrap();
         let is_visible = state.is_visible;
-        let history = state.current_focus_history.clone();
+        let has_history = state.current_focus_history.is_some();
+        let all_windows = state
+            .current_focus_history
+            .as_ref()
+            .map(|h| h.windows.clone())
+            .unwrap_or_default();
+        let windows = state.visible_windows();
+        let query = state.query.clone();
         let focus_index = state.focus_index;
         drop(state);
 
-        log::debug!(
-            "ui() | visible={} history={}",
-            is_visible,
-            history.is_some()
-        );
+        log::debug!("ui() | visible={} history={}", is_visible, has_history);
         // Always render if we have history — keeps the buffer populated so
         // the window has content ready when place() makes it visible.
-        if let Some(history) = history {
+        if has_history {
             self.render_ui(
                 ui,
                 &ui.ctx().clone(),
-                &history.windows,
+                &windows,
+                &query,
                 is_visible,
                 focus_index,
             );
         }
+        if is_visible && let Some(thumbnails) = &self.thumbnails {
+            let ctx = ui.ctx().clone();
+            // The selected card first, then the ones shown, then the rest.
+            let by_priority: Vec<WindowInfo> = windows
+                .get(focus_index)
+                .into_iter()
+                .chain(&windows)
+                .chain(&all_windows)
+                .cloned()
+                .collect();
+            thumbnails.refresh(&by_priority, move || ctx.request_repaint());
+            for uri in thumbnails.take_replaced() {
+                ui.ctx().forget_image(&uri);
+            }
+        }
         if is_visible {
+            self.handle_keys(ui.ctx());
             ui.ctx().request_repaint();
         }
     }
 }
 
+/// Run the overlay until it is closed or asked to quit, then remove its
+/// socket. The caller holds the [`crate::instance::InstanceLock`].
 pub fn run_ui() -> anyhow::Result<()> {
-    let s = System::new_all();
-    let qalttab_processes_parents = s
-        .processes_by_exact_name("qalttab".as_ref())
-        .map(|p| p.parent());
-    let mut qalttab_processes_vec = qalttab_processes_parents.collect::<Vec<Option<Pid>>>();
-    qalttab_processes_vec.sort();
-    qalttab_processes_vec.dedup();
-    if qalttab_processes_vec.len() >= 4 {
-        bail!("qalttab already running");
+    let config = crate::config::load();
+    let result = match config.wm.overlay {
+        OverlayMode::Window => run_window(config),
+        #[cfg(feature = "layer-shell")]
+        OverlayMode::LayerShell => crate::layer_surface::run(config),
+        #[cfg(not(feature = "layer-shell"))]
+        OverlayMode::LayerShell => {
+            bail!(
+                "wm.overlay is layer_shell, but qalttab was built without the layer-shell feature"
+            )
+        }
     };
+    crate::ipc::remove_socket(None);
+    result
+}
+
+/// Run the overlay as a regular window the window manager hides and places.
+fn run_window(config: Config) -> anyhow::Result<()> {
     match eframe::run_native(
         "qalttab",
         eframe::NativeOptions {
@@ -829,7 +1534,13 @@ pub fn run_ui() -> anyhow::Result<()> {
             },
             ..eframe::NativeOptions::default()
         },
-        Box::new(|cc| Ok(Box::<AsyncApp>::new(AsyncApp::new(cc)))),
+        Box::new(|cc| {
+            Ok(Box::<AsyncApp>::new(AsyncApp::with_config(
+                &cc.egui_ctx,
+                config,
+                crate::wm::backend,
+            )))
+        }),
     ) {
         Ok(()) => Ok(()),
         Err(e) => bail!("eframe crashed: {}", e),
@@ -928,5 +1639,260 @@ mod tests {
         assert!(s.cached_wid.is_none());
         assert_eq!(s.focus_index, 0);
         assert!(s.current_focus_history.is_none());
+        assert!(s.last_windows.is_empty());
+        assert_eq!(s.key_listener, KeyListenerStatus::Starting);
+    }
+
+    #[test]
+    fn client_focus_builds_the_switcher_list_from_focus_history() {
+        let window = |id| WindowInfo {
+            id,
+            ..WindowInfo::default()
+        };
+        let focus = |windows: Vec<u64>, focus_index| Response {
+            message_type: MessageType::ClientFocus,
+            windows: windows.into_iter().map(window).collect(),
+            focus_index,
+        };
+        let ids = |s: &SharedState| s.last_windows.iter().map(|w| w.id).collect::<Vec<_>>();
+
+        let mut s = SharedState::default();
+        s.record_focus(focus(vec![1, 2, 3], None));
+        s.record_focus(focus(vec![1, 2, 3], Some(2)));
+        assert_eq!(ids(&s), vec![3, 1, 2]);
+        let current = s.current_focus_history.as_ref().unwrap();
+        assert_eq!(current.windows, s.last_windows);
+        assert_eq!(current.focus_index, Some(0));
+
+        s.forget_window(1);
+        assert_eq!(ids(&s), vec![3, 2]);
+        assert_eq!(s.focus_history.windows().len(), 2);
+    }
+
+    #[test]
+    fn state_json_reports_degraded_key_listener() {
+        let mut s = SharedState::default();
+        assert_eq!(s.to_json()["degraded"], false);
+        s.key_listener = KeyListenerStatus::Degraded {
+            restarts: 2,
+            last_error: "libinput exited".into(),
+            retry_in_ms: 1000,
+        };
+        let json = s.to_json();
+        assert_eq!(json["degraded"], true);
+        assert_eq!(json["key_listener"]["status"], "degraded");
+        assert_eq!(json["key_listener"]["restarts"], 2);
+    }
+
+    #[test]
+    fn overlay_events_serialize_with_event_tag() {
+        assert_eq!(
+            serde_json::to_value(OverlayEvent::Hidden).unwrap(),
+            serde_json::json!({"event": "hidden"})
+        );
+        let window = WindowInfo {
+            id: 4,
+            name: "Term".into(),
+            ..WindowInfo::default()
+        };
+        let value = serde_json::to_value(OverlayEvent::SelectionChanged {
+            focus_index: 2,
+            window: Some(window.clone()),
+        })
+        .unwrap();
+        assert_eq!(value["event"], "selection_changed");
+        assert_eq!(value["focus_index"], 2);
+        assert_eq!(value["window"]["id"], 4);
+        let value = serde_json::to_value(OverlayEvent::WindowClosed { window }).unwrap();
+        assert_eq!(value["event"], "window_closed");
+        assert_eq!(value["window"]["name"], "Term");
+    }
+
+    #[test]
+    fn show_overlay_publishes_shown_then_selection_changed() {
+        let (wm, _ops) = WmQueue::new();
+        let shared = Arc::new(Mutex::new(SharedState::default()));
+        let (events, mut rx) = broadcast::channel(8);
+        let response = Response {
+            message_type: MessageType::CycleWindows,
+            windows: vec![WindowInfo::default(), WindowInfo::default()],
+            focus_index: Some(0),
+        };
+
+        show_overlay(&wm, &shared, &events, response.clone(), 0);
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(OverlayEvent::Shown { focus_index: 0, .. })
+        ));
+
+        show_overlay(&wm, &shared, &events, response.clone(), 0);
+        assert!(rx.try_recv().is_err());
+
+        show_overlay(&wm, &shared, &events, response, 1);
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(OverlayEvent::SelectionChanged { focus_index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn forgetting_a_listed_window_keeps_the_selection_in_range() {
+        let mut s = SharedState::default();
+        s.current_focus_history = Some(Response {
+            message_type: MessageType::CycleWindows,
+            windows: (1..=3)
+                .map(|id| WindowInfo {
+                    id,
+                    ..WindowInfo::default()
+                })
+                .collect(),
+            focus_index: Some(2),
+        });
+        s.focus_index = 2;
+        s.forget_window(1);
+        assert_eq!(s.selected_window().map(|w| w.id), Some(3));
+        s.forget_window(3);
+        assert_eq!(s.selected_window().map(|w| w.id), Some(2));
+        s.forget_window(2);
+        assert_eq!(s.focus_index, 0);
+        assert!(s.selected_window().is_none());
+    }
+
+    #[test]
+    fn overlay_keys_map_to_control_commands() {
+        use egui::{Key, Modifiers};
+        let none = Modifiers::NONE;
+        assert_eq!(key_command(Key::Tab, none), Some(ControlCommand::Next));
+        assert_eq!(
+            key_command(Key::Tab, Modifiers::SHIFT),
+            Some(ControlCommand::Prev)
+        );
+        assert_eq!(
+            key_command(Key::ArrowDown, none),
+            Some(ControlCommand::Next)
+        );
+        assert_eq!(
+            key_command(Key::ArrowLeft, none),
+            Some(ControlCommand::Prev)
+        );
+        assert_eq!(key_command(Key::Enter, none), Some(ControlCommand::Accept));
+        assert_eq!(key_command(Key::Escape, none), Some(ControlCommand::Hide));
+        assert_eq!(key_command(Key::Delete, none), Some(ControlCommand::Close));
+        assert_eq!(key_command(Key::A, none), None);
+    }
+
+    #[test]
+    fn sections_keep_mru_indices_in_either_order() {
+        let windows: Vec<WindowInfo> = [(1, "10"), (2, "web"), (3, "2"), (4, "10"), (5, "web")]
+            .into_iter()
+            .map(|(id, group)| WindowInfo {
+                id,
+                group_name: group.into(),
+                group_label: format!("<{group}>"),
+                ..WindowInfo::default()
+            })
+            .collect();
+        let layout = |order| {
+            group_sections(&windows, order)
+                .iter()
+                .map(|s| {
+                    let indices: Vec<usize> = s.windows.iter().map(|(i, _)| *i).collect();
+                    (s.name.to_string(), indices)
+                })
+                .collect::<Vec<_>>()
+        };
+        let section = |name: &str, indices: &[usize]| (name.to_string(), indices.to_vec());
+        assert_eq!(
+            layout(GroupOrder::Mru),
+            [
+                section("10", &[0, 3]),
+                section("web", &[1, 4]),
+                section("2", &[2])
+            ]
+        );
+        assert_eq!(
+            layout(GroupOrder::Group),
+            [
+                section("2", &[2]),
+                section("10", &[0, 3]),
+                section("web", &[1, 4])
+            ]
+        );
+        assert_eq!(group_sections(&windows, GroupOrder::Mru)[1].label, "<web>");
+        assert!(group_sections(&[], GroupOrder::Mru).is_empty());
+    }
+
+    #[test]
+    fn highlight_runs_split_matched_characters() {
+        let runs = |text, positions: &[usize]| {
+            highlight_runs(text, positions)
+                .into_iter()
+                .map(|(run, matched)| if matched { run.to_uppercase() } else { run })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(runs("firefox", &[0, 4, 5]), ["F", "ire", "FO", "x"]);
+        assert_eq!(runs("café", &[3, 9]), ["caf", "É"]);
+        assert!(runs("", &[0]).is_empty());
+    }
+
+    #[test]
+    fn query_filters_the_cards_and_the_selection() {
+        let window = |id, name: &str| WindowInfo {
+            id,
+            name: name.into(),
+            ..WindowInfo::default()
+        };
+        let mut s = SharedState::default();
+        s.current_focus_history = Some(Response {
+            message_type: MessageType::CycleWindows,
+            windows: vec![window(1, "firefox"), window(2, "foot"), window(3, "mpv")],
+            focus_index: Some(0),
+        });
+        s.query = "fo".into();
+        let ids: Vec<u64> = s.visible_windows().iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![2, 1]);
+        s.focus_index = 1;
+        assert_eq!(s.selected_window().map(|w| w.id), Some(1));
+        s.forget_window(2);
+        assert_eq!(s.focus_index, 0);
+        assert_eq!(s.selected_window().map(|w| w.id), Some(1));
+    }
+
+    #[test]
+    fn next_and_prev_wrap_while_visible() {
+        assert_eq!(control_focus_index(ControlCommand::Next, true, 2, 3), 0);
+        assert_eq!(control_focus_index(ControlCommand::Next, true, 0, 3), 1);
+        assert_eq!(control_focus_index(ControlCommand::Prev, true, 0, 3), 2);
+        assert_eq!(control_focus_index(ControlCommand::Prev, true, 2, 3), 1);
+    }
+
+    #[test]
+    fn next_and_prev_open_on_neighbour_of_focused_window() {
+        assert_eq!(control_focus_index(ControlCommand::Next, false, 5, 3), 1);
+        assert_eq!(control_focus_index(ControlCommand::Prev, false, 5, 3), 2);
+        assert_eq!(control_focus_index(ControlCommand::Next, false, 0, 1), 0);
+    }
+
+    #[test]
+    fn select_and_show_clamp_to_list() {
+        assert_eq!(
+            control_focus_index(ControlCommand::Select(7), true, 0, 3),
+            2
+        );
+        assert_eq!(control_focus_index(ControlCommand::Show, true, 9, 3), 2);
+        assert_eq!(control_focus_index(ControlCommand::Show, false, 2, 3), 0);
+        assert_eq!(control_focus_index(ControlCommand::Next, true, 0, 0), 0);
+    }
+
+    #[test]
+    fn palette_falls_back_to_default_colours() {
+        let colors = Colors {
+            bg_color: "#000000".into(),
+            text_color: "not a colour".into(),
+            ..Colors::default()
+        };
+        let palette = Palette::new(&colors);
+        assert_eq!(palette.bg, Color32::BLACK);
+        assert_eq!(palette.text, Color32::from_rgb(0x6C, 0x70, 0x86));
     }
 }
@@ -0,0 +1,399 @@
+//! Window-manager backends.
+//!
+//! The overlay only needs a handful of operations from the window manager:
+//! show, hide and place its own window, focus or close another one, and find
+//! its own window id. Each backend implements [`WindowManager`]; every
+//! operation goes through one [`WmQueue`], so they reach the window manager
+//! in the order they were made.
+
+use std::fmt::{self, Display};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::config::{Config, WmBackend};
+use crate::hyprland::HyprlandBackend;
+use crate::qtile::QtileBackend;
+use crate::sway::SwayBackend;
+use crate::ui::{AppEvent, IccQtileClient, MessageType, OverlayEvent, Response, WindowInfo};
+
+pub type WmFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// Title and app id of the overlay window, used to find it.
+pub const OVERLAY_NAME: &str = "qalttab";
+
+/// One step queued on a [`WmQueue`]. Steps made of several calls run as a unit.
+///
+/// `selected` is the selected window, for the `selection` placement policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WmOp {
+    /// Unhide our window and place it with the given size.
+    Show {
+        wid: String,
+        width: i32,
+        height: i32,
+        selected: Option<u64>,
+    },
+    Hide {
+        wid: String,
+    },
+    /// Resize our window and place it again.
+    Place {
+        wid: String,
+        width: i32,
+        height: i32,
+        selected: Option<u64>,
+    },
+    /// The selection moved to `selected`: move our window to its screen
+    /// when placing by selection, do nothing otherwise.
+    Follow {
+        wid: String,
+        width: i32,
+        height: i32,
+        selected: u64,
+    },
+    /// Focus another client and raise it.
+    Focus {
+        wid: u64,
+    },
+    Kill {
+        wid: u64,
+    },
+    /// The modifier was released with `wid` selected.
+    Commit {
+        wid: Option<u64>,
+    },
+}
+
+impl Display for WmOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Show {
+                wid, width, height, ..
+            } => {
+                write!(f, "show overlay {wid} at {width}x{height}")
+            }
+            Self::Hide { wid } => write!(f, "hide overlay {wid}"),
+            Self::Place {
+                wid, width, height, ..
+            } => {
+                write!(f, "resize overlay {wid} to {width}x{height}")
+            }
+            Self::Follow { wid, selected, .. } => {
+                write!(f, "move overlay {wid} to the screen of window {selected}")
+            }
+            Self::Focus { wid } => write!(f, "focus window {wid}"),
+            Self::Kill { wid } => write!(f, "close window {wid}"),
+            Self::Commit { wid: Some(wid) } => write!(f, "commit selection of window {wid}"),
+            Self::Commit { wid: None } => write!(f, "commit selection"),
+        }
+    }
+}
+
+/// A window manager the overlay can drive.
+pub trait WindowManager: Send + Sync {
+    /// Short name used in logs.
+    fn name(&self) -> &'static str;
+
+    fn run(&self, op: WmOp) -> WmFuture<'_, ()>;
+
+    /// Id of the first window called `name`, if the window manager knows one.
+    fn find_window<'a>(&'a self, name: &'a str) -> WmFuture<'a, Option<u64>>;
+
+    /// Id of the overlay when the backend owns it and needs no
+    /// [`find_window`](Self::find_window) to know it.
+    fn overlay_id(&self) -> Option<u64> {
+        None
+    }
+
+    /// Send the window list to the app whenever focus changes, until the
+    /// connection to the window manager ends.
+    ///
+    /// Backends whose focus changes arrive some other way, like Qtile's hook
+    /// writing to the IPC socket, keep this default and return immediately.
+    fn watch_focus(&self, tx: UnboundedSender<AppEvent>) -> WmFuture<'_, ()> {
+        drop(tx);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Pick the backend for `wm.backend`, placing the overlay by `placement`.
+///
+/// `auto` uses Hyprland when `$HYPRLAND_INSTANCE_SIGNATURE` is set, sway
+/// when `$SWAYSOCK` is set and Qtile otherwise.
+pub fn backend(config: &Config) -> Arc<dyn WindowManager> {
+    let placement = &config.placement;
+    let qtile = || -> Arc<dyn WindowManager> {
+        Arc::new(QtileBackend::new(Arc::new(IccQtileClient)).with_placement(placement.clone()))
+    };
+    let or_qtile = |name: &str, backend: anyhow::Result<Arc<dyn WindowManager>>| {
+        backend.unwrap_or_else(|e| {
+            log::warn!("Cannot use {name} ({e:#}), falling back to Qtile");
+            qtile()
+        })
+    };
+    let sway = || {
+        let backend = SwayBackend::from_env().map(|b| b.with_placement(placement.clone()));
+        or_qtile("sway", backend.map(|b| Arc::new(b) as _))
+    };
+    let hyprland = || {
+        let backend = HyprlandBackend::from_env().map(|b| b.with_placement(placement.clone()));
+        or_qtile("Hyprland", backend.map(|b| Arc::new(b) as _))
+    };
+    match config.wm.backend {
+        WmBackend::Qtile => qtile(),
+        WmBackend::Sway => sway(),
+        WmBackend::Hyprland => hyprland(),
+        WmBackend::Auto if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() => hyprland(),
+        WmBackend::Auto if std::env::var_os("SWAYSOCK").is_some() => sway(),
+        WmBackend::Auto => qtile(),
+    }
+}
+
+/// Ordered queue of window-manager operations, run one at a time by a single worker.
+///
+/// Cloning the queue shares the worker, so a hide queued by the event
+/// processor can never be overtaken by a resize queued from the render loop.
+#[derive(Clone, Debug)]
+pub struct WmQueue {
+    tx: UnboundedSender<WmOp>,
+}
+
+impl WmQueue {
+    /// A queue and the receiving end of its operations, for [`run_queue`] or for tests.
+    pub fn new() -> (Self, UnboundedReceiver<WmOp>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+
+    /// Create a queue and spawn its worker on the current tokio runtime.
+    pub fn start(wm: Arc<dyn WindowManager>, events: broadcast::Sender<OverlayEvent>) -> Self {
+        let (queue, rx) = Self::new();
+        tokio::spawn(run_queue(wm, events, rx));
+        queue
+    }
+
+    pub fn push(&self, op: WmOp) {
+        log::debug!("Queueing window manager operation: {op}");
+        if self.tx.send(op).is_err() {
+            log::warn!("Window manager queue worker is gone, dropping operation");
+        }
+    }
+}
+
+/// Run queued operations in order until every [`WmQueue`] handle is dropped.
+///
+/// A failed operation is logged and published as [`OverlayEvent::CommandFailed`];
+/// the next one still runs.
+pub async fn run_queue(
+    wm: Arc<dyn WindowManager>,
+    events: broadcast::Sender<OverlayEvent>,
+    mut rx: UnboundedReceiver<WmOp>,
+) {
+    while let Some(op) = rx.recv().await {
+        if let Err(e) = wm.run(op.clone()).await {
+            log::warn!("Failed to {op} with {}: {e:#}", wm.name());
+            let event = OverlayEvent::CommandFailed {
+                operation: op.to_string(),
+                error: format!("{e:#}"),
+            };
+            if events.send(event).is_err() {
+                log::debug!("No subscriber for command failure");
+            }
+        }
+    }
+}
+
+/// Windows ordered from most to least recently focused.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FocusHistory {
+    windows: Vec<WindowInfo>,
+}
+
+impl FocusHistory {
+    /// Move `window` to the front, adding it if it is new.
+    pub fn focus(&mut self, window: WindowInfo) {
+        self.remove(window.id);
+        self.windows.insert(0, window);
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<WindowInfo> {
+        let index = self.windows.iter().position(|w| w.id == id)?;
+        Some(self.windows.remove(index))
+    }
+
+    /// Replace the known windows with `current`, keeping the focus order.
+    /// Windows that are gone are pruned; new ones go to the back.
+    pub fn sync(&mut self, mut current: Vec<WindowInfo>) {
+        let rank = |id| {
+            self.windows
+                .iter()
+                .position(|w: &WindowInfo| w.id == id)
+                .unwrap_or(usize::MAX)
+        };
+        // Stable, so new windows stay in the order they were given.
+        current.sort_by_key(|w| rank(w.id));
+        self.windows = current;
+    }
+
+    /// Take in a `client_focus` window list: `windows` are all open windows
+    /// and the one at `focused` has just been focused. Our own order wins
+    /// over the order of the list.
+    pub fn observe(&mut self, windows: Vec<WindowInfo>, focused: usize) {
+        let focused = windows.get(focused).cloned();
+        self.sync(windows);
+        if let Some(window) = focused {
+            self.focus(window);
+        }
+    }
+
+    pub fn windows(&self) -> &[WindowInfo] {
+        &self.windows
+    }
+}
+
+/// Sends a backend's [`FocusHistory`] to the app as `client_focus` messages.
+pub struct FocusReporter {
+    tx: UnboundedSender<AppEvent>,
+    sent: Option<Vec<u64>>,
+}
+
+impl FocusReporter {
+    pub fn new(tx: UnboundedSender<AppEvent>) -> Self {
+        Self { tx, sent: None }
+    }
+
+    /// Send `history` if its order or set of windows changed since the last
+    /// report. A title change alone would close a running cycle, so it is
+    /// not sent. Returns `false` once the app is gone.
+    pub fn report(&mut self, history: &FocusHistory) -> bool {
+        let ids: Vec<u64> = history.windows().iter().map(|w| w.id).collect();
+        if self.sent.as_ref() == Some(&ids) {
+            return true;
+        }
+        let response = Response {
+            message_type: MessageType::ClientFocus,
+            windows: history.windows().to_vec(),
+            focus_index: Some(0),
+        };
+        self.sent = Some(ids);
+        self.tx.send(AppEvent::UnixSocketMsg(response)).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(id: u64) -> WindowInfo {
+        WindowInfo {
+            id,
+            name: format!("win{id}"),
+            ..WindowInfo::default()
+        }
+    }
+
+    fn ids(history: &FocusHistory) -> Vec<u64> {
+        history.windows().iter().map(|w| w.id).collect()
+    }
+
+    #[test]
+    fn focus_moves_window_to_front() {
+        let mut history = FocusHistory::default();
+        for id in [1, 2, 3] {
+            history.focus(window(id));
+        }
+        assert_eq!(ids(&history), vec![3, 2, 1]);
+        history.focus(window(1));
+        assert_eq!(ids(&history), vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn remove_prunes_closed_window() {
+        let mut history = FocusHistory::default();
+        history.focus(window(1));
+        history.focus(window(2));
+        assert_eq!(history.remove(1).map(|w| w.id), Some(1));
+        assert!(history.remove(1).is_none());
+        assert_eq!(ids(&history), vec![2]);
+    }
+
+    #[test]
+    fn sync_keeps_order_prunes_and_appends() {
+        let mut history = FocusHistory::default();
+        for id in [1, 2, 3] {
+            history.focus(window(id));
+        }
+        let mut renamed = window(1);
+        renamed.name = "renamed".into();
+        history.sync(vec![renamed, window(4), window(3)]);
+        assert_eq!(ids(&history), vec![3, 1, 4]);
+        assert_eq!(history.windows()[1].name, "renamed");
+    }
+
+    #[test]
+    fn observe_seeds_then_keeps_its_own_order() {
+        let mut history = FocusHistory::default();
+        history.observe(vec![window(1), window(2), window(3)], 0);
+        assert_eq!(ids(&history), vec![1, 2, 3]);
+        // A list in some other order only moves the focused window.
+        history.observe(vec![window(3), window(2), window(1)], 1);
+        assert_eq!(ids(&history), vec![2, 1, 3]);
+        history.observe(vec![window(1), window(3)], 0);
+        assert_eq!(ids(&history), vec![1, 3]);
+        history.observe(vec![], 0);
+        assert!(history.windows().is_empty());
+    }
+
+    #[test]
+    fn reporter_skips_unchanged_order() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut reporter = FocusReporter::new(tx);
+        let mut history = FocusHistory::default();
+        history.focus(window(1));
+        history.focus(window(2));
+        assert!(reporter.report(&history));
+        assert!(matches!(rx.try_recv(), Ok(AppEvent::UnixSocketMsg(_))));
+
+        let mut renamed = window(2);
+        renamed.name = "renamed".into();
+        history.focus(renamed);
+        assert!(reporter.report(&history));
+        assert!(rx.try_recv().is_err());
+
+        drop(rx);
+        history.focus(window(1));
+        assert!(!reporter.report(&history));
+    }
+
+    #[test]
+    fn ops_display_for_logs() {
+        assert_eq!(
+            WmOp::Commit { wid: Some(7) }.to_string(),
+            "commit selection of window 7"
+        );
+        assert_eq!(
+            WmOp::Show {
+                wid: "42".into(),
+                width: 300,
+                height: 400,
+                selected: None,
+            }
+            .to_string(),
+            "show overlay 42 at 300x400"
+        );
+        assert_eq!(
+            WmOp::Follow {
+                wid: "42".into(),
+                width: 300,
+                height: 400,
+                selected: 7,
+            }
+            .to_string(),
+            "move overlay 42 to the screen of window 7"
+        );
+    }
+}
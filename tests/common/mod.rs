@@ -0,0 +1,205 @@
+//! Test support shared by the integration tests.
+
+// Each test binary uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use qalttab::qtile::{QtileBackend, QtileCommand};
+use qalttab::ui::{
+    AppEvent, MessageType, OverlayEvent, QtileCallFuture, QtileClientTrait, Response, SharedState,
+    WindowInfo, discover_window_id, process_events,
+};
+use qalttab::wm::WmQueue;
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+
+/// Id of the qalttab window in the default `windows()` reply.
+pub const OVERLAY_WID: u64 = 42;
+
+/// Stands in for Qtile: records every call as `object.function(args)` and
+/// answers from scripted replies, falling back to a fixed screen and window list.
+#[derive(Default)]
+pub struct MockQtileClient {
+    calls: Mutex<Vec<String>>,
+    scripted: Mutex<HashMap<String, VecDeque<Result<Value, String>>>>,
+}
+
+impl MockQtileClient {
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Answer the next call to `function` with `reply`. Replies for the same
+    /// function are used in the order they were scripted.
+    pub fn reply_once(&self, function: &str, reply: Value) {
+        self.script(function, Ok(reply));
+    }
+
+    /// Fail the next call to `function` with `error`.
+    pub fn fail_once(&self, function: &str, error: &str) {
+        self.script(function, Err(error.to_owned()));
+    }
+
+    fn script(&self, function: &str, reply: Result<Value, String>) {
+        self.scripted
+            .lock()
+            .unwrap()
+            .entry(function.to_owned())
+            .or_default()
+            .push_back(reply);
+    }
+
+    /// Wait until the recorded calls satisfy `done`.
+    pub async fn wait_for_calls(&self, done: impl Fn(&[String]) -> bool) -> Vec<String> {
+        for _ in 0..100 {
+            let calls = self.calls();
+            if done(&calls) {
+                return calls;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("calls never completed: {:?}", self.calls());
+    }
+
+    fn default_reply(function: &str) -> Value {
+        match function {
+            "info" => json!({"index": 0, "x": 0, "y": 0, "width": 1920, "height": 1080}),
+            "windows" => json!([
+                {"id": 7, "name": "firefox", "wm_class": ["Navigator", "firefox"]},
+                {"id": OVERLAY_WID, "name": "qalttab", "wm_class": ["qalttab", "qalttab"]},
+            ]),
+            _ => Value::Null,
+        }
+    }
+}
+
+impl QtileClientTrait for MockQtileClient {
+    fn call(&self, command: QtileCommand) -> QtileCallFuture<'_> {
+        Box::pin(async move {
+            self.calls.lock().unwrap().push(command.to_string());
+            let scripted = self
+                .scripted
+                .lock()
+                .unwrap()
+                .get_mut(&command.function)
+                .and_then(VecDeque::pop_front);
+            match scripted {
+                Some(Ok(reply)) => Ok(reply),
+                Some(Err(error)) => Err(anyhow::anyhow!(error)),
+                None => Ok(Self::default_reply(&command.function)),
+            }
+        })
+    }
+}
+
+/// The event processor on a [`MockQtileClient`], with handles to drive it.
+pub struct Harness {
+    pub client: Arc<MockQtileClient>,
+    pub queue: WmQueue,
+    pub shared: Arc<Mutex<SharedState>>,
+    pub events: broadcast::Receiver<OverlayEvent>,
+    pub tx: mpsc::UnboundedSender<AppEvent>,
+}
+
+/// Whether [`start`] looks for our window first, like `AsyncApp` does.
+pub enum Discovery {
+    Run,
+    /// Start with [`OVERLAY_WID`] already known.
+    Skip,
+}
+
+/// Run the event processor, after WID discovery if `discovery` asks for it.
+pub fn start(client: MockQtileClient, discovery: Discovery) -> Harness {
+    let client = Arc::new(client);
+    let shared = Arc::new(Mutex::new(SharedState::default()));
+    let (events_tx, events) = broadcast::channel(16);
+    let (tx, rx) = mpsc::unbounded_channel();
+    let wm = Arc::new(QtileBackend::new(client.clone()));
+    let queue = WmQueue::start(wm.clone(), events_tx.clone());
+    let (queue_bg, shared_bg) = (queue.clone(), shared.clone());
+    tokio::spawn(async move {
+        match discovery {
+            Discovery::Run => discover_window_id(wm.as_ref(), &queue_bg, &shared_bg).await,
+            Discovery::Skip => {
+                shared_bg.lock().unwrap().cached_wid = Some(OVERLAY_WID.to_string());
+            }
+        }
+        process_events(queue_bg, shared_bg, events_tx, egui::Context::default(), rx).await;
+    });
+    Harness {
+        client,
+        queue,
+        shared,
+        events,
+        tx,
+    }
+}
+
+/// A `cycle_windows` message for windows 1 to 3, named `win1` to `win3`.
+pub fn cycle(focus_index: usize) -> AppEvent {
+    AppEvent::UnixSocketMsg(Response {
+        message_type: MessageType::CycleWindows,
+        windows: (1..=3)
+            .map(|id| WindowInfo {
+                id,
+                name: format!("win{id}"),
+                ..WindowInfo::default()
+            })
+            .collect(),
+        focus_index: Some(focus_index),
+    })
+}
+
+pub async fn next_event(events: &mut broadcast::Receiver<OverlayEvent>) -> OverlayEvent {
+    tokio::time::timeout(Duration::from_secs(2), events.recv())
+        .await
+        .expect("timed out waiting for overlay event")
+        .unwrap()
+}
+
+/// A fresh path in the temp dir for a fake window manager's socket(s).
+/// The pid keeps concurrent runs of the suite apart.
+pub fn fake_socket_path(backend: &str, test: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "qalttab_fake_{backend}_{test}_{}",
+        std::process::id()
+    ));
+    std::fs::remove_file(&path).ok();
+    std::fs::remove_dir_all(&path).ok();
+    path
+}
+
+/// Accept connections on `listener` forever, serving each on its own task.
+pub fn serve<F, Fut>(listener: UnixListener, handle: F)
+where
+    F: Fn(UnixStream) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(handle(stream));
+        }
+    });
+}
+
+/// The window ids of the next `client_focus` list a backend sends, which
+/// must have the focused window first.
+pub async fn next_window_list(rx: &mut mpsc::UnboundedReceiver<AppEvent>) -> Vec<u64> {
+    let event = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+        .await
+        .expect("timed out waiting for a window list")
+        .unwrap();
+    match event {
+        AppEvent::UnixSocketMsg(response) => {
+            assert_eq!(response.message_type, MessageType::ClientFocus);
+            assert_eq!(response.focus_index, Some(0));
+            response.windows.iter().map(|w| w.id).collect()
+        }
+        other => panic!("expected a window list, got {other:?}"),
+    }
+}
@@ -0,0 +1,110 @@
+use qalttab::args::CtlAction;
+use qalttab::config::LiveConfig;
+use qalttab::ctl::{request_for, send_request};
+use qalttab::ipc::{DEFAULT_MAX_FRAME_SIZE, IpcContext, listen};
+use qalttab::ui::{AppEvent, ControlCommand, SharedState, WindowInfo};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Start a server on `socket_path` and return its event receiver and shared state.
+async fn start_server(
+    socket_path: &str,
+) -> (mpsc::UnboundedReceiver<AppEvent>, Arc<Mutex<SharedState>>) {
+    let (tx, rx) = mpsc::unbounded_channel::<AppEvent>();
+    let path = Path::new(socket_path);
+    if path.exists() {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let shared = Arc::new(Mutex::new(SharedState::default()));
+    let ipc = IpcContext::new(
+        tx,
+        egui::Context::default(),
+        shared.clone(),
+        LiveConfig::default(),
+    );
+    let path_clone = path.to_owned();
+    tokio::spawn(async move {
+        let _ = listen(ipc, Some(&path_clone), DEFAULT_MAX_FRAME_SIZE).await;
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    (rx, shared)
+}
+
+#[tokio::test]
+async fn ctl_next_sends_control_event() {
+    let socket_path = "/tmp/q_ctl_next.sock";
+    let (mut rx, _shared) = start_server(socket_path).await;
+
+    let result = send_request(&request_for(CtlAction::Next), Some(Path::new(socket_path)))
+        .await
+        .unwrap();
+    assert!(result.is_none());
+
+    let event = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+        .await
+        .expect("timed out waiting for event");
+    assert!(matches!(
+        event,
+        Some(AppEvent::Control(ControlCommand::Next))
+    ));
+}
+
+#[tokio::test]
+async fn ctl_select_out_of_range_fails() {
+    let socket_path = "/tmp/q_ctl_select.sock";
+    let (mut rx, _shared) = start_server(socket_path).await;
+
+    let e = send_request(
+        &request_for(CtlAction::Select { index: 2 }),
+        Some(Path::new(socket_path)),
+    )
+    .await
+    .unwrap_err();
+    assert!(e.to_string().contains("invalid_argument"), "got: {e}");
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn ctl_state_returns_shared_state() {
+    let socket_path = "/tmp/q_ctl_state.sock";
+    let (_rx, shared) = start_server(socket_path).await;
+    {
+        let mut state = shared.lock().unwrap();
+        state.focus_index = 1;
+        state.last_windows = vec![
+            WindowInfo {
+                id: 1,
+                name: "a".into(),
+                ..WindowInfo::default()
+            },
+            WindowInfo {
+                id: 2,
+                name: "b".into(),
+                ..WindowInfo::default()
+            },
+        ];
+    }
+
+    let state = send_request(&request_for(CtlAction::State), Some(Path::new(socket_path)))
+        .await
+        .unwrap()
+        .expect("state result");
+    assert_eq!(state["visible"], false);
+    assert_eq!(state["focus_index"], 1);
+    assert_eq!(state["windows"].as_array().unwrap().len(), 2);
+    assert_eq!(state["windows"][1]["name"], "b");
+}
+
+#[tokio::test]
+async fn ctl_fails_when_no_instance_is_running() {
+    let socket_path = "/tmp/q_ctl_missing.sock";
+    let _ = std::fs::remove_file(socket_path);
+    let e = send_request(&request_for(CtlAction::Show), Some(Path::new(socket_path)))
+        .await
+        .unwrap_err();
+    assert!(e.to_string().contains("is qalttab running"), "got: {e}");
+}
@@ -21,7 +21,7 @@ fn cycle_windows_event_carries_correct_data() {
     if let AppEvent::UnixSocketMsg(r) = event {
         assert_eq!(r.message_type, MessageType::CycleWindows);
         assert_eq!(r.windows.len(), 2);
-        assert_eq!(r.windows[0].get("id").unwrap(), "1");
+        assert_eq!(r.windows[0].id, 1);
     } else {
         panic!("expected UnixSocketMsg");
     }
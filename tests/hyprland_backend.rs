@@ -0,0 +1,275 @@
+mod common;
+
+use common::next_window_list;
+use qalttab::config::{PlacementConfig, ScreenPolicy};
+use qalttab::hyprland::HyprlandBackend;
+use qalttab::ui::OverlayEvent;
+use qalttab::wm::{WindowManager, WmOp, WmQueue};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixListener;
+use tokio::sync::{broadcast, mpsc};
+
+/// Answers requests on `.socket.sock` from scripted clients and records
+/// dispatch batches; any dispatch naming window `0x99` fails. Lines sent
+/// with [`FakeHyprland::event`] go out on `.socket2.sock`. There are two
+/// monitors side by side, the left one focused, and the cursor is on the
+/// right one.
+struct FakeHyprland {
+    dir: PathBuf,
+    clients: Arc<Mutex<Value>>,
+    requests: Arc<Mutex<Vec<String>>>,
+    events: broadcast::Sender<String>,
+}
+
+impl FakeHyprland {
+    fn start(name: &str, clients: Value) -> Self {
+        let dir = common::fake_socket_path("hypr", name);
+        std::fs::create_dir_all(&dir).unwrap();
+        let fake = Self {
+            dir,
+            clients: Arc::new(Mutex::new(clients)),
+            requests: Arc::default(),
+            events: broadcast::channel(16).0,
+        };
+
+        let commands = UnixListener::bind(fake.dir.join(".socket.sock")).unwrap();
+        let (clients, requests) = (fake.clients.clone(), fake.requests.clone());
+        common::serve(commands, move |mut stream| {
+            let (clients, requests) = (clients.clone(), requests.clone());
+            async move {
+                let mut buf = vec![0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).into_owned();
+                let reply = match request.as_str() {
+                    "j/clients" => clients.lock().unwrap().to_string(),
+                    "j/monitors" => json!([
+                        {
+                            "id": 0, "x": 0, "y": 0, "width": 1920, "height": 1080,
+                            "scale": 1.0, "focused": true,
+                            "activeWorkspace": {"id": 2, "name": "2"},
+                        },
+                        {
+                            "id": 1, "x": 1920, "y": 0, "width": 2560, "height": 1440,
+                            "scale": 1.0, "focused": false,
+                            "activeWorkspace": {"id": 5, "name": "5"},
+                        },
+                    ])
+                    .to_string(),
+                    "j/cursorpos" => json!({"x": 2500, "y": 700}).to_string(),
+                    batch => {
+                        requests.lock().unwrap().push(batch.to_string());
+                        batch
+                            .split(';')
+                            .map(|d| {
+                                if d.contains("0x99") {
+                                    "Window not found"
+                                } else {
+                                    "ok"
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n\n")
+                    }
+                };
+                stream.write_all(reply.as_bytes()).await.ok();
+            }
+        });
+
+        let event_socket = UnixListener::bind(fake.dir.join(".socket2.sock")).unwrap();
+        let events = fake.events.clone();
+        common::serve(event_socket, move |mut stream| {
+            let mut events = events.subscribe();
+            async move {
+                while let Ok(line) = events.recv().await {
+                    if stream
+                        .write_all(format!("{line}\n").as_bytes())
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        });
+        fake
+    }
+
+    fn backend(&self) -> Arc<HyprlandBackend> {
+        self.backend_with(PlacementConfig::default())
+    }
+
+    fn backend_with(&self, placement: PlacementConfig) -> Arc<HyprlandBackend> {
+        Arc::new(HyprlandBackend::new(self.dir.clone()).with_placement(placement))
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Replace the clients, then send `line` like Hyprland would.
+    fn event(&self, line: &str, clients: Value) {
+        *self.clients.lock().unwrap() = clients;
+        self.events.send(line.to_string()).unwrap();
+    }
+}
+
+impl Drop for FakeHyprland {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+/// `windows` as `(address, title)` in focus order, each 1920 px right of
+/// the one before, plus the overlay at `0xff` on its special workspace.
+fn clients(windows: &[(u64, &str)]) -> Value {
+    let mut clients: Vec<Value> = windows
+        .iter()
+        .enumerate()
+        .map(|(rank, (address, title))| {
+            json!({
+                "address": format!("0x{address:x}"),
+                "title": title,
+                "class": title,
+                "workspace": {"id": 1, "name": "1"},
+                "focusHistoryID": rank,
+                "at": [1920 * rank, 0],
+                "size": [800, 600],
+            })
+        })
+        .collect();
+    clients.push(json!({
+        "address": "0xff",
+        "title": "qalttab",
+        "class": "qalttab",
+        "workspace": {"id": -98, "name": "special:qalttab"},
+        "focusHistoryID": windows.len(),
+    }));
+    Value::Array(clients)
+}
+
+#[tokio::test]
+async fn finds_the_overlay_by_title() {
+    let hypr = FakeHyprland::start("find", clients(&[(0xa, "foot")]));
+    let backend = hypr.backend();
+    assert_eq!(backend.find_window("qalttab").await.unwrap(), Some(0xff));
+    assert_eq!(backend.find_window("missing").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn queued_ops_become_dispatch_batches_and_failures_are_published() {
+    let hypr = FakeHyprland::start("dispatch", clients(&[(0xa, "foot")]));
+    let (events, mut failures) = broadcast::channel(8);
+    let queue = WmQueue::start(hypr.backend(), events);
+    queue.push(WmOp::Show {
+        wid: "255".into(),
+        width: 300,
+        height: 400,
+        selected: None,
+    });
+    queue.push(WmOp::Hide { wid: "255".into() });
+    queue.push(WmOp::Commit { wid: Some(0x99) });
+    queue.push(WmOp::Kill { wid: 0xa });
+
+    match tokio::time::timeout(Duration::from_secs(2), failures.recv())
+        .await
+        .unwrap()
+        .unwrap()
+    {
+        OverlayEvent::CommandFailed { operation, error } => {
+            assert_eq!(operation, "commit selection of window 153");
+            assert!(error.contains("Window not found"), "{error}");
+        }
+        other => panic!("expected command_failed, got {other:?}"),
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(
+        hypr.requests(),
+        vec![
+            "[[BATCH]]dispatch movetoworkspacesilent 2,address:0xff;\
+             dispatch setfloating address:0xff;\
+             dispatch resizewindowpixel exact 300 400,address:0xff;\
+             dispatch movewindowpixel exact 810 340,address:0xff;\
+             dispatch focuswindow address:0xff",
+            "[[BATCH]]dispatch movetoworkspacesilent special:qalttab,address:0xff",
+            "[[BATCH]]dispatch focuswindow address:0x99",
+            "[[BATCH]]dispatch closewindow address:0xa",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn placement_picks_the_pointer_or_selection_monitor() {
+    let hypr = FakeHyprland::start("placement", clients(&[(0xa, "foot"), (0xb, "mpv")]));
+    let placement = |screen| PlacementConfig {
+        screen,
+        ..PlacementConfig::default()
+    };
+    let show = WmOp::Show {
+        wid: "255".into(),
+        width: 300,
+        height: 400,
+        selected: Some(0xa),
+    };
+    let follow = |selected| WmOp::Follow {
+        wid: "255".into(),
+        width: 300,
+        height: 400,
+        selected,
+    };
+
+    let pointer = hypr.backend_with(placement(ScreenPolicy::Pointer));
+    pointer.run(show.clone()).await.unwrap();
+    // Only the selection policy follows the selection.
+    pointer.run(follow(0xb)).await.unwrap();
+    let selection = hypr.backend_with(placement(ScreenPolicy::Selection));
+    selection.run(show).await.unwrap();
+    selection.run(follow(0xb)).await.unwrap();
+
+    let on = |workspace: u32, x: u32, y: u32| {
+        format!(
+            "[[BATCH]]dispatch movetoworkspacesilent {workspace},address:0xff;\
+             dispatch setfloating address:0xff;\
+             dispatch resizewindowpixel exact 300 400,address:0xff;\
+             dispatch movewindowpixel exact {x} {y},address:0xff;\
+             dispatch focuswindow address:0xff"
+        )
+    };
+    assert_eq!(
+        hypr.requests(),
+        vec![on(5, 3050, 520), on(2, 810, 340), on(5, 3050, 520)]
+    );
+}
+
+#[tokio::test]
+async fn focus_events_build_the_mru_list() {
+    let hypr = FakeHyprland::start(
+        "watch",
+        clients(&[(0xb, "firefox"), (0xa, "foot"), (0xc, "mpv")]),
+    );
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let backend = hypr.backend();
+    tokio::spawn(async move { backend.watch_focus(tx).await });
+
+    // Seeded from Hyprland's focus history, without the overlay.
+    assert_eq!(next_window_list(&mut rx).await, vec![0xb, 0xa, 0xc]);
+
+    let all = clients(&[(0xb, "firefox"), (0xa, "foot"), (0xc, "mpv")]);
+    hypr.event("activewindowv2>>c", all.clone());
+    assert_eq!(next_window_list(&mut rx).await, vec![0xc, 0xb, 0xa]);
+
+    hypr.event("activewindow>>foot,foot", all.clone());
+    hypr.event("activewindowv2>>a", all.clone());
+    assert_eq!(next_window_list(&mut rx).await, vec![0xa, 0xc, 0xb]);
+
+    // Focusing the overlay itself changes nothing; closing a window prunes it.
+    hypr.event("activewindowv2>>ff", all);
+    hypr.event(
+        "closewindow>>c",
+        clients(&[(0xb, "firefox"), (0xa, "foot")]),
+    );
+    assert_eq!(next_window_list(&mut rx).await, vec![0xa, 0xb]);
+}
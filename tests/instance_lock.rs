@@ -0,0 +1,75 @@
+use qalttab::instance::{acquire, is_live, lock_path};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::UnixListener;
+
+fn fresh(socket_path: &str) -> &Path {
+    let path = Path::new(socket_path);
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(lock_path(path));
+    path
+}
+
+#[tokio::test]
+async fn second_instance_is_refused_until_the_first_exits() {
+    let socket_path = fresh("/tmp/q_instance_refused.sock");
+    let first = acquire(Some(socket_path), false).await.unwrap();
+    assert_eq!(
+        std::fs::read_to_string(first.path()).unwrap(),
+        std::process::id().to_string()
+    );
+
+    let err = acquire(Some(socket_path), false).await.unwrap_err();
+    assert!(err.to_string().contains("does not answer"), "{err:#}");
+
+    drop(first);
+    acquire(Some(socket_path), false).await.unwrap();
+}
+
+#[tokio::test]
+async fn stale_socket_does_not_block_startup() {
+    let socket_path = fresh("/tmp/q_instance_stale.sock");
+    // Bound, then dropped without removing the file, as after a crash.
+    drop(std::os::unix::net::UnixListener::bind(socket_path).unwrap());
+    assert!(socket_path.exists());
+    assert!(!is_live(socket_path).await);
+
+    acquire(Some(socket_path), false).await.unwrap();
+}
+
+#[tokio::test]
+async fn live_instance_is_refused_without_replace() {
+    let socket_path = fresh("/tmp/q_instance_live.sock");
+    let _first = acquire(Some(socket_path), false).await.unwrap();
+    let _listener = UnixListener::bind(socket_path).unwrap();
+
+    let err = acquire(Some(socket_path), false).await.unwrap_err();
+    assert!(err.to_string().contains("--replace"), "{err:#}");
+}
+
+#[tokio::test]
+async fn replace_asks_the_running_instance_to_quit() {
+    let socket_path = fresh("/tmp/q_instance_replace.sock");
+    let first = acquire(Some(socket_path), false).await.unwrap();
+    let listener = UnixListener::bind(socket_path).unwrap();
+
+    // Stands in for the running instance: quits on the first request.
+    let running = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = vec![0; 256];
+        let n = stream.read(&mut request).await.unwrap();
+        drop((stream, listener, first));
+        String::from_utf8_lossy(&request[..n]).into_owned()
+    });
+
+    let second = tokio::time::timeout(Duration::from_secs(5), acquire(Some(socket_path), true))
+        .await
+        .expect("timed out taking over")
+        .unwrap();
+    assert!(running.await.unwrap().contains(r#""message_type":"quit""#));
+    assert_eq!(
+        std::fs::read_to_string(second.path()).unwrap(),
+        std::process::id().to_string()
+    );
+}
@@ -11,7 +11,7 @@ fn parses_client_focus_with_windows() {
         });
         let (mt, wins, _fi) = parse_ipc_message(&serde_json::to_vec(&payload).unwrap()).unwrap();
         assert_eq!(wins.len(), 1);
-        assert_eq!(wins[0].get("id").unwrap(), id);
+        assert_eq!(wins[0].id.to_string(), id);
         let expected = if m_type == "client_focus" {
             MessageType::ClientFocus
         } else {
@@ -1,9 +1,11 @@
-use qalttab::ipc::listen;
-use qalttab::ui::AppEvent;
+use qalttab::config::LiveConfig;
+use qalttab::ipc::{DEFAULT_MAX_FRAME_SIZE, IpcContext, listen};
+use qalttab::ui::{AppEvent, OverlayEvent, SharedState};
 use serde_json::json;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixStream;
 use tokio::sync::mpsc;
 
@@ -16,12 +18,15 @@ async fn run_single_server_test(socket_path: &str, m_type: &str) {
         let _ = std::fs::remove_file(path);
     }
 
-    let ctx = egui::Context::default();
-    let tx_clone = tx.clone();
+    let ipc = IpcContext::new(
+        tx.clone(),
+        egui::Context::default(),
+        Arc::new(Mutex::new(SharedState::default())),
+        LiveConfig::default(),
+    );
     let path_clone = path.to_owned();
-    let ctx_clone = ctx.clone();
     tokio::spawn(async move {
-        let _ = listen(tx_clone, ctx_clone, Some(&path_clone)).await;
+        let _ = listen(ipc, Some(&path_clone), DEFAULT_MAX_FRAME_SIZE).await;
     });
 
     tokio::time::sleep(Duration::from_millis(50)).await;
@@ -62,12 +67,15 @@ async fn server_handles_sequential_messages() {
         let _ = std::fs::remove_file(path);
     }
 
-    let ctx = egui::Context::default();
-    let tx_clone = tx.clone();
+    let ipc = IpcContext::new(
+        tx.clone(),
+        egui::Context::default(),
+        Arc::new(Mutex::new(SharedState::default())),
+        LiveConfig::default(),
+    );
     let path_clone = path.to_owned();
-    let ctx_clone = ctx.clone();
     tokio::spawn(async move {
-        let _ = listen(tx_clone, ctx_clone, Some(&path_clone)).await;
+        let _ = listen(ipc, Some(&path_clone), DEFAULT_MAX_FRAME_SIZE).await;
     });
 
     tokio::time::sleep(Duration::from_millis(50)).await;
@@ -88,3 +96,237 @@ async fn server_handles_sequential_messages() {
         assert!(matches!(event, Some(AppEvent::UnixSocketMsg(_))));
     }
 }
+
+#[tokio::test]
+async fn server_reassembles_payload_sent_in_chunks() {
+    let socket_path = "/tmp/q_srv_chunked.sock";
+    let (tx, mut rx) = mpsc::unbounded_channel::<AppEvent>();
+    let path = Path::new(socket_path);
+    if path.exists() {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let ipc = IpcContext::new(
+        tx,
+        egui::Context::default(),
+        Arc::new(Mutex::new(SharedState::default())),
+        LiveConfig::default(),
+    );
+    let path_clone = path.to_owned();
+    tokio::spawn(async move {
+        let _ = listen(ipc, Some(&path_clone), DEFAULT_MAX_FRAME_SIZE).await;
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let windows: Vec<_> = (0..80)
+        .map(|i| {
+            json!({
+                "id": i.to_string(),
+                "name": format!("{} - Mozilla Firefox", "a very long tab title ".repeat(8)),
+                "class": "firefox",
+                "group_name": "www",
+                "group_label": "",
+            })
+        })
+        .collect();
+    let mut payload =
+        serde_json::to_vec(&json!({ "message_type": "cycle_windows", "windows": windows }))
+            .unwrap();
+    payload.push(b'\n');
+    assert!(payload.len() > 8192);
+
+    let mut stream = UnixStream::connect(socket_path).await.unwrap();
+    for chunk in payload.chunks(1000) {
+        stream.write_all(chunk).await.unwrap();
+        stream.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    let mut buf = [0; 256];
+    let n = stream.read(&mut buf).await.unwrap();
+    let reply: serde_json::Value = serde_json::from_slice(&buf[..n]).unwrap();
+    assert_eq!(reply["status"], "ok");
+
+    let event = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+        .await
+        .expect("timed out waiting for event");
+    match event {
+        Some(AppEvent::UnixSocketMsg(r)) => assert_eq!(r.windows.len(), 80),
+        other => panic!("expected UnixSocketMsg, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn server_replies_with_error_for_oversized_frame() {
+    let socket_path = "/tmp/q_srv_too_large.sock";
+    let (tx, mut rx) = mpsc::unbounded_channel::<AppEvent>();
+    let path = Path::new(socket_path);
+    if path.exists() {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let ipc = IpcContext::new(
+        tx,
+        egui::Context::default(),
+        Arc::new(Mutex::new(SharedState::default())),
+        LiveConfig::default(),
+    );
+    let path_clone = path.to_owned();
+    tokio::spawn(async move {
+        let _ = listen(ipc, Some(&path_clone), 128).await;
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut stream = UnixStream::connect(socket_path).await.unwrap();
+    let payload = json!({
+        "message_type": "cycle_windows",
+        "windows": [{ "id": "1", "name": "x".repeat(512) }]
+    });
+    stream
+        .write_all(&serde_json::to_vec(&payload).unwrap())
+        .await
+        .unwrap();
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await.unwrap();
+    let reply: serde_json::Value = serde_json::from_slice(&reply).unwrap();
+    assert_eq!(reply["status"], "frame_too_large");
+    assert!(reply["error"].as_str().unwrap().contains("128"));
+
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), rx.recv())
+            .await
+            .is_err(),
+        "oversized frame must not reach the event loop"
+    );
+}
+
+#[tokio::test]
+async fn server_keeps_connection_open_across_requests() {
+    let socket_path = "/tmp/q_srv_persistent.sock";
+    let (tx, mut rx) = mpsc::unbounded_channel::<AppEvent>();
+    let path = Path::new(socket_path);
+    if path.exists() {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let ipc = IpcContext::new(
+        tx,
+        egui::Context::default(),
+        Arc::new(Mutex::new(SharedState::default())),
+        LiveConfig::default(),
+    );
+    let path_clone = path.to_owned();
+    tokio::spawn(async move {
+        let _ = listen(ipc, Some(&path_clone), DEFAULT_MAX_FRAME_SIZE).await;
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let stream = UnixStream::connect(socket_path).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut replies = BufReader::new(reader).lines();
+
+    let requests = [
+        json!({ "id": 1, "message_type": "client_focus", "windows": [] }),
+        json!({ "id": 2, "message_type": "no_such_type", "windows": [] }),
+        json!({ "id": 3, "message_type": "cycle_windows" }),
+        json!({ "id": "four", "message_type": "cycle_windows", "windows": [] }),
+    ];
+    let expected = [
+        (json!(1), "ok"),
+        (json!(2), "unknown_message_type"),
+        (json!(3), "parse_error"),
+        (json!("four"), "ok"),
+    ];
+    for (request, (id, status)) in requests.iter().zip(expected) {
+        let mut bytes = serde_json::to_vec(request).unwrap();
+        bytes.push(b'\n');
+        writer.write_all(&bytes).await.unwrap();
+
+        let line = tokio::time::timeout(Duration::from_secs(1), replies.next_line())
+            .await
+            .expect("timed out waiting for reply")
+            .unwrap()
+            .expect("connection closed early");
+        let reply: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(reply["id"], id);
+        assert_eq!(reply["status"], status);
+    }
+
+    // Only the two valid requests reach the event loop
+    for _ in 0..2 {
+        let event = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("timed out waiting for event");
+        assert!(matches!(event, Some(AppEvent::UnixSocketMsg(_))));
+    }
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn subscriber_receives_overlay_events() {
+    let socket_path = "/tmp/q_srv_subscribe.sock";
+    let (tx, _rx) = mpsc::unbounded_channel::<AppEvent>();
+    let path = Path::new(socket_path);
+    if path.exists() {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let ipc = IpcContext::new(
+        tx,
+        egui::Context::default(),
+        Arc::new(Mutex::new(SharedState::default())),
+        LiveConfig::default(),
+    );
+    let events = ipc.events.clone();
+    let path_clone = path.to_owned();
+    tokio::spawn(async move {
+        let _ = listen(ipc, Some(&path_clone), DEFAULT_MAX_FRAME_SIZE).await;
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let stream = UnixStream::connect(socket_path).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    writer
+        .write_all(b"{\"id\":1,\"message_type\":\"subscribe\"}\n")
+        .await
+        .unwrap();
+
+    let mut next_line = async || {
+        let line = tokio::time::timeout(Duration::from_secs(1), lines.next_line())
+            .await
+            .expect("timed out waiting for line")
+            .unwrap()
+            .expect("connection closed early");
+        serde_json::from_str::<serde_json::Value>(&line).unwrap()
+    };
+
+    let reply = next_line().await;
+    assert_eq!(reply["id"], 1);
+    assert_eq!(reply["status"], "ok");
+
+    events
+        .send(OverlayEvent::Shown {
+            focus_index: 1,
+            window: None,
+        })
+        .unwrap();
+    events.send(OverlayEvent::Hidden).unwrap();
+
+    let shown = next_line().await;
+    assert_eq!(shown["event"], "shown");
+    assert_eq!(shown["focus_index"], 1);
+    assert_eq!(next_line().await["event"], "hidden");
+
+    // Requests are still answered on a subscribed connection
+    writer
+        .write_all(b"{\"id\":2,\"message_type\":\"get_version\"}\n")
+        .await
+        .unwrap();
+    let reply = next_line().await;
+    assert_eq!(reply["id"], 2);
+    assert_eq!(reply["status"], "ok");
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{Discovery, MockQtileClient, cycle, next_event, start};
+use qalttab::qaltd::{ScriptStep, ScriptedKeySource, forward_key_events};
+use qalttab::ui::OverlayEvent;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn cycle_then_release_hides_and_focuses_the_selection() {
+    let mut harness = start(MockQtileClient::default(), Discovery::Skip);
+    harness.tx.send(cycle(1)).unwrap();
+    assert!(matches!(
+        next_event(&mut harness.events).await,
+        OverlayEvent::Shown { focus_index: 1, .. }
+    ));
+
+    let source = ScriptedKeySource::from_steps([ScriptStep::Release]);
+    forward_key_events(
+        Box::new(source),
+        harness.tx.clone(),
+        egui::Context::default(),
+    )
+    .await
+    .unwrap();
+
+    match next_event(&mut harness.events).await {
+        OverlayEvent::WindowFocused { window } => assert_eq!(window.id, 2),
+        other => panic!("expected window_focused, got {other:?}"),
+    }
+    assert!(matches!(
+        next_event(&mut harness.events).await,
+        OverlayEvent::Hidden
+    ));
+
+    let calls = harness
+        .client
+        .wait_for_calls(|calls| calls.iter().any(|c| c.starts_with("fire_user_hook")))
+        .await;
+    let position = |needle: &str| calls.iter().position(|c| c.contains(needle));
+    let shown = position("window[42].togroup()").expect("overlay was never shown");
+    let hidden = position("window[42].togroup(qalttab)").expect("overlay was never hidden");
+    let focused = position("window[2].focus()").expect("selection was never focused");
+    let raised = position("window[2].bring_to_front()").expect("selection was never raised");
+    let hook = position("fire_user_hook(alt_release)").unwrap();
+    assert!(
+        shown < hidden && hidden < focused && focused < raised && raised < hook,
+        "calls out of order: {calls:?}"
+    );
+    assert!(!harness.shared.lock().unwrap().is_visible);
+}
+
+#[tokio::test]
+async fn release_without_cycle_does_nothing() {
+    let harness = start(MockQtileClient::default(), Discovery::Skip);
+    let source = ScriptedKeySource::from_steps([ScriptStep::Release]);
+    forward_key_events(
+        Box::new(source),
+        harness.tx.clone(),
+        egui::Context::default(),
+    )
+    .await
+    .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(harness.client.calls().is_empty());
+}
+
+#[tokio::test]
+async fn cycling_again_before_the_hide_keeps_the_overlay_open() {
+    let mut harness = start(MockQtileClient::default(), Discovery::Skip);
+    let (steps_tx, steps_rx) = mpsc::unbounded_channel();
+    tokio::spawn(forward_key_events(
+        Box::new(ScriptedKeySource::from_channel(steps_rx)),
+        harness.tx.clone(),
+        egui::Context::default(),
+    ));
+
+    harness.tx.send(cycle(1)).unwrap();
+    next_event(&mut harness.events).await;
+    steps_tx.send(ScriptStep::Release).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    harness.tx.send(cycle(2)).unwrap();
+    assert!(matches!(
+        next_event(&mut harness.events).await,
+        OverlayEvent::SelectionChanged { focus_index: 2, .. }
+    ));
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(harness.shared.lock().unwrap().is_visible);
+    assert!(
+        !harness
+            .client
+            .calls()
+            .iter()
+            .any(|c| c.starts_with("fire_user_hook"))
+    );
+}
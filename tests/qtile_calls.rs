@@ -0,0 +1,339 @@
+mod common;
+
+use common::{Discovery, MockQtileClient, OVERLAY_WID, cycle, next_event, start};
+use qalttab::qtile::QtileBackend;
+use qalttab::ui::{
+    AppEvent, ControlCommand, MessageType, OverlayEvent, Response, SearchEdit, SharedState,
+    WindowInfo, resize_overlay,
+};
+use qalttab::wm::WmQueue;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// How the overlay is hidden under Qtile.
+const HIDE: &str = "window[42].togroup(qalttab)";
+
+fn focused(ids: &[u64], focus_index: usize) -> AppEvent {
+    AppEvent::UnixSocketMsg(Response {
+        message_type: MessageType::ClientFocus,
+        windows: ids
+            .iter()
+            .map(|&id| WindowInfo {
+                id,
+                ..WindowInfo::default()
+            })
+            .collect(),
+        focus_index: Some(focus_index),
+    })
+}
+
+#[tokio::test]
+async fn discovery_retries_until_our_window_appears_then_hides_it() {
+    let client = MockQtileClient::default();
+    client.reply_once("windows", json!([{"id": 7, "name": "firefox"}]));
+    client.fail_once("windows", "Qtile is not running");
+    let harness = start(client, Discovery::Run);
+
+    let calls = harness
+        .client
+        .wait_for_calls(|calls| calls.iter().any(|c| c == HIDE))
+        .await;
+    assert_eq!(
+        calls,
+        vec![
+            "windows()",
+            "windows()",
+            "windows()",
+            "addgroup(qalttab)",
+            HIDE,
+        ]
+    );
+    assert_eq!(
+        harness.shared.lock().unwrap().cached_wid,
+        Some(OVERLAY_WID.to_string())
+    );
+}
+
+#[tokio::test]
+async fn cycle_shows_and_centres_the_overlay_with_the_default_size() {
+    let mut harness = start(MockQtileClient::default(), Discovery::Run);
+    harness
+        .client
+        .wait_for_calls(|calls| calls.len() == 3)
+        .await;
+
+    harness.tx.send(cycle(1)).unwrap();
+    assert!(matches!(
+        next_event(&mut harness.events).await,
+        OverlayEvent::Shown { focus_index: 1, .. }
+    ));
+    let calls = harness
+        .client
+        .wait_for_calls(|calls| calls.len() == 8)
+        .await;
+    assert_eq!(
+        calls[3..],
+        [
+            "window[42].togroup()",
+            "screen.info()",
+            "window[42].place(810, 340, 300, 400, 0, None)",
+            "window[42].keep_above(True)",
+            "window[42].bring_to_front()",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn client_focus_during_a_cycle_hides_without_the_user_hook() {
+    let mut harness = start(MockQtileClient::default(), Discovery::Run);
+    harness.tx.send(cycle(1)).unwrap();
+    next_event(&mut harness.events).await;
+
+    harness
+        .tx
+        .send(AppEvent::UnixSocketMsg(Response {
+            message_type: MessageType::ClientFocus,
+            windows: vec![],
+            focus_index: None,
+        }))
+        .unwrap();
+    assert!(matches!(
+        next_event(&mut harness.events).await,
+        OverlayEvent::Hidden
+    ));
+
+    let calls = harness
+        .client
+        .wait_for_calls(|calls| calls.iter().filter(|c| *c == HIDE).count() == 2)
+        .await;
+    assert_eq!(calls.last().unwrap(), HIDE, "{calls:?}");
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(
+        !harness
+            .client
+            .calls()
+            .iter()
+            .any(|c| c.starts_with("fire_user_hook"))
+    );
+}
+
+#[tokio::test]
+async fn releasing_the_modifier_focuses_the_selected_window() {
+    let mut harness = start(MockQtileClient::default(), Discovery::Run);
+    harness.tx.send(cycle(1)).unwrap();
+    next_event(&mut harness.events).await;
+
+    harness.tx.send(AppEvent::AltReleased).unwrap();
+    let calls = harness
+        .client
+        .wait_for_calls(|calls| calls.iter().any(|c| c.starts_with("fire_user_hook")))
+        .await;
+    let last_hide = calls
+        .iter()
+        .rposition(|c| c == "addgroup(qalttab)")
+        .unwrap();
+    assert_eq!(
+        calls[last_hide..],
+        [
+            "addgroup(qalttab)",
+            HIDE,
+            "window[2].focus()",
+            "window[2].bring_to_front()",
+            "fire_user_hook(alt_release)",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn resize_places_the_overlay_only_when_its_height_changes() {
+    let harness = start(MockQtileClient::default(), Discovery::Run);
+    harness
+        .client
+        .wait_for_calls(|calls| calls.len() == 3)
+        .await;
+
+    resize_overlay(&harness.queue, &harness.shared, 500, 200);
+    resize_overlay(&harness.queue, &harness.shared, 520, 200);
+    resize_overlay(&harness.queue, &harness.shared, 500, 300);
+    let calls = harness
+        .client
+        .wait_for_calls(|calls| calls.len() == 11)
+        .await;
+    let places: Vec<_> = calls.iter().filter(|c| c.contains(".place(")).collect();
+    assert_eq!(
+        places,
+        vec![
+            "window[42].place(710, 440, 500, 200, 0, None)",
+            "window[42].place(710, 390, 500, 300, 0, None)",
+        ]
+    );
+    let state = harness.shared.lock().unwrap();
+    assert_eq!((state.last_width, state.last_height), (500, 300));
+}
+
+#[tokio::test]
+async fn resize_before_discovery_does_nothing() {
+    let client = Arc::new(MockQtileClient::default());
+    let (events, _) = broadcast::channel(4);
+    let queue = WmQueue::start(Arc::new(QtileBackend::new(client.clone())), events);
+    let shared = Arc::new(Mutex::new(SharedState::default()));
+
+    resize_overlay(&queue, &shared, 500, 200);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(client.calls().is_empty());
+    assert_eq!(shared.lock().unwrap().last_height, 0);
+}
+
+#[tokio::test]
+async fn failed_show_is_reported_to_subscribers() {
+    let mut harness = start(MockQtileClient::default(), Discovery::Run);
+    harness
+        .client
+        .wait_for_calls(|calls| calls.len() == 3)
+        .await;
+    // Discovery has hidden the overlay, so this fails the unhide.
+    harness.client.fail_once("togroup", "window 42 is gone");
+
+    harness.tx.send(cycle(0)).unwrap();
+    assert!(matches!(
+        next_event(&mut harness.events).await,
+        OverlayEvent::Shown { .. }
+    ));
+    match next_event(&mut harness.events).await {
+        OverlayEvent::CommandFailed { operation, error } => {
+            assert_eq!(operation, "show overlay 42 at 300x400");
+            assert!(error.contains("window 42 is gone"), "{error}");
+        }
+        other => panic!("expected command_failed, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn cycle_without_a_window_list_opens_on_the_focus_history() {
+    let mut harness = start(MockQtileClient::default(), Discovery::Run);
+    // The hook lists windows in its own order; only the focused one matters.
+    harness.tx.send(focused(&[1, 2, 3], 0)).unwrap();
+    harness.tx.send(focused(&[1, 2, 3], 2)).unwrap();
+    harness.tx.send(focused(&[3, 2, 1], 1)).unwrap();
+    harness
+        .tx
+        .send(AppEvent::Control(ControlCommand::Next))
+        .unwrap();
+
+    match next_event(&mut harness.events).await {
+        OverlayEvent::Shown {
+            focus_index: 1,
+            window: Some(window),
+        } => assert_eq!(window.id, 3),
+        other => panic!("expected the overlay on window 3, got {other:?}"),
+    }
+    let state = harness.shared.lock().unwrap();
+    let ids: Vec<u64> = state.last_windows.iter().map(|w| w.id).collect();
+    assert_eq!(ids, vec![2, 3, 1]);
+}
+
+#[tokio::test]
+async fn overlay_keys_move_close_and_accept_the_selection() {
+    let mut harness = start(MockQtileClient::default(), Discovery::Run);
+    harness.tx.send(cycle(0)).unwrap();
+    next_event(&mut harness.events).await;
+
+    let control = |command| AppEvent::Control(command);
+    harness.tx.send(control(ControlCommand::Next)).unwrap();
+    assert!(matches!(
+        next_event(&mut harness.events).await,
+        OverlayEvent::SelectionChanged { focus_index: 1, .. }
+    ));
+
+    // Closing the selected window keeps the overlay open on its neighbour.
+    harness.tx.send(control(ControlCommand::Close)).unwrap();
+    match next_event(&mut harness.events).await {
+        OverlayEvent::WindowClosed { window } => assert_eq!(window.id, 2),
+        other => panic!("expected window_closed, got {other:?}"),
+    }
+    match next_event(&mut harness.events).await {
+        OverlayEvent::SelectionChanged {
+            focus_index: 1,
+            window: Some(window),
+        } => assert_eq!(window.id, 3),
+        other => panic!("expected selection_changed, got {other:?}"),
+    }
+
+    harness.tx.send(control(ControlCommand::Accept)).unwrap();
+    match next_event(&mut harness.events).await {
+        OverlayEvent::WindowFocused { window } => assert_eq!(window.id, 3),
+        other => panic!("expected window_focused, got {other:?}"),
+    }
+    assert!(matches!(
+        next_event(&mut harness.events).await,
+        OverlayEvent::Hidden
+    ));
+
+    let calls = harness
+        .client
+        .wait_for_calls(|calls| calls.iter().any(|c| c == "window[3].bring_to_front()"))
+        .await;
+    let tail: Vec<_> = calls
+        .iter()
+        .skip_while(|c| !c.ends_with(".kill()"))
+        .collect();
+    assert_eq!(
+        tail,
+        [
+            "window[2].kill()",
+            "addgroup(qalttab)",
+            HIDE,
+            "window[3].focus()",
+            "window[3].bring_to_front()",
+        ]
+    );
+    assert!(!harness.shared.lock().unwrap().is_visible);
+}
+
+#[tokio::test]
+async fn typing_filters_the_cards_and_selects_the_best_match() {
+    let mut harness = start(MockQtileClient::default(), Discovery::Run);
+    harness.tx.send(cycle(1)).unwrap();
+    next_event(&mut harness.events).await;
+
+    let search = |edit| AppEvent::Search(edit);
+    harness
+        .tx
+        .send(search(SearchEdit::Type("win3".into())))
+        .unwrap();
+    match next_event(&mut harness.events).await {
+        OverlayEvent::SelectionChanged {
+            focus_index: 0,
+            window: Some(window),
+        } => assert_eq!(window.id, 3),
+        other => panic!("expected the selection on window 3, got {other:?}"),
+    }
+    assert_eq!(harness.shared.lock().unwrap().visible_windows().len(), 1);
+
+    // Backspace widens the filter again, best match first.
+    harness.tx.send(search(SearchEdit::Backspace)).unwrap();
+    next_event(&mut harness.events).await;
+    assert_eq!(harness.shared.lock().unwrap().visible_windows().len(), 3);
+
+    harness
+        .tx
+        .send(search(SearchEdit::Type("3".into())))
+        .unwrap();
+    next_event(&mut harness.events).await;
+    harness.tx.send(AppEvent::AltReleased).unwrap();
+    match next_event(&mut harness.events).await {
+        OverlayEvent::WindowFocused { window } => assert_eq!(window.id, 3),
+        other => panic!("expected window_focused, got {other:?}"),
+    }
+    assert!(matches!(
+        next_event(&mut harness.events).await,
+        OverlayEvent::Hidden
+    ));
+    assert!(harness.shared.lock().unwrap().query.is_empty());
+    harness
+        .client
+        .wait_for_calls(|calls| calls.iter().any(|c| c == "window[3].focus()"))
+        .await;
+}
@@ -0,0 +1,285 @@
+mod common;
+
+use common::next_window_list;
+use qalttab::config::{Anchor, PlacementConfig, ScreenPolicy};
+use qalttab::sway::{
+    GET_OUTPUTS, GET_TREE, RUN_COMMAND, SUBSCRIBE, SwayBackend, WINDOW_EVENT, read_message,
+    write_message,
+};
+use qalttab::ui::OverlayEvent;
+use qalttab::wm::{WindowManager, WmOp, WmQueue};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UnixListener;
+use tokio::sync::{broadcast, mpsc};
+
+/// Answers i3 IPC requests from a scripted tree and records commands.
+/// Commands naming `con_id=99` fail like they would for a missing window.
+/// There are two active outputs side by side, the left one focused.
+struct FakeSway {
+    path: PathBuf,
+    tree: Arc<Mutex<Value>>,
+    commands: Arc<Mutex<Vec<String>>>,
+    events: broadcast::Sender<Value>,
+}
+
+impl FakeSway {
+    fn start(name: &str, tree: Value) -> Self {
+        let path = common::fake_socket_path("sway", name);
+        let listener = UnixListener::bind(&path).unwrap();
+        let fake = Self {
+            path,
+            tree: Arc::new(Mutex::new(tree)),
+            commands: Arc::default(),
+            events: broadcast::channel(16).0,
+        };
+        let (tree, commands, events) = (
+            fake.tree.clone(),
+            fake.commands.clone(),
+            fake.events.clone(),
+        );
+        common::serve(listener, move |stream| {
+            let (tree, commands, events) = (tree.clone(), commands.clone(), events.clone());
+            async move {
+                let (mut reader, writer) = stream.into_split();
+                let writer = Arc::new(tokio::sync::Mutex::new(writer));
+                while let Ok((kind, payload)) = read_message(&mut reader).await {
+                    let reply = match kind {
+                        GET_TREE => tree.lock().unwrap().clone(),
+                        GET_OUTPUTS => json!([
+                            {"name": "eDP-1", "active": false, "focused": false,
+                             "rect": {"x": 0, "y": 0, "width": 0, "height": 0}},
+                            {"name": "DP-1", "active": true, "focused": true,
+                             "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080}},
+                            {"name": "DP-2", "active": true, "focused": false,
+                             "rect": {"x": 1920, "y": 0, "width": 2560, "height": 1440}},
+                        ]),
+                        RUN_COMMAND => {
+                            let command = String::from_utf8(payload).unwrap();
+                            let missing = command.contains("con_id=99");
+                            commands.lock().unwrap().push(command);
+                            if missing {
+                                json!([{"success": false, "error": "No matching node"}])
+                            } else {
+                                json!([{"success": true}])
+                            }
+                        }
+                        SUBSCRIBE => {
+                            let mut events = events.subscribe();
+                            let writer = writer.clone();
+                            tokio::spawn(async move {
+                                while let Ok(event) = events.recv().await {
+                                    let payload = serde_json::to_vec(&event).unwrap();
+                                    let mut writer = writer.lock().await;
+                                    write_message(&mut *writer, WINDOW_EVENT, &payload)
+                                        .await
+                                        .unwrap();
+                                }
+                            });
+                            json!({"success": true})
+                        }
+                        _ => json!({"success": false}),
+                    };
+                    let payload = serde_json::to_vec(&reply).unwrap();
+                    let mut writer = writer.lock().await;
+                    if write_message(&mut *writer, kind, &payload).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        fake
+    }
+
+    fn backend(&self) -> Arc<SwayBackend> {
+        self.backend_with(PlacementConfig::default())
+    }
+
+    fn backend_with(&self, placement: PlacementConfig) -> Arc<SwayBackend> {
+        Arc::new(SwayBackend::new(self.path.clone()).with_placement(placement))
+    }
+
+    fn commands(&self) -> Vec<String> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    /// Replace the tree, then announce the change like sway would.
+    fn window_event(&self, change: &str, tree: Value) {
+        *self.tree.lock().unwrap() = tree;
+        self.events
+            .send(json!({"change": change, "container": {}}))
+            .unwrap();
+    }
+}
+
+impl Drop for FakeSway {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// A workspace holding `windows` as `(con_id, name)`, with `focused` focused.
+/// Each window starts 1920 px right of the one before. The overlay (con 12)
+/// always sits in the scratchpad.
+fn tree(windows: &[(u64, &str)], focused: u64) -> Value {
+    let nodes: Vec<Value> = windows
+        .iter()
+        .enumerate()
+        .map(|(i, (id, name))| {
+            json!({
+                "id": id,
+                "type": "con",
+                "name": name,
+                "app_id": name,
+                "focused": *id == focused,
+                "rect": {"x": 1920 * i, "y": 0, "width": 800, "height": 600},
+            })
+        })
+        .collect();
+    json!({
+        "id": 1, "type": "root", "nodes": [
+            {"id": 2, "type": "output", "name": "__i3", "nodes": [
+                {"id": 3, "type": "workspace", "name": "__i3_scratch", "floating_nodes": [
+                    {"id": 12, "type": "floating_con", "name": "qalttab", "app_id": "qalttab"},
+                ]},
+            ]},
+            {"id": 4, "type": "output", "name": "DP-1", "nodes": [
+                {"id": 5, "type": "workspace", "name": "web", "nodes": nodes},
+            ]},
+        ],
+    })
+}
+
+#[tokio::test]
+async fn finds_the_overlay_in_the_scratchpad() {
+    let sway = FakeSway::start("find", tree(&[(10, "foot")], 10));
+    let backend = sway.backend();
+    assert_eq!(backend.find_window("qalttab").await.unwrap(), Some(12));
+    assert_eq!(backend.find_window("missing").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn queued_ops_become_commands_and_failures_are_published() {
+    let sway = FakeSway::start("commands", tree(&[(10, "foot")], 10));
+    let (events, mut failures) = broadcast::channel(8);
+    let queue = WmQueue::start(sway.backend(), events);
+    queue.push(WmOp::Show {
+        wid: "12".into(),
+        width: 300,
+        height: 400,
+        selected: None,
+    });
+    queue.push(WmOp::Hide { wid: "12".into() });
+    queue.push(WmOp::Commit { wid: Some(99) });
+    queue.push(WmOp::Kill { wid: 10 });
+
+    match tokio::time::timeout(Duration::from_secs(2), failures.recv())
+        .await
+        .unwrap()
+        .unwrap()
+    {
+        OverlayEvent::CommandFailed { operation, error } => {
+            assert_eq!(operation, "commit selection of window 99");
+            assert!(error.contains("No matching node"), "{error}");
+        }
+        other => panic!("expected command_failed, got {other:?}"),
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(
+        sway.commands(),
+        vec![
+            "[con_id=12] focus, resize set width 300 px height 400 px, move position center",
+            "[con_id=12] move scratchpad",
+            "[con_id=99] focus",
+            "[con_id=10] kill",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn placement_moves_the_overlay_to_the_picked_output() {
+    let sway = FakeSway::start("placement", tree(&[(10, "foot"), (11, "firefox")], 10));
+    let run = |placement: PlacementConfig, ops: Vec<WmOp>| {
+        let backend = sway.backend_with(placement);
+        async move {
+            for op in ops {
+                backend.run(op).await.unwrap();
+            }
+        }
+    };
+    let show = WmOp::Show {
+        wid: "12".into(),
+        width: 300,
+        height: 400,
+        selected: Some(10),
+    };
+    let follow = WmOp::Follow {
+        wid: "12".into(),
+        width: 300,
+        height: 400,
+        selected: 11,
+    };
+
+    // The second active output, against its top edge.
+    let index = PlacementConfig {
+        screen: ScreenPolicy::Index,
+        screen_index: 1,
+        anchor: Anchor::Top,
+        offset_y: 20,
+        ..PlacementConfig::default()
+    };
+    run(index, vec![show.clone(), follow.clone()]).await;
+    // Following the selection from window 10 on the left to 11 on the right.
+    let selection = PlacementConfig {
+        screen: ScreenPolicy::Selection,
+        ..PlacementConfig::default()
+    };
+    run(selection, vec![show, follow]).await;
+    assert_eq!(
+        sway.commands(),
+        vec![
+            "[con_id=12] focus, resize set width 300 px height 400 px, \
+             move absolute position 3050 px 20 px",
+            "[con_id=12] focus, resize set width 300 px height 400 px, \
+             move absolute position 810 px 340 px",
+            "[con_id=12] resize set width 300 px height 400 px, \
+             move absolute position 3050 px 520 px",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn focus_events_build_the_mru_list() {
+    let sway = FakeSway::start(
+        "watch",
+        tree(&[(10, "foot"), (11, "firefox"), (13, "mpv")], 11),
+    );
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let backend = sway.backend();
+    tokio::spawn(async move { backend.watch_focus(tx).await });
+
+    // The focused window leads, the overlay is left out.
+    assert_eq!(next_window_list(&mut rx).await, vec![11, 10, 13]);
+
+    sway.window_event(
+        "focus",
+        tree(&[(10, "foot"), (11, "firefox"), (13, "mpv")], 13),
+    );
+    assert_eq!(next_window_list(&mut rx).await, vec![13, 11, 10]);
+
+    sway.window_event(
+        "focus",
+        tree(&[(10, "foot"), (11, "firefox"), (13, "mpv")], 10),
+    );
+    assert_eq!(next_window_list(&mut rx).await, vec![10, 13, 11]);
+
+    // A title change alone sends nothing; closing a window prunes it.
+    sway.window_event(
+        "title",
+        tree(&[(10, "vim"), (11, "firefox"), (13, "mpv")], 10),
+    );
+    sway.window_event("close", tree(&[(10, "vim"), (11, "firefox")], 10));
+    assert_eq!(next_window_list(&mut rx).await, vec![10, 11]);
+}